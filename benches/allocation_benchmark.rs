@@ -0,0 +1,131 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use ssrrr::algorithm::process::chunked::{calculate_chunked, DEFAULT_CHUNK_MS};
+use ssrrr::algorithm::process::preprocess::preprocess_file;
+use ssrrr::algorithm::process::process::calculate;
+use ssrrr::Calculator;
+
+/// System allocator counting allocations and allocated bytes
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATED_BYTES: AtomicUsize = AtomicUsize::new(0);
+static LIVE_BYTES: AtomicUsize = AtomicUsize::new(0);
+static PEAK_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+/// Tracks the live byte count and its high-water mark
+fn track_live(added: usize, removed: usize) {
+    let live = LIVE_BYTES.fetch_add(added, Ordering::Relaxed) + added - removed;
+    LIVE_BYTES.fetch_sub(removed, Ordering::Relaxed);
+    PEAK_BYTES.fetch_max(live, Ordering::Relaxed);
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        track_live(layout.size(), 0);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        track_live(0, layout.size());
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(new_size, Ordering::Relaxed);
+        track_live(new_size, layout.size());
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+/// Allocations and bytes allocated while running `f`
+fn count_allocations<R>(f: impl FnOnce() -> R) -> (usize, usize, R) {
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let bytes = ALLOCATED_BYTES.load(Ordering::Relaxed);
+    let result = f();
+    (
+        ALLOCATIONS.load(Ordering::Relaxed) - allocations,
+        ALLOCATED_BYTES.load(Ordering::Relaxed) - bytes,
+        result,
+    )
+}
+
+/// Peak live bytes above the starting point while running `f`
+fn peak_memory<R>(f: impl FnOnce() -> R) -> (usize, R) {
+    let base = LIVE_BYTES.load(Ordering::Relaxed);
+    PEAK_BYTES.store(base, Ordering::Relaxed);
+    let result = f();
+    (PEAK_BYTES.load(Ordering::Relaxed) - base, result)
+}
+
+/// Compares the free function with a warm pooled `Calculator`
+fn benchmark_allocations(c: &mut Criterion) {
+    let files = [
+        "assets/benchmark.osu",
+        "assets/test.osu",
+        "assets/Various Artist - Moonlight 1.25x (flashnias) [Synthesis pattern].osu",
+    ];
+    let maps: Vec<_> = files.iter()
+        .map(|file| preprocess_file(file, "None").expect("preprocess ok"))
+        .collect();
+
+    // Warm the pool over the whole set so every buffer size has been seen once
+    let calculator = Calculator::new();
+    for map in &maps {
+        calculator.calculate(map).expect("calculate ok");
+    }
+
+    for (file, map) in files.iter().zip(maps.iter()) {
+        let (free_allocs, free_bytes, free) = count_allocations(|| calculate(map).expect("calculate ok"));
+        let (pooled_allocs, pooled_bytes, pooled) = count_allocations(|| calculator.calculate(map).expect("calculate ok"));
+        assert_eq!(free.rating.to_bits(), pooled.rating.to_bits(), "pooled rating differs for {}", file);
+        println!(
+            "{}: free fn {} allocs / {} KiB, pooled {} allocs / {} KiB",
+            file,
+            free_allocs,
+            free_bytes / 1024,
+            pooled_allocs,
+            pooled_bytes / 1024,
+        );
+    }
+
+    // Peak memory of the one-shot and chunked calculations
+    for (file, map) in files.iter().zip(maps.iter()) {
+        let (one_shot_peak, one_shot) = peak_memory(|| calculate(map).expect("calculate ok"));
+        let (chunked_peak, chunked) = peak_memory(|| calculate_chunked(map, DEFAULT_CHUNK_MS).expect("calculate ok"));
+        assert!((one_shot.rating - chunked.rating).abs() < 1e-9, "chunked rating differs for {}", file);
+        println!(
+            "{}: one-shot peak {} KiB, chunked peak {} KiB",
+            file,
+            one_shot_peak / 1024,
+            chunked_peak / 1024,
+        );
+    }
+
+    c.bench_function("free_fn_library", |b| {
+        b.iter(|| {
+            for map in &maps {
+                black_box(calculate(black_box(map)).expect("calculate ok"));
+            }
+        })
+    });
+
+    c.bench_function("pooled_calculator_library", |b| {
+        b.iter(|| {
+            for map in &maps {
+                black_box(calculator.calculate(black_box(map)).expect("calculate ok"));
+            }
+        })
+    });
+}
+
+criterion_group!(benches, benchmark_allocations);
+criterion_main!(benches);
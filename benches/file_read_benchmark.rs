@@ -12,6 +12,25 @@ fn benchmark_file_read_speed(c: &mut Criterion) {
             black_box(starts.len())
         })
     });
+
+    // Same file streamed through `Parser::from_reader`, from disk and from memory
+    c.bench_function("file_read_parser_from_reader", |b| {
+        b.iter(|| {
+            let file = std::fs::File::open(black_box(benchmark_file)).unwrap();
+            let parser = ssrrr::file_parser::builder::Parser::from_reader(std::io::BufReader::new(file)).unwrap();
+            let (_cc, _cols, starts, _ends, _types, _od) = parser.get_parsed_data();
+            black_box(starts.len())
+        })
+    });
+
+    let bytes = std::fs::read(benchmark_file).unwrap();
+    c.bench_function("memory_parser_from_reader", |b| {
+        b.iter(|| {
+            let parser = ssrrr::file_parser::builder::Parser::from_reader(black_box(&bytes[..])).unwrap();
+            let (_cc, _cols, starts, _ends, _types, _od) = parser.get_parsed_data();
+            black_box(starts.len())
+        })
+    });
 }
 
 criterion_group!(benches, benchmark_file_read_speed);
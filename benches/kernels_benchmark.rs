@@ -0,0 +1,36 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use std::hint::black_box;
+use ssrrr::algorithm::calculations::corners::get_corners;
+use ssrrr::algorithm::interpolation::{interp_values, step_interp};
+use ssrrr::algorithm::process::preprocess::preprocess_file;
+use ssrrr::algorithm::smoothing::{smooth_on_corners, SmoothMode};
+use ssrrr::algorithm::sums::cumulative_sum;
+
+/// Benchmarks the smoothing, cumulative sum and interpolation kernels on real corners
+///
+/// Their equivalence with the scalar and bisecting reference versions is covered by the
+/// unit tests of each module.
+fn benchmark_kernels(c: &mut Criterion) {
+    let map = preprocess_file("assets/benchmark.osu", "None").expect("preprocess ok");
+    let (all_corners, base_corners, _) = get_corners(map.total_duration, &map.notes);
+    let values: Vec<f64> = base_corners.iter()
+        .enumerate()
+        .map(|(i, &x)| (x * 0.013).sin().abs() * 40.0 + (i % 7) as f64)
+        .collect();
+
+    c.bench_function("cumulative_sum", |b| {
+        b.iter(|| black_box(cumulative_sum(black_box(&base_corners), &values)))
+    });
+    c.bench_function("smooth_on_corners", |b| {
+        b.iter(|| black_box(smooth_on_corners(black_box(&base_corners), &values, 500.0, 0.001, SmoothMode::Sum)))
+    });
+    c.bench_function("interp_values", |b| {
+        b.iter(|| black_box(interp_values(black_box(&all_corners), &base_corners, &values)))
+    });
+    c.bench_function("step_interp", |b| {
+        b.iter(|| black_box(step_interp(black_box(&all_corners), &base_corners, &values)))
+    });
+}
+
+criterion_group!(benches, benchmark_kernels);
+criterion_main!(benches);
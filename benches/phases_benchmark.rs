@@ -10,31 +10,32 @@ fn benchmark_phases(c: &mut Criterion) {
     // Phase 1
     c.bench_function("phase1_data_prep", |b| {
         b.iter(|| {
-            let out = ssrrr::algorithm::process::process::phase1(black_box(&map_data));
+            let out = ssrrr::algorithm::process::process::phase1(black_box(&map_data)).expect("phase1 ok");
             black_box(out.0.len() + out.1.len() + out.2.len())
         })
     });
 
-    let (all_corners, base_corners, a_corners, _key_usage, active_columns, _key_usage_400, anchor) =
-        ssrrr::algorithm::process::process::phase1(&map_data);
+    let (all_corners, base_corners, a_corners, key_usage, active_columns, _key_usage_400, anchor) =
+        ssrrr::algorithm::process::process::phase1(&map_data).expect("phase1 ok");
 
     // Phase 2
     c.bench_function("phase2_bars", |b| {
         b.iter(|| {
             let out = ssrrr::algorithm::process::process::phase2(
                 black_box(&map_data),
+                black_box(&key_usage),
                 black_box(&active_columns),
                 black_box(&a_corners),
                 black_box(&base_corners),
                 black_box(&all_corners),
                 black_box(&anchor),
-            );
+            ).expect("phase2 ok");
             black_box(out.0.len() + out.1.len() + out.2.len() + out.3.len() + out.4.len())
         })
     });
 
     let (jbar, xbar, pbar, abar, rbar, c_arr, ks_arr) =
-        ssrrr::algorithm::process::process::phase2(&map_data, &active_columns, &a_corners, &base_corners, &all_corners, &anchor);
+        ssrrr::algorithm::process::process::phase2(&map_data, &key_usage, &active_columns, &a_corners, &base_corners, &all_corners, &anchor).expect("phase2 ok");
 
     // Phase 3
     c.bench_function("phase3_final_values", |b| {
@@ -0,0 +1,63 @@
+use std::fs;
+use std::path::Path;
+use std::time::Instant;
+use ssrrr::algorithm::process::approximate::{calculate_approximate, FAST_GRID_MS, FINE_GRID_MS};
+use ssrrr::algorithm::process::process::calculate;
+use ssrrr::algorithm::process::preprocess::preprocess_file;
+
+fn main() {
+    let test_dir = Path::new("assets");
+
+    let mut files: Vec<_> = match fs::read_dir(test_dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().and_then(|s| s.to_str()) == Some("osu"))
+            .collect(),
+        Err(e) => {
+            eprintln!("Error reading directory: {}", e);
+            return;
+        }
+    };
+    files.sort();
+
+    // Error of each grid against the exact rating, over every map and mod
+    let grids = [FINE_GRID_MS, FAST_GRID_MS, 50];
+    let mut worst = vec![0.0f64; grids.len()];
+    for path in &files {
+        let file_name = path.file_name().and_then(|name| name.to_str()).unwrap_or("unknown");
+        for mod_name in ["None", "DT", "HT"] {
+            let map_data = match preprocess_file(&path.to_string_lossy(), mod_name) {
+                Ok(map_data) => map_data,
+                Err(e) => {
+                    println!("{} [{}] | PARSE ERROR: {}", file_name, mod_name, e);
+                    continue;
+                }
+            };
+
+            let start = Instant::now();
+            let exact = calculate(&map_data).expect("exact rating").rating;
+            let exact_time = start.elapsed();
+            println!("{} [{}] | exact {:.4} ({:.1} ms)", file_name, mod_name, exact, exact_time.as_secs_f64() * 1000.0);
+
+            for (grid, worst) in grids.iter().zip(worst.iter_mut()) {
+                let start = Instant::now();
+                let approx = calculate_approximate(&map_data, *grid).expect("approximate rating").rating;
+                let approx_time = start.elapsed();
+                let error = approx - exact;
+                *worst = worst.max(error.abs());
+                println!(
+                    "    grid {:>2} ms | {:.4} (error {:+.4}, {:+.2}%) ({:.1} ms)",
+                    grid,
+                    approx,
+                    error,
+                    100.0 * error / exact,
+                    approx_time.as_secs_f64() * 1000.0,
+                );
+            }
+        }
+    }
+
+    for (grid, worst) in grids.iter().zip(worst.iter()) {
+        println!("grid {:>2} ms | worst absolute error {:.4}", grid, worst);
+    }
+}
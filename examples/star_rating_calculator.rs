@@ -1,61 +1,100 @@
-use std::fs;
+use std::io;
 use std::path::Path;
+use ssrrr::algorithm::process::batch::rate_directory;
+use ssrrr::algorithm::process::preprocess::preprocess_reader;
 use ssrrr::algorithm::process::process::calculate;
-use ssrrr::algorithm::process::preprocess::preprocess_file;
+use ssrrr::{preprocess_stepmania_file, rate_osz};
+use ssrrr::types::{write_csv, BatchOptions};
 
 fn main() {
     let test_dir = Path::new("assets");
-    
+    let csv = std::env::args().any(|arg| arg == "--csv");
+
+    // `-` rates a single map piped on stdin instead of the directory
+    if std::env::args().any(|arg| arg == "-") {
+        match preprocess_reader(io::stdin().lock(), "None").and_then(|map_data| calculate(&map_data)) {
+            Ok(rating) => println!("stdin | {:.4}", rating.rating),
+            Err(e) => println!("stdin | ERROR: {}", e),
+        }
+        return;
+    }
+
+    // A .osz argument rates the mania difficulties of that mapset
+    if let Some(osz) = std::env::args().skip(1).find(|arg| arg.to_ascii_lowercase().ends_with(".osz")) {
+        match rate_osz(&osz, "None") {
+            Ok(mapset) => {
+                println!("{} - {} ({})", mapset.artist, mapset.title, mapset.creator);
+                for difficulty in &mapset.difficulties {
+                    match &difficulty.outcome {
+                        Ok(rating) => println!("[{}] | {:.4} ({}K)", difficulty.metadata.version, rating.rating, rating.column_count),
+                        Err(e) => println!("[{}] | ERROR: {}", difficulty.metadata.version, e),
+                    }
+                }
+            }
+            Err(e) => eprintln!("Error reading {}: {}", osz, e),
+        }
+        return;
+    }
+
+    // A .sm/.ssc argument rates the dance-single and dance-double charts of that simfile
+    if let Some(simfile) = std::env::args().skip(1).find(|arg| {
+        let arg = arg.to_ascii_lowercase();
+        arg.ends_with(".sm") || arg.ends_with(".ssc")
+    }) {
+        match preprocess_stepmania_file(&simfile, "None") {
+            Ok(simfile) => {
+                println!("{} - {}", simfile.artist, simfile.title);
+                for chart in &simfile.charts {
+                    match calculate(&chart.map_data) {
+                        Ok(rating) => println!("[{} {}] | {:.4} ({}K)", chart.steps_type, chart.difficulty, rating.rating, chart.map_data.column_count),
+                        Err(e) => println!("[{} {}] | ERROR: {}", chart.steps_type, chart.difficulty, e),
+                    }
+                }
+            }
+            Err(e) => eprintln!("Error reading {}: {}", simfile, e),
+        }
+        return;
+    }
+
     if !test_dir.exists() {
         eprintln!("Test directory not found: {:?}", test_dir);
         return;
     }
-    
-    // Read all .osu files in the Test directory
-    let entries = match fs::read_dir(test_dir) {
-        Ok(entries) => entries,
+
+    let options = BatchOptions::new().with_progress(|progress| {
+        eprintln!("[{}/{}] {}", progress.completed, progress.total, progress.path.display());
+    });
+
+    // Rate every .osu file under the directory on a worker pool
+    let results = match rate_directory(test_dir, &options) {
+        Ok(stream) => stream.into_sorted(),
         Err(e) => {
             eprintln!("Error reading directory: {}", e);
             return;
         }
     };
-    
-    for entry in entries {
-        let entry = match entry {
-            Ok(entry) => entry,
-            Err(e) => {
-                eprintln!("Error reading entry: {}", e);
-                continue;
+
+    if csv {
+        if let Err(e) = write_csv(&results, io::stdout().lock()) {
+            eprintln!("Error writing CSV: {}", e);
+        }
+        return;
+    }
+
+    for result in &results {
+        let file_name = result.path.file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or("unknown");
+        match &result.outcome {
+            Ok(rating) => {
+                println!("{} | {:.4} ({} notes, {} long notes)",
+                         file_name,
+                         rating.rating,
+                         rating.note_count,
+                         rating.long_note_count);
             }
-        };
-        
-        let path = entry.path();
-        if path.extension().and_then(|s| s.to_str()) == Some("osu") {
-            let file_path = path.to_string_lossy();
-            let file_name = path.file_name()
-                .and_then(|name| name.to_str())
-                .unwrap_or("unknown");
-            
-            
-            // Calculate star rating using the new improved method
-            match preprocess_file(&file_path, "None") {
-                Ok(map_data) => {
-                    match calculate(&map_data) {
-                        Ok(result) => {
-                            println!("{} | {:.4} ({} notes, {} long notes)", 
-                                     file_name,
-                                     result.rating, 
-                                     map_data.note_count(), 
-                                     map_data.long_note_count());
-                        }
-                        Err(e) => {
-                            println!("{} | CALCULATION ERROR: {}", file_name, e);
-                        }
-                    }
-                }
-                Err(e) => {
-                    println!("{} | PARSE ERROR: {}", file_name, e);
-                }
+            Err(e) => {
+                println!("{} | ERROR: {}", file_name, e);
             }
         }
     }
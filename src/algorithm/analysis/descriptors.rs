@@ -0,0 +1,69 @@
+use crate::algorithm::process::process::{compute_effective_weights, phase1, phase2, phase3, sort_by_difficulty};
+use crate::params::{spikiness, switch, variety};
+use crate::types::{CalculationError, MapData, MapDescriptors, StarRatingResult};
+
+/// Computes the pattern descriptors of a map
+///
+/// Rhythm variety is taken from the note sequence alone; spikiness and hand-switch
+/// rate are computed over the `d_all`, effective weight and `ks_arr` curves.
+///
+/// # Arguments
+/// * `map_data` - Parsed map data (mods already applied)
+///
+/// # Returns
+/// Map descriptors
+pub fn map_descriptors(map_data: &MapData) -> StarRatingResult<MapDescriptors> {
+    if map_data.notes.len() < 2 {
+        return Err(CalculationError::EmptyData("map_descriptors: at least two notes are required".to_string()).into());
+    }
+
+    let (all_corners, base_corners, a_corners, key_usage, active_columns, _key_usage_400, anchor) = phase1(map_data)?;
+    let (jbar, xbar, pbar, abar, rbar, c_arr, ks_arr) = phase2(map_data, &key_usage, &active_columns, &a_corners, &base_corners, &all_corners, &anchor)?;
+    let (_s_all, _t_all, d_all) = phase3(&jbar, &xbar, &pbar, &abar, &rbar, &c_arr, &ks_arr);
+
+    let weights = compute_effective_weights(&c_arr, &all_corners);
+    let (d_sorted, w_sorted) = sort_by_difficulty(&d_all, &weights);
+
+    Ok(MapDescriptors {
+        rhythm_variety: variety(&map_data.notes, &map_data.notes_by_column)?,
+        spikiness: spikiness(&d_sorted, &w_sorted)?,
+        hand_switch: switch(&map_data.notes, &map_data.tail_sequence, &all_corners, &ks_arr, &weights)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithm::process::preprocess::preprocess_file;
+    use crate::types::StarRatingError;
+
+    #[test]
+    fn maps_need_two_notes() {
+        let mut map_data = preprocess_file("assets/test.osu", "None").unwrap();
+        map_data.notes.truncate(1);
+        assert!(matches!(map_descriptors(&map_data), Err(StarRatingError::CalculationError(CalculationError::EmptyData(_)))));
+    }
+
+    #[test]
+    fn descriptors_stay_in_their_documented_ranges() {
+        for file in ["assets/test.osu", "assets/benchmark.osu"] {
+            let descriptors = map_descriptors(&preprocess_file(file, "None").unwrap()).unwrap();
+            assert!(descriptors.rhythm_variety > 0.0, "{}: {:?}", file, descriptors);
+            assert!(descriptors.spikiness > 0.0, "{}: {:?}", file, descriptors);
+            assert!((0.5..1.5).contains(&descriptors.hand_switch), "{}: {:?}", file, descriptors);
+        }
+    }
+
+    #[test]
+    fn descriptors_match_the_params_functions() {
+        let map_data = preprocess_file("assets/test.osu", "None").unwrap();
+        let descriptors = map_descriptors(&map_data).unwrap();
+        let expected = variety(&map_data.notes, &map_data.notes_by_column).unwrap();
+        assert_eq!(descriptors.rhythm_variety.to_bits(), expected.to_bits());
+
+        // Per-column gaps are pooled, so the column order does not matter
+        let mut reordered = map_data.clone();
+        reordered.notes_by_column.reverse();
+        assert_eq!(variety(&reordered.notes, &reordered.notes_by_column).unwrap().to_bits(), expected.to_bits());
+    }
+}
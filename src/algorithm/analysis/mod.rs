@@ -0,0 +1,2 @@
+pub mod descriptors;
+pub mod weakness;
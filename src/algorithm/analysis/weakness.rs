@@ -0,0 +1,267 @@
+use crate::algorithm::process::process::{compute_gaps, phase1, phase2, phase3};
+use crate::types::{
+    BarSnapshot, CalculationError, JudgementResult, MapData, NoteJudgement, SkillKind, SkillShares,
+    SkillSummary, StarRatingResult, WeaknessEntry, WeaknessReport,
+};
+
+/// Options for the weakness report
+#[derive(Debug, Clone, Default)]
+pub struct WeaknessOptions {
+    /// Absolute hit error (ms) above which a hit is flagged.
+    /// Defaults to the osu!mania 300 window of the map's OD (64 - 3 * OD).
+    pub large_error_threshold: Option<f64>,
+}
+
+/// Builds a weakness report from per-note replay judgements
+///
+/// Every miss and every hit whose absolute error exceeds the threshold is placed on
+/// the per-corner `d_all` curve, and the local difficulty is split between the skills
+/// feeding it (jack, stream, LN release, technical). Judgements in a column the map
+/// does not have are rejected with `IndexOutOfBounds`.
+///
+/// # Arguments
+/// * `map_data` - Map data the replay was played on (mods already applied)
+/// * `judgements` - Per-note judgements from the replay
+/// * `options` - Report options
+///
+/// # Returns
+/// Weakness report with one entry per flagged judgement
+pub fn weakness_report(
+    map_data: &MapData,
+    judgements: &[NoteJudgement],
+    options: &WeaknessOptions,
+) -> StarRatingResult<WeaknessReport> {
+    if map_data.notes.is_empty() {
+        return Err(CalculationError::EmptyData("weakness_report: map has no notes".to_string()).into());
+    }
+    // a judgement from another key mode means the replay does not belong to this map
+    if let Some(judgement) = judgements.iter().find(|j| j.column >= map_data.column_count) {
+        return Err(CalculationError::IndexOutOfBounds(
+            "weakness_report: judgement column".to_string(),
+            judgement.column,
+            map_data.column_count.saturating_sub(1),
+        )
+        .into());
+    }
+
+    let (all_corners, base_corners, a_corners, key_usage, active_columns, _key_usage_400, anchor) = phase1(map_data)?;
+    let (jbar, xbar, pbar, abar, rbar, c_arr, ks_arr) = phase2(map_data, &key_usage, &active_columns, &a_corners, &base_corners, &all_corners, &anchor)?;
+    let (s_all, t_all, d_all) = phase3(&jbar, &xbar, &pbar, &abar, &rbar, &c_arr, &ks_arr);
+
+    let gaps = compute_gaps(&all_corners);
+    let weights: Vec<f64> = c_arr.iter().zip(gaps.iter()).map(|(c, g)| c * g).collect();
+
+    // Weighted distribution of d_all, used to place each judgement in the map
+    let mut order: Vec<usize> = (0..d_all.len()).collect();
+    order.sort_unstable_by(|&i, &j| d_all[i].total_cmp(&d_all[j]));
+    let d_sorted: Vec<f64> = order.iter().map(|&i| d_all[i]).collect();
+    let mut cum_weights = Vec::with_capacity(order.len() + 1);
+    cum_weights.push(0.0);
+    for &i in &order {
+        let last = *cum_weights.last().expect("Vecteur non vide attendu");
+        cum_weights.push(last + weights[i]);
+    }
+    let total_weight = *cum_weights.last().expect("Vecteur non vide attendu");
+
+    let shares_at = |i: usize| -> SkillShares {
+        skill_shares(jbar[i], pbar[i], abar[i], rbar[i], c_arr[i], ks_arr[i], s_all[i], t_all[i], d_all[i])
+    };
+
+    // Reference share of each skill over the whole map
+    let mut map_shares = SkillShares::default();
+    if total_weight > 0.0 {
+        for (i, &w) in weights.iter().enumerate() {
+            let shares = shares_at(i);
+            map_shares.jack += shares.jack * w;
+            map_shares.stream += shares.stream * w;
+            map_shares.release += shares.release * w;
+            map_shares.technical += shares.technical * w;
+        }
+        map_shares.jack /= total_weight;
+        map_shares.stream /= total_weight;
+        map_shares.release /= total_weight;
+        map_shares.technical /= total_weight;
+    }
+
+    let threshold = options
+        .large_error_threshold
+        .unwrap_or_else(|| (64.0 - 3.0 * map_data.overall_difficulty).max(16.0));
+
+    let mut flagged: Vec<&NoteJudgement> = judgements.iter()
+        .filter(|j| match j.result {
+            JudgementResult::Miss => true,
+            JudgementResult::Hit(error) => error.abs() > threshold,
+        })
+        .collect();
+    flagged.sort_by(|a, b| a.time.total_cmp(&b.time).then(a.column.cmp(&b.column)));
+
+    let mut entries = Vec::with_capacity(flagged.len());
+    for judgement in flagged {
+        let idx = all_corners
+            .partition_point(|&v| v < judgement.time)
+            .min(all_corners.len() - 1);
+        let difficulty = d_all[idx];
+        let below = d_sorted.partition_point(|&v| v < difficulty);
+        let difficulty_percentile = if total_weight > 0.0 { cum_weights[below] / total_weight } else { 0.0 };
+        let shares = shares_at(idx);
+        entries.push(WeaknessEntry {
+            judgement: *judgement,
+            difficulty,
+            difficulty_percentile,
+            bars: BarSnapshot {
+                jbar: jbar[idx],
+                xbar: xbar[idx],
+                pbar: pbar[idx],
+                abar: abar[idx],
+                rbar: rbar[idx],
+            },
+            shares,
+            dominant_skill: shares.dominant(),
+        });
+    }
+
+    let mut summaries: Vec<SkillSummary> = SkillKind::ALL.iter()
+        .map(|&skill| SkillSummary {
+            skill,
+            misses: entries.iter()
+                .filter(|e| e.dominant_skill == Some(skill) && e.judgement.result == JudgementResult::Miss)
+                .count(),
+            large_errors: entries.iter()
+                .filter(|e| e.dominant_skill == Some(skill) && e.judgement.result != JudgementResult::Miss)
+                .count(),
+            map_share: map_shares.get(skill),
+        })
+        .collect();
+    summaries.sort_by_key(|s| std::cmp::Reverse(s.misses + s.large_errors));
+
+    // Weakest skill: the one whose share of flagged judgements most exceeds its share of the map
+    let flagged_total = entries.len() as f64;
+    let weakest_skill = summaries.iter()
+        .filter(|s| s.misses + s.large_errors > 0)
+        .map(|s| {
+            let flagged_share = (s.misses + s.large_errors) as f64 / flagged_total;
+            (s.skill, flagged_share / s.map_share.max(1e-9))
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(skill, _)| skill);
+
+    let mean_percentile = if entries.is_empty() {
+        0.0
+    } else {
+        entries.iter().map(|e| e.difficulty_percentile).sum::<f64>() / flagged_total
+    };
+
+    Ok(WeaknessReport {
+        entries,
+        summaries,
+        weakest_skill,
+        mean_percentile,
+        large_error_threshold: threshold,
+    })
+}
+
+/// Splits the local difficulty between skills, following the S/T/D combination of phase3
+///
+/// S is a power mean of a jack term and a press term (Pbar + Rbar); D adds a technical
+/// part driven by T (Xbar). The technical share is the T-driven part of D and the rest is
+/// divided between the S terms proportionally to their weight in S^1.5.
+#[allow(clippy::too_many_arguments)]
+fn skill_shares(j: f64, p: f64, a: f64, r: f64, c: f64, ks: f64, s: f64, t: f64, d: f64) -> SkillShares {
+    if d <= 0.0 || !d.is_finite() {
+        return SkillShares::default();
+    }
+
+    let jack_term = 0.4 * (a.powf(3.0 / ks) * (j.min(8.0 + 0.85 * j))).powf(1.5);
+    let stream_inner = 0.8 * p;
+    let release_inner = r * 35.0 / (c + 8.0);
+    let press_inner = stream_inner + release_inner;
+    let press_term = 0.6 * (a.powf(2.0 / 3.0) * press_inner).powf(1.5);
+    let (stream_term, release_term) = if press_inner > 0.0 {
+        (press_term * stream_inner / press_inner, press_term * release_inner / press_inner)
+    } else {
+        (0.0, 0.0)
+    };
+
+    let technical = (2.7 * s.sqrt() * t.powf(1.5) / d).clamp(0.0, 1.0);
+    let rest = 1.0 - technical;
+    let s_total = jack_term + stream_term + release_term;
+    if s_total <= 0.0 {
+        return SkillShares { technical, ..SkillShares::default() };
+    }
+
+    SkillShares {
+        jack: rest * jack_term / s_total,
+        stream: rest * stream_term / s_total,
+        release: rest * release_term / s_total,
+        technical,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithm::process::preprocess::preprocess_file;
+
+    fn test_map() -> MapData {
+        preprocess_file("assets/test.osu", "None").unwrap()
+    }
+
+    #[test]
+    fn dominant_skill_needs_a_non_zero_share() {
+        assert_eq!(SkillShares::default().dominant(), None);
+        let shares = SkillShares { jack: 0.2, stream: 0.5, release: 0.0, technical: 0.3 };
+        assert_eq!(shares.dominant(), Some(SkillKind::Stream));
+        let tied = SkillShares { jack: 0.0, stream: 0.0, release: 0.5, technical: 0.5 };
+        assert_eq!(tied.dominant(), Some(SkillKind::Release));
+    }
+
+    #[test]
+    fn misses_and_large_errors_are_flagged() {
+        let map_data = test_map();
+        let notes = &map_data.notes;
+        let (early, late) = (notes[notes.len() / 4], notes[3 * notes.len() / 4]);
+        let judgements = [
+            NoteJudgement::hit(notes[0].column, notes[0].hit_time, 5.0),
+            NoteJudgement::miss(late.column, late.hit_time),
+            NoteJudgement::hit(early.column, early.hit_time, -120.0),
+        ];
+        let report = weakness_report(&map_data, &judgements, &WeaknessOptions::default()).unwrap();
+
+        assert_eq!(report.large_error_threshold, 64.0 - 3.0 * map_data.overall_difficulty);
+        // in time order, the 5 ms hit left out
+        let flagged: Vec<NoteJudgement> = report.entries.iter().map(|entry| entry.judgement).collect();
+        assert_eq!(flagged, [judgements[2], judgements[1]]);
+        for entry in &report.entries {
+            assert!((0.0..=1.0).contains(&entry.difficulty_percentile), "{}", entry.difficulty_percentile);
+            let share_sum: f64 = SkillKind::ALL.iter().map(|&skill| entry.shares.get(skill)).sum();
+            assert!((share_sum - 1.0).abs() < 1e-9, "{:?}", entry.shares);
+            assert!(entry.dominant_skill.is_some());
+        }
+        let counted: usize = report.summaries.iter().map(|summary| summary.misses + summary.large_errors).sum();
+        assert_eq!(counted, 2);
+        assert_eq!(report.summaries.iter().map(|summary| summary.misses).sum::<usize>(), 1);
+        assert!(report.weakest_skill.is_some());
+        let map_share: f64 = report.summaries.iter().map(|summary| summary.map_share).sum();
+        assert!((map_share - 1.0).abs() < 1e-9, "{}", map_share);
+    }
+
+    #[test]
+    fn clean_replays_have_no_weakness() {
+        let map_data = test_map();
+        let judgements: Vec<NoteJudgement> = map_data.notes.iter().map(|note| NoteJudgement::hit(note.column, note.hit_time, 3.0)).collect();
+        let options = WeaknessOptions { large_error_threshold: Some(10.0) };
+        let report = weakness_report(&map_data, &judgements, &options).unwrap();
+        assert!(report.entries.is_empty());
+        assert_eq!((report.weakest_skill, report.mean_percentile), (None, 0.0));
+    }
+
+    #[test]
+    fn judgements_outside_the_map_columns_are_errors() {
+        let map_data = test_map();
+        let judgements = [NoteJudgement::miss(map_data.column_count, 1000.0)];
+        assert!(matches!(
+            weakness_report(&map_data, &judgements, &WeaknessOptions::default()),
+            Err(crate::types::StarRatingError::CalculationError(CalculationError::IndexOutOfBounds(_, _, _)))
+        ));
+    }
+}
@@ -1,6 +1,6 @@
-use std::collections::HashMap;
-use crate::algorithm::smoothing::{smooth_on_corners, SmoothMode};
-use crate::types::Note;
+use crate::algorithm::pool::BufferPool;
+use crate::algorithm::smoothing::{smooth_on_corners_with_pool, SmoothMode};
+use crate::types::{ActiveColumns, ColumnMatrix, Note};
 
 /// Computes Abar values for the star rating algorithm
 /// 
@@ -18,46 +18,66 @@ use crate::types::Note;
 /// Vector of Abar values
 pub fn compute_abar(
     k: usize,
-    _t: i64,
+    _t: f64,
     _x: f64,
     _notes_by_column: &[Vec<Note>],
-    active_columns: &[Vec<usize>],
-    delta_ks: &HashMap<usize, Vec<f64>>,
+    active_columns: &ActiveColumns,
+    delta_ks: &ColumnMatrix<f64>,
     a_corners: &[f64],
     base_corners: &[f64]
+) -> Vec<f64> {
+    compute_abar_with_pool(k, _t, _x, _notes_by_column, active_columns, delta_ks, a_corners, base_corners, &mut BufferPool::new())
+}
+
+/// Same as `compute_abar`, with every buffer taken from `pool`
+#[allow(clippy::too_many_arguments)]
+pub fn compute_abar_with_pool(
+    k: usize,
+    _t: f64,
+    _x: f64,
+    _notes_by_column: &[Vec<Note>],
+    active_columns: &ActiveColumns,
+    delta_ks: &ColumnMatrix<f64>,
+    a_corners: &[f64],
+    base_corners: &[f64],
+    pool: &mut BufferPool,
 ) -> Vec<f64> {
     let n = base_corners.len();
-    // dks: k-1 x n
-    let mut dks: Vec<Vec<f64>> = vec![vec![0.0; n]; k.saturating_sub(1)];
+    // dks: k-1 x n, set for each pair of consecutive active columns
+    let dks_columns = k.saturating_sub(1);
+    let mut dks = ColumnMatrix::from_vec(dks_columns, n, pool.take(dks_columns * n, 0.0));
     for i in 0..n {
-        let cols = &active_columns[i];
-        for j in 0..cols.len().saturating_sub(1) {
-            let k0 = cols[j];
-            let k1 = cols[j + 1];
-            let dk0 = delta_ks.get(&k0).unwrap()[i];
-            let dk1 = delta_ks.get(&k1).unwrap()[i];
-            dks[k0][i] = (dk0 - dk1).abs() + 0.4 * ((dk0.max(dk1) - 0.11).max(0.0));
+        let mut cols = active_columns.iter(i);
+        let Some(mut k0) = cols.next() else { continue; };
+        for k1 in cols {
+            let dk0 = delta_ks.get(k0, i);
+            let dk1 = delta_ks.get(k1, i);
+            dks.set(k0, i, (dk0 - dk1).abs() + 0.4 * ((dk0.max(dk1) - 0.11).max(0.0)));
+            k0 = k1;
         }
     }
 
-    let mut a_step = vec![1.0; a_corners.len()];
+    let mut a_step = pool.take(a_corners.len(), 1.0);
     for (i, &s) in a_corners.iter().enumerate() {
         let mut idx = base_corners.partition_point(|&v| v < s);
         if idx >= base_corners.len() { idx = base_corners.len() - 1; }
-        let cols = &active_columns[idx];
-        for j in 0..cols.len().saturating_sub(1) {
-            let k0 = cols[j];
-            let k1 = cols[j + 1];
-            let d_val = dks[k0][idx];
-            let dk0 = delta_ks.get(&k0).unwrap()[idx];
-            let dk1 = delta_ks.get(&k1).unwrap()[idx];
+        let mut cols = active_columns.iter(idx);
+        let Some(mut k0) = cols.next() else { continue; };
+        for k1 in cols {
+            let d_val = dks.get(k0, idx);
+            let dk0 = delta_ks.get(k0, idx);
+            let dk1 = delta_ks.get(k1, idx);
             if d_val < 0.02 {
                 a_step[i] *= (0.75 + 0.5 * dk0.max(dk1)).min(1.0);
             } else if d_val < 0.07 {
                 a_step[i] *= (0.65 + 5.0 * d_val + 0.5 * dk0.max(dk1)).min(1.0);
             }
+            k0 = k1;
         }
     }
-    smooth_on_corners(a_corners, &a_step, 250.0, 1.0, SmoothMode::Avg)
+    pool.give(dks.into_vec());
+    let abar = smooth_on_corners_with_pool(a_corners, &a_step, 250.0, 1.0, SmoothMode::Avg, pool);
+    pool.give(a_step);
+    abar
 }
 
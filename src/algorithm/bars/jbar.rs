@@ -1,6 +1,8 @@
-use std::collections::HashMap;
-use crate::algorithm::smoothing::{smooth_on_corners, SmoothMode};
-use crate::types::Note;
+use crate::algorithm::pool::BufferPool;
+use crate::algorithm::smoothing::{smooth_on_corners_with_pool, SmoothMode};
+use crate::types::{ColumnMatrix, Note};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 
 /// Computes Jbar values for the star rating algorithm
 /// 
@@ -15,65 +17,149 @@ use crate::types::Note;
 /// Tuple (delta_ks, jbar) - deltas per column and Jbar values
 pub fn compute_jbar(
     k: usize,
-    _t: i64,
+    _t: f64,
     x: f64,
     notes_by_column: &[Vec<Note>],
     base_corners: &[f64]
-) -> (HashMap<usize, Vec<f64>>, Vec<f64>) {
-    let n = base_corners.len();
-    let mut j_ks: Vec<Vec<f64>> = vec![vec![0.0; n]; k];
-    let mut delta_ks: Vec<Vec<f64>> = vec![vec![1e9; n]; k];
-    let jack_nerfer = |delta: f64| -> f64 {
-        1.0 - 7e-5 * (0.15 + (delta - 0.08).abs()).powf(-4.0)
-    };
+) -> (ColumnMatrix<f64>, Vec<f64>) {
+    compute_jbar_with_pool(k, _t, x, notes_by_column, base_corners, &mut BufferPool::new())
+}
 
+/// Same as `compute_jbar`, with every buffer taken from `pool`
+pub fn compute_jbar_with_pool(
+    k: usize,
+    _t: f64,
+    x: f64,
+    notes_by_column: &[Vec<Note>],
+    base_corners: &[f64],
+    pool: &mut BufferPool,
+) -> (ColumnMatrix<f64>, Vec<f64>) {
+    let delta_ks = compute_delta_ks_with_pool(k, notes_by_column, base_corners, pool);
+    let jbar = compute_jbar_from_deltas_with_pool(k, x, &delta_ks, base_corners, pool);
+    (delta_ks, jbar)
+}
+
+/// Computes Jbar from deltas already computed by `compute_delta_ks`
+///
+/// # Arguments
+/// * `k` - Number of columns
+/// * `x` - Difficulty parameter
+/// * `delta_ks` - Deltas per column (1e9 where the column has no following note)
+/// * `base_corners` - Reference time points
+/// * `pool` - Buffer pool
+///
+/// # Returns
+/// Jbar values on the base corners
+pub fn compute_jbar_from_deltas_with_pool(
+    k: usize,
+    x: f64,
+    delta_ks: &ColumnMatrix<f64>,
+    base_corners: &[f64],
+    pool: &mut BufferPool,
+) -> Vec<f64> {
+    let n = base_corners.len();
+    let mut j_ks = ColumnMatrix::from_vec(k, n, pool.take(k * n, 0.0));
     let x_quarter = x.powf(0.25);
-    for col in 0..k {
-        let notes = &notes_by_column[col];
-        if notes.len() < 2 { continue; }
-        let mut left_idx = 0usize;
-        let mut right_idx = 0usize;
-        for i in 0..(notes.len() - 1) {
-            let start = notes[i].hit_time as f64;
-            let end = notes[i + 1].hit_time as f64;
-            while left_idx < base_corners.len() && base_corners[left_idx] < start { left_idx += 1; }
-            if right_idx < left_idx { right_idx = left_idx; }
-            while right_idx < base_corners.len() && base_corners[right_idx] < end { right_idx += 1; }
-            if left_idx >= right_idx { continue; }
-            let delta = 0.001 * (end - start);
-            let inv_delta = 1.0 / delta.max(1e-12);
-            let val = inv_delta * (1.0 / (delta + 0.11 * x_quarter).max(1e-12));
-            let j_val = val * jack_nerfer(delta);
-            for idx in left_idx..right_idx { j_ks[col][idx] = j_val; }
-            for idx in left_idx..right_idx { delta_ks[col][idx] = delta; }
-        }
+    #[cfg(not(feature = "parallel"))]
+    for (j_col, delta_col) in j_ks.columns_mut().zip(delta_ks.iter_columns()) {
+        fill_jack_column(delta_col, x_quarter, j_col);
     }
+    #[cfg(feature = "parallel")]
+    j_ks.par_columns_mut()
+        .zip(delta_ks.par_columns())
+        .for_each(|(j_col, delta_col)| fill_jack_column(delta_col, x_quarter, j_col));
 
     // Smooth each column's J_ks
-    let mut jbar_ks: Vec<Vec<f64>> = Vec::with_capacity(k);
-    for col in 0..k { jbar_ks.push(smooth_on_corners(base_corners, &j_ks[col], 500.0, 0.001, SmoothMode::Sum)); }
+    let mut jbar_ks = ColumnMatrix::from_vec(k, n, pool.take(k * n, 0.0));
+    #[cfg(not(feature = "parallel"))]
+    for (out, j_col) in jbar_ks.columns_mut().zip(j_ks.iter_columns()) {
+        let smoothed = smooth_on_corners_with_pool(base_corners, j_col, 500.0, 0.001, SmoothMode::Sum, pool);
+        out.copy_from_slice(&smoothed);
+        pool.give(smoothed);
+    }
+    #[cfg(feature = "parallel")]
+    jbar_ks.par_columns_mut()
+        .zip(j_ks.par_columns())
+        .for_each_init(BufferPool::new, |worker_pool, (out, j_col)| {
+            let smoothed = smooth_on_corners_with_pool(base_corners, j_col, 500.0, 0.001, SmoothMode::Sum, worker_pool);
+            out.copy_from_slice(&smoothed);
+            worker_pool.give(smoothed);
+        });
+    pool.give(j_ks.into_vec());
 
     // Aggregate across columns using weighted average
-    let mut jbar = vec![0.0; n];
-    for i in 0..n {
+    let mut jbar = pool.take(n, 0.0);
+    for (i, value) in jbar.iter_mut().enumerate() {
         let mut num = 0.0;
         let mut den = 0.0;
         for col in 0..k {
-            let v = jbar_ks[col][i];
-            let w = 1.0 / delta_ks[col][i];
+            let v = jbar_ks.get(col, i);
+            let w = 1.0 / delta_ks.get(col, i);
             num += (v.max(0.0).powf(5.0)) * w;
             den += w;
         }
         let val = num / den.max(1e-9);
-        jbar[i] = val.powf(1.0 / 5.0);
+        *value = val.powf(1.0 / 5.0);
     }
+    pool.give(jbar_ks.into_vec());
 
-    // Convert back to HashMap to preserve public API
-    let mut delta_ks_map: HashMap<usize, Vec<f64>> = HashMap::with_capacity(k);
-    for col in 0..k {
-        delta_ks_map.insert(col, delta_ks[col].clone());
+    jbar
+}
+
+/// Fills one column's J values from its deltas over the base corners
+fn fill_jack_column(delta_col: &[f64], x_quarter: f64, j_col: &mut [f64]) {
+    let jack_nerfer = |delta: f64| -> f64 {
+        1.0 - 7e-5 * (0.15 + (delta - 0.08).abs()).powf(-4.0)
+    };
+
+    for (j, &delta) in j_col.iter_mut().zip(delta_col) {
+        // corners without a following note keep the 1e9 sentinel and J = 0
+        if delta >= 1e9 { continue; }
+        let inv_delta = 1.0 / delta.max(1e-12);
+        let val = inv_delta * (1.0 / (delta + 0.11 * x_quarter).max(1e-12));
+        *j = val * jack_nerfer(delta);
     }
-    (delta_ks_map, jbar)
 }
 
+/// Computes the gap to the next note of each column at each base corner
+///
+/// # Arguments
+/// * `k` - Number of columns
+/// * `notes_by_column` - Notes organized by column
+/// * `base_corners` - Reference time points
+///
+/// # Returns
+/// Deltas (s) per column, 1e9 where the column has no following note
+pub fn compute_delta_ks(
+    k: usize,
+    notes_by_column: &[Vec<Note>],
+    base_corners: &[f64]
+) -> ColumnMatrix<f64> {
+    compute_delta_ks_with_pool(k, notes_by_column, base_corners, &mut BufferPool::new())
+}
 
+/// Same as `compute_delta_ks`, with the matrix storage taken from `pool`
+pub fn compute_delta_ks_with_pool(
+    k: usize,
+    notes_by_column: &[Vec<Note>],
+    base_corners: &[f64],
+    pool: &mut BufferPool,
+) -> ColumnMatrix<f64> {
+    let n = base_corners.len();
+    let mut delta_ks = ColumnMatrix::from_vec(k, n, pool.take(k * n, 1e9));
+    for (col, notes) in notes_by_column.iter().enumerate().take(k) {
+        let deltas = delta_ks.column_mut(col);
+        let mut left_idx = 0usize;
+        let mut right_idx = 0usize;
+        for pair in notes.windows(2) {
+            let start = pair[0].hit_time;
+            let end = pair[1].hit_time;
+            while left_idx < n && base_corners[left_idx] < start { left_idx += 1; }
+            if right_idx < left_idx { right_idx = left_idx; }
+            while right_idx < n && base_corners[right_idx] < end { right_idx += 1; }
+            if left_idx >= right_idx { continue; }
+            deltas[left_idx..right_idx].fill(0.001 * (end - start));
+        }
+    }
+    delta_ks
+}
@@ -2,5 +2,6 @@ pub mod abar;
 pub mod jbar;
 pub mod pbar;
 pub mod rbar;
+pub mod skill;
 pub mod xbar;
 
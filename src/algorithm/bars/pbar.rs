@@ -1,4 +1,5 @@
-use crate::algorithm::smoothing::{smooth_on_corners, SmoothMode};
+use crate::algorithm::pool::BufferPool;
+use crate::algorithm::smoothing::{smooth_on_corners_with_pool, SmoothMode};
 use crate::algorithm::calculations::ln::ln_sum;
 use crate::types::Note;
 
@@ -17,15 +18,60 @@ use crate::types::Note;
 /// Vector of Pbar values
 pub fn compute_pbar(
     _k: usize,
-    _t: i64,
+    _t: f64,
     x: f64,
     notes: &[Note],
-    ln_rep: &(Vec<i64>, Vec<f64>, Vec<f64>),
+    ln_rep: &(Vec<f64>, Vec<f64>, Vec<f64>),
     anchor: &[f64],
     base_corners: &[f64]
+) -> Vec<f64> {
+    compute_pbar_with_pool(_k, _t, x, notes, ln_rep, anchor, base_corners, &mut BufferPool::new())
+}
+
+/// Same as `compute_pbar`, with every buffer taken from `pool`
+#[allow(clippy::too_many_arguments)]
+pub fn compute_pbar_with_pool(
+    _k: usize,
+    _t: f64,
+    x: f64,
+    notes: &[Note],
+    ln_rep: &(Vec<f64>, Vec<f64>, Vec<f64>),
+    anchor: &[f64],
+    base_corners: &[f64],
+    pool: &mut BufferPool,
+) -> Vec<f64> {
+    pbar_on_corners(x, notes, ln_rep, anchor, base_corners, false, pool)
+}
+
+/// Same as `compute_pbar_with_pool`, on the evenly spaced corners of the approximate mode
+///
+/// A chord spike lasts 1 ms; a grid cell is wider, so the spike is divided by the
+/// width of the cell holding the chord to keep its smoothed contribution.
+#[allow(clippy::too_many_arguments)]
+pub fn compute_pbar_on_grid_with_pool(
+    _k: usize,
+    _t: f64,
+    x: f64,
+    notes: &[Note],
+    ln_rep: &(Vec<f64>, Vec<f64>, Vec<f64>),
+    anchor: &[f64],
+    grid_corners: &[f64],
+    pool: &mut BufferPool,
+) -> Vec<f64> {
+    pbar_on_corners(x, notes, ln_rep, anchor, grid_corners, true, pool)
+}
+
+fn pbar_on_corners(
+    x: f64,
+    notes: &[Note],
+    ln_rep: &(Vec<f64>, Vec<f64>, Vec<f64>),
+    anchor: &[f64],
+    base_corners: &[f64],
+    on_grid: bool,
+    pool: &mut BufferPool,
 ) -> Vec<f64> {
     let n = base_corners.len();
-    let mut p_step = vec![0.0; n];
+    let mut p_step = pool.take(n, 0.0);
     let stream_booster = |delta: f64| -> f64 {
         let r = 7.5 / delta;
         if 160.0 < r && r < 360.0 {
@@ -43,16 +89,24 @@ pub fn compute_pbar(
     let mut left_idx = 0usize;
     let mut right_idx = 0usize;
     for i in 0..notes.len().saturating_sub(1) {
-        let h_l = notes[i].hit_time as f64;
-        let h_r = notes[i + 1].hit_time as f64;
+        let h_l = notes[i].hit_time;
+        let h_r = notes[i + 1].hit_time;
         let delta_time = h_r - h_l;
         if delta_time.abs() < 1e-9 {
             let spike = 1000.0 * (0.02 * (4.0 / x - 24.0)).powf(0.25);
             while left_idx < base_corners.len() && base_corners[left_idx] < h_l { left_idx += 1; }
+            if on_grid {
+                let cell = if base_corners.get(left_idx) == Some(&h_l) { Some(left_idx) } else { left_idx.checked_sub(1) };
+                if let Some(idx) = cell {
+                    let width = base_corners.get(idx + 1).map_or(1.0, |&next| next - base_corners[idx]);
+                    p_step[idx] += spike / width;
+                }
+                continue;
+            }
             if right_idx < left_idx { right_idx = left_idx; }
             while right_idx < base_corners.len() && base_corners[right_idx] <= h_l { right_idx += 1; }
-            for idx in left_idx..right_idx {
-                p_step[idx] += spike;
+            for value in &mut p_step[left_idx..right_idx] {
+                *value += spike;
             }
             continue;
         }
@@ -80,7 +134,9 @@ pub fn compute_pbar(
         }
     }
 
-    smooth_on_corners(base_corners, &p_step, 500.0, 0.001, SmoothMode::Sum)
+    let pbar = smooth_on_corners_with_pool(base_corners, &p_step, 500.0, 0.001, SmoothMode::Sum, pool);
+    pool.give(p_step);
+    pbar
 }
 
 
@@ -0,0 +1,321 @@
+use crate::algorithm::bars::abar::compute_abar_with_pool;
+use crate::algorithm::bars::jbar::{compute_delta_ks_with_pool, compute_jbar_from_deltas_with_pool};
+use crate::algorithm::bars::pbar::{compute_pbar_on_grid_with_pool, compute_pbar_with_pool};
+use crate::algorithm::bars::rbar::compute_rbar_with_pool;
+use crate::algorithm::bars::xbar::compute_xbar_with_pool;
+use crate::algorithm::calculations::ln::ln_bodies_count_sparse_representation;
+use crate::algorithm::interpolation::interp_values_with_pool;
+use crate::algorithm::pool::{BufferPool, PoolLease, SharedPool};
+use crate::types::{ActiveColumns, ColumnMatrix, MapData, StarRatingResult};
+
+/// Common inputs shared by every skill, built once after phase1
+pub struct SkillContext<'a> {
+    /// Map being rated
+    pub map_data: &'a MapData,
+    /// Number of columns
+    pub column_count: usize,
+    /// Hit leniency
+    pub hit_leniency: f64,
+    /// All corners (output time points of every skill)
+    pub all_corners: &'a [f64],
+    /// Base corners
+    pub base_corners: &'a [f64],
+    /// A corners
+    pub a_corners: &'a [f64],
+    /// Key usage per column at each base corner
+    pub key_usage: &'a ColumnMatrix<bool>,
+    /// Active columns at each base corner
+    pub active_columns: &'a ActiveColumns,
+    /// Anchor values at each base corner
+    pub anchor: &'a [f64],
+    /// Gap (s) to the next note of each column, at each base corner
+    pub delta_ks: ColumnMatrix<f64>,
+    /// True when the corners are the fixed grid of the approximate mode
+    pub grid_corners: bool,
+    /// Scratch buffers shared by the skills
+    pool: SharedPool,
+}
+
+impl<'a> SkillContext<'a> {
+    /// Builds the context from phase1 outputs
+    pub fn new(
+        map_data: &'a MapData,
+        key_usage: &'a ColumnMatrix<bool>,
+        active_columns: &'a ActiveColumns,
+        a_corners: &'a [f64],
+        base_corners: &'a [f64],
+        all_corners: &'a [f64],
+        anchor: &'a [f64],
+    ) -> Self {
+        Self::with_pool(map_data, key_usage, active_columns, a_corners, base_corners, all_corners, anchor, BufferPool::new())
+    }
+
+    /// Builds the context from phase1 outputs, taking scratch buffers from `pool`
+    #[allow(clippy::too_many_arguments)]
+    pub fn with_pool(
+        map_data: &'a MapData,
+        key_usage: &'a ColumnMatrix<bool>,
+        active_columns: &'a ActiveColumns,
+        a_corners: &'a [f64],
+        base_corners: &'a [f64],
+        all_corners: &'a [f64],
+        anchor: &'a [f64],
+        mut pool: BufferPool,
+    ) -> Self {
+        let delta_ks = compute_delta_ks_with_pool(map_data.column_count, &map_data.notes_by_column, base_corners, &mut pool);
+        Self {
+            map_data,
+            column_count: map_data.column_count,
+            hit_leniency: map_data.hit_leniency,
+            all_corners,
+            base_corners,
+            a_corners,
+            key_usage,
+            active_columns,
+            anchor,
+            delta_ks,
+            grid_corners: false,
+            pool: SharedPool::new(pool),
+        }
+    }
+
+    /// Marks the corners as the fixed grid of the approximate mode
+    pub fn with_grid_corners(mut self, grid_corners: bool) -> Self {
+        self.grid_corners = grid_corners;
+        self
+    }
+
+    /// Scratch buffer pool, leased until the guard is dropped; skills may take buffers from it and give them back
+    ///
+    /// Skills evaluated concurrently each get their own pool, so the context stays `Sync`.
+    pub fn pool(&self) -> PoolLease<'_> {
+        self.pool.lease()
+    }
+
+    /// Releases the context, returning its buffers to the pool
+    pub fn into_pool(self) -> BufferPool {
+        let mut pool = self.pool.into_inner();
+        pool.give(self.delta_ks.into_vec());
+        pool
+    }
+}
+
+/// Part of the S/T/D combination an extra skill feeds into
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkillTarget {
+    /// Added to the strain power sum: S = (S^1.5 + weight * v^1.5)^(2/3)
+    Strain,
+    /// Added to Xbar before T is computed: T = a^(3/ks) * (X + weight * v) / (X + weight * v + S + 1)
+    Technical,
+}
+
+/// A difficulty skill evaluated on the corner timeline
+///
+/// The five built-in bars implement this trait; extra skills registered on the
+/// calculator are evaluated the same way and folded into S or T.
+pub trait Skill: Send + Sync {
+    /// Short identifier of the skill
+    fn name(&self) -> &str;
+
+    /// Skill values on `context.all_corners`, or the error that prevented them
+    fn evaluate(&self, context: &SkillContext) -> StarRatingResult<Vec<f64>>;
+
+    /// Part of the combination the skill feeds into (extra skills only)
+    fn target(&self) -> SkillTarget {
+        SkillTarget::Strain
+    }
+
+    /// Weight of the skill in the combination (extra skills only)
+    fn weight(&self) -> f64 {
+        1.0
+    }
+}
+
+/// Same-column repetitions (Jbar)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JackSkill;
+
+/// Adjacent-column coordination (Xbar)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CrossColumnSkill;
+
+/// Pressing intensity (Pbar)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PressingSkill;
+
+/// Unevenness between adjacent columns (Abar)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UnevennessSkill;
+
+/// Long note releases (Rbar)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ReleaseSkill;
+
+impl Skill for JackSkill {
+    fn name(&self) -> &str {
+        "jbar"
+    }
+
+    fn evaluate(&self, context: &SkillContext) -> StarRatingResult<Vec<f64>> {
+        let mut pool = context.pool();
+        let jbar = compute_jbar_from_deltas_with_pool(context.column_count, context.hit_leniency, &context.delta_ks, context.base_corners, &mut pool);
+        let values = interp_values_with_pool(context.all_corners, context.base_corners, &jbar, &mut pool);
+        pool.give(jbar);
+        Ok(values)
+    }
+}
+
+impl Skill for CrossColumnSkill {
+    fn name(&self) -> &str {
+        "xbar"
+    }
+
+    fn evaluate(&self, context: &SkillContext) -> StarRatingResult<Vec<f64>> {
+        let map = context.map_data;
+        let mut pool = context.pool();
+        let xbar = compute_xbar_with_pool(context.column_count, map.total_duration, context.hit_leniency, &map.notes_by_column, context.active_columns, context.base_corners, &mut pool)?;
+        let values = interp_values_with_pool(context.all_corners, context.base_corners, &xbar, &mut pool);
+        pool.give(xbar);
+        Ok(values)
+    }
+}
+
+impl Skill for PressingSkill {
+    fn name(&self) -> &str {
+        "pbar"
+    }
+
+    fn evaluate(&self, context: &SkillContext) -> StarRatingResult<Vec<f64>> {
+        let map = context.map_data;
+        let ln_rep = ln_bodies_count_sparse_representation(&map.long_notes, map.total_duration);
+        let mut pool = context.pool();
+        let compute = if context.grid_corners { compute_pbar_on_grid_with_pool } else { compute_pbar_with_pool };
+        let pbar = compute(context.column_count, map.total_duration, context.hit_leniency, &map.notes, &ln_rep, context.anchor, context.base_corners, &mut pool);
+        let values = interp_values_with_pool(context.all_corners, context.base_corners, &pbar, &mut pool);
+        pool.give(pbar);
+        Ok(values)
+    }
+}
+
+impl Skill for UnevennessSkill {
+    fn name(&self) -> &str {
+        "abar"
+    }
+
+    fn evaluate(&self, context: &SkillContext) -> StarRatingResult<Vec<f64>> {
+        let map = context.map_data;
+        let mut pool = context.pool();
+        let abar = compute_abar_with_pool(context.column_count, map.total_duration, context.hit_leniency, &map.notes_by_column, context.active_columns, &context.delta_ks, context.a_corners, context.base_corners, &mut pool);
+        let values = interp_values_with_pool(context.all_corners, context.a_corners, &abar, &mut pool);
+        pool.give(abar);
+        Ok(values)
+    }
+}
+
+impl Skill for ReleaseSkill {
+    fn name(&self) -> &str {
+        "rbar"
+    }
+
+    fn evaluate(&self, context: &SkillContext) -> StarRatingResult<Vec<f64>> {
+        let map = context.map_data;
+        let mut pool = context.pool();
+        let rbar = compute_rbar_with_pool(context.column_count, map.total_duration, context.hit_leniency, &map.notes_by_column, &map.tail_sequence, context.base_corners, &mut pool);
+        let values = interp_values_with_pool(context.all_corners, context.base_corners, &rbar, &mut pool);
+        pool.give(rbar);
+        Ok(values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithm::process::preprocess::preprocess_file;
+    use crate::algorithm::process::process::{calculate, calculate_with_skills};
+    use crate::types::{CalculationError, StarRatingError};
+
+    /// Extra skill with the same value on every corner
+    struct ConstantSkill {
+        target: SkillTarget,
+        weight: f64,
+        value: f64,
+    }
+
+    impl Skill for ConstantSkill {
+        fn name(&self) -> &str {
+            "constant"
+        }
+
+        fn evaluate(&self, context: &SkillContext) -> StarRatingResult<Vec<f64>> {
+            assert_eq!(context.column_count, context.map_data.column_count);
+            assert_eq!((context.delta_ks.columns(), context.delta_ks.rows()), (context.column_count, context.base_corners.len()));
+            Ok(vec![self.value; context.all_corners.len()])
+        }
+
+        fn target(&self) -> SkillTarget {
+            self.target
+        }
+
+        fn weight(&self) -> f64 {
+            self.weight
+        }
+    }
+
+    struct FailingSkill;
+
+    impl Skill for FailingSkill {
+        fn name(&self) -> &str {
+            "failing"
+        }
+
+        fn evaluate(&self, _context: &SkillContext) -> StarRatingResult<Vec<f64>> {
+            Err(CalculationError::EmptyData("failing skill".to_string()).into())
+        }
+    }
+
+    fn rating_with(skill: ConstantSkill) -> f64 {
+        let map_data = preprocess_file("assets/test.osu", "None").unwrap();
+        calculate_with_skills(&map_data, &[Box::new(skill)]).unwrap().rating
+    }
+
+    #[test]
+    fn built_in_skills_are_named_after_their_bar() {
+        let skills: [&dyn Skill; 5] = [&JackSkill, &CrossColumnSkill, &PressingSkill, &UnevennessSkill, &ReleaseSkill];
+        let names: Vec<&str> = skills.iter().map(|skill| skill.name()).collect();
+        assert_eq!(names, ["jbar", "xbar", "pbar", "abar", "rbar"]);
+        assert!(skills.iter().all(|skill| skill.target() == SkillTarget::Strain && skill.weight() == 1.0));
+    }
+
+    #[test]
+    fn neutral_extra_skills_leave_the_rating_unchanged() {
+        let expected = calculate(&preprocess_file("assets/test.osu", "None").unwrap()).unwrap().rating;
+        let zero_strain = rating_with(ConstantSkill { target: SkillTarget::Strain, weight: 1.0, value: 0.0 });
+        let zero_technical = rating_with(ConstantSkill { target: SkillTarget::Technical, weight: 1.0, value: 0.0 });
+        let unweighted = rating_with(ConstantSkill { target: SkillTarget::Strain, weight: 0.0, value: 5.0 });
+        for rating in [zero_strain, zero_technical, unweighted] {
+            assert_eq!(rating.to_bits(), expected.to_bits());
+        }
+    }
+
+    #[test]
+    fn extra_strain_raises_the_rating() {
+        let light = rating_with(ConstantSkill { target: SkillTarget::Strain, weight: 1.0, value: 1.0 });
+        let heavy = rating_with(ConstantSkill { target: SkillTarget::Strain, weight: 2.0, value: 1.0 });
+        let expected = calculate(&preprocess_file("assets/test.osu", "None").unwrap()).unwrap().rating;
+        assert!(expected < light && light < heavy, "{} {} {}", expected, light, heavy);
+    }
+
+    #[test]
+    fn extra_technical_values_change_the_rating() {
+        let expected = calculate(&preprocess_file("assets/test.osu", "None").unwrap()).unwrap().rating;
+        let technical = rating_with(ConstantSkill { target: SkillTarget::Technical, weight: 1.0, value: 1.0 });
+        assert_ne!(technical.to_bits(), expected.to_bits());
+    }
+
+    #[test]
+    fn skill_errors_fail_the_calculation() {
+        let map_data = preprocess_file("assets/test.osu", "None").unwrap();
+        let result = calculate_with_skills(&map_data, &[Box::new(FailingSkill)]);
+        assert!(matches!(result, Err(StarRatingError::CalculationError(CalculationError::EmptyData(_)))));
+    }
+}
@@ -1,5 +1,34 @@
-use crate::algorithm::smoothing::smooth_on_corners;
-use crate::types::Note;
+use crate::algorithm::pool::BufferPool;
+use crate::algorithm::smoothing::{smooth_on_corners_with_pool, SmoothMode};
+use crate::types::{ActiveColumns, CalculationError, ColumnMatrix, Note, StarRatingResult, MAX_KEY_COUNT};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+/// Cross-column coefficients per key count, as given in the original script
+const CROSS_MATRIX: [&[f64]; 11] = [
+    &[-1.0],
+    &[0.075, 0.075],
+    &[0.125, 0.05, 0.125],
+    &[0.125, 0.125, 0.125, 0.125],
+    &[0.175, 0.25, 0.05, 0.25, 0.175],
+    &[0.175, 0.25, 0.175, 0.175, 0.25, 0.175],
+    &[0.225, 0.35, 0.25, 0.05, 0.25, 0.35, 0.225],
+    &[0.225, 0.35, 0.25, 0.225, 0.225, 0.25, 0.35, 0.225],
+    &[0.275, 0.45, 0.35, 0.25, 0.05, 0.25, 0.35, 0.45, 0.275],
+    &[0.275, 0.45, 0.35, 0.25, 0.275, 0.275, 0.25, 0.35, 0.45, 0.275],
+    &[0.325, 0.55, 0.45, 0.35, 0.25, 0.05, 0.25, 0.35, 0.45, 0.55, 0.325],
+];
+
+// one row of coefficients per supported key count, 0 included
+const _: () = assert!(CROSS_MATRIX.len() == MAX_KEY_COUNT + 1);
+
+/// Cross-column coefficients of a `k`-key map, or `IndexOutOfBounds` above `MAX_KEY_COUNT`
+fn cross_coefficients(k: usize) -> StarRatingResult<&'static [f64]> {
+    CROSS_MATRIX
+        .get(k)
+        .copied()
+        .ok_or_else(|| CalculationError::IndexOutOfBounds("key count".to_string(), k, MAX_KEY_COUNT).into())
+}
 
 /// Computes Xbar values for the star rating algorithm
 /// 
@@ -12,158 +41,146 @@ use crate::types::Note;
 /// * `base_corners` - Reference time points
 /// 
 /// # Returns
-/// Vector of Xbar values
+/// Vector of Xbar values, or `IndexOutOfBounds` for more than `MAX_KEY_COUNT` keys
 pub fn compute_xbar(
     k: usize,
-    _t: i64,
+    _t: f64,
     x: f64,
     notes_by_column: &[Vec<Note>],
-    active_columns: &[Vec<usize>],
+    active_columns: &ActiveColumns,
     base_corners: &[f64]
-) -> Vec<f64> {
-    // cross_matrix given in original script
-    let cross_matrix: Vec<Vec<f64>> = vec![
-        vec![-1.0],
-        vec![0.075, 0.075],
-        vec![0.125, 0.05, 0.125],
-        vec![0.125, 0.125, 0.125, 0.125],
-        vec![0.175, 0.25, 0.05, 0.25, 0.175],
-        vec![0.175, 0.25, 0.175, 0.175, 0.25, 0.175],
-        vec![0.225, 0.35, 0.25, 0.05, 0.25, 0.35, 0.225],
-        vec![0.225, 0.35, 0.25, 0.225, 0.225, 0.25, 0.35, 0.225],
-        vec![0.275, 0.45, 0.35, 0.25, 0.05, 0.25, 0.35, 0.45, 0.275],
-        vec![0.275, 0.45, 0.35, 0.25, 0.275, 0.275, 0.25, 0.35, 0.45, 0.275],
-        vec![0.325, 0.55, 0.45, 0.35, 0.25, 0.05, 0.25, 0.35, 0.45, 0.55, 0.325]
-    ];
-    let cross_coeff = cross_matrix.get(k).expect("cross_matrix[k] exists");
-    let mut cross_complement: Vec<f64> = Vec::with_capacity(cross_coeff.len());
-    for &c in cross_coeff.iter() { cross_complement.push(1.0 - c); }
+) -> StarRatingResult<Vec<f64>> {
+    compute_xbar_with_pool(k, _t, x, notes_by_column, active_columns, base_corners, &mut BufferPool::new())
+}
+
+/// Same as `compute_xbar`, with every buffer taken from `pool`
+pub fn compute_xbar_with_pool(
+    k: usize,
+    _t: f64,
+    x: f64,
+    notes_by_column: &[Vec<Note>],
+    active_columns: &ActiveColumns,
+    base_corners: &[f64],
+    pool: &mut BufferPool,
+) -> StarRatingResult<Vec<f64>> {
+    let cross_coeff = cross_coefficients(k)?;
 
     let n = base_corners.len();
-    let mut x_ks: Vec<Vec<f64>> = vec![vec![0.0; n]; k + 1];
-    let mut fast_cross: Vec<Vec<f64>> = vec![vec![0.0; n]; k + 1];
+    let mut x_ks = ColumnMatrix::from_vec(k + 1, n, pool.take((k + 1) * n, 0.0));
+    let mut fast_cross = ColumnMatrix::from_vec(k + 1, n, pool.take((k + 1) * n, 0.0));
 
-    for col in 0..=k {
-        // two-pointer stream over adjacent columns without allocating a merged vec
-        let (a, b) = if col == 0 {
-            (&notes_by_column[0][..], &[][..])
-        } else if col == k {
-            (&notes_by_column[k - 1][..], &[][..])
-        } else {
-            (&notes_by_column[col - 1][..], &notes_by_column[col][..])
-        };
+    #[cfg(not(feature = "parallel"))]
+    for (col, (x_col, fc_col)) in x_ks.columns_mut().zip(fast_cross.columns_mut()).enumerate() {
+        fill_cross_column(col, k, x, notes_by_column, active_columns, base_corners, cross_coeff, x_col, fc_col);
+    }
+    #[cfg(feature = "parallel")]
+    x_ks.par_columns_mut()
+        .zip(fast_cross.par_columns_mut())
+        .enumerate()
+        .for_each(|(col, (x_col, fc_col))| {
+            fill_cross_column(col, k, x, notes_by_column, active_columns, base_corners, cross_coeff, x_col, fc_col);
+        });
 
-        // initialize prev time as the first available note
-        let mut ia = 0usize;
-        let mut ib = 0usize;
-        let mut have_prev = false;
-        let mut prev_time: f64 = 0.0;
-        if ia < a.len() && (ib >= b.len() || a[ia].hit_time <= b[ib].hit_time) {
-            prev_time = a[ia].hit_time as f64;
+    // compute X_base
+    let x_base = combine_cross(k, cross_coeff, &x_ks, &fast_cross, pool);
+    pool.give_all([x_ks.into_vec(), fast_cross.into_vec()]);
+
+    let xbar = smooth_on_corners_with_pool(base_corners, &x_base, 500.0, 0.001, SmoothMode::Sum, pool);
+    pool.give(x_base);
+    Ok(xbar)
+}
+
+/// Fills the cross values and fast-cross values of one column boundary (0..=k)
+#[allow(clippy::too_many_arguments)]
+fn fill_cross_column(
+    col: usize,
+    k: usize,
+    x: f64,
+    notes_by_column: &[Vec<Note>],
+    active_columns: &ActiveColumns,
+    base_corners: &[f64],
+    cross_coeff: &[f64],
+    x_col: &mut [f64],
+    fc_col: &mut [f64],
+) {
+    // two-pointer stream over adjacent columns without allocating a merged vec
+    let (a, b) = if col == 0 {
+        (&notes_by_column[0][..], &[][..])
+    } else if col == k {
+        (&notes_by_column[k - 1][..], &[][..])
+    } else {
+        (&notes_by_column[col - 1][..], &notes_by_column[col][..])
+    };
+
+    // initialize prev time as the first available note
+    let mut ia = 0usize;
+    let mut ib = 0usize;
+    let mut have_prev = false;
+    let mut prev_time: f64 = 0.0;
+    if ia < a.len() && (ib >= b.len() || a[ia].hit_time <= b[ib].hit_time) {
+        prev_time = a[ia].hit_time;
+        ia += 1;
+        have_prev = true;
+    } else if ib < b.len() {
+        prev_time = b[ib].hit_time;
+        ib += 1;
+        have_prev = true;
+    }
+    if !have_prev { return; }
+
+    let mut idx_start = 0usize;
+    let mut idx_end = 0usize;
+    loop {
+        let next_time_opt = if ia < a.len() && (ib >= b.len() || a[ia].hit_time <= b[ib].hit_time) {
+            let t = a[ia].hit_time;
             ia += 1;
-            have_prev = true;
+            Some(t)
         } else if ib < b.len() {
-            prev_time = b[ib].hit_time as f64;
+            let t = b[ib].hit_time;
             ib += 1;
-            have_prev = true;
-        }
-        if !have_prev { continue; }
-
-        let mut idx_start = 0usize;
-        let mut idx_end = 0usize;
-        loop {
-            let next_time_opt = if ia < a.len() && (ib >= b.len() || a[ia].hit_time <= b[ib].hit_time) {
-                let t = a[ia].hit_time as f64;
-                ia += 1;
-                Some(t)
-            } else if ib < b.len() {
-                let t = b[ib].hit_time as f64;
-                ib += 1;
-                Some(t)
-            } else {
-                None
-            };
-            let next_time = match next_time_opt { Some(t) => t, None => break };
+            Some(t)
+        } else {
+            None
+        };
+        let next_time = match next_time_opt { Some(t) => t, None => break };
 
-            // interval [prev_time, next_time)
-            while idx_start < base_corners.len() && base_corners[idx_start] < prev_time { idx_start += 1; }
-            if idx_end < idx_start { idx_end = idx_start; }
-            while idx_end < base_corners.len() && base_corners[idx_end] < next_time { idx_end += 1; }
-            if idx_start < idx_end {
-                let delta = 0.001 * (next_time - prev_time);
-                let inv = 1.0 / (x.max(delta));
-                let mut val = 0.16 * inv * inv;
+        // interval [prev_time, next_time)
+        while idx_start < base_corners.len() && base_corners[idx_start] < prev_time { idx_start += 1; }
+        if idx_end < idx_start { idx_end = idx_start; }
+        while idx_end < base_corners.len() && base_corners[idx_end] < next_time { idx_end += 1; }
+        if idx_start < idx_end {
+            let delta = 0.001 * (next_time - prev_time);
+            let inv = 1.0 / (x.max(delta));
+            let mut val = 0.16 * inv * inv;
 
-            // check active_columns condition
-            let cond1 = {
-                let a0 = active_columns.get(idx_start).map(|v| v.contains(&(col - 1))).unwrap_or(false);
-                let a1 = active_columns.get(idx_end).map(|v| v.contains(&(col - 1))).unwrap_or(false);
-                !a0 && !a1
-            };
-            let cond2 = {
-                let b0 = active_columns.get(idx_start).map(|v| v.contains(&col)).unwrap_or(false);
-                let b1 = active_columns.get(idx_end).map(|v| v.contains(&col)).unwrap_or(false);
-                !b0 && !b1
-            };
-            if cond1 || cond2 { val *= cross_complement[col]; }
-            for idx in idx_start..idx_end { x_ks[col][idx] = val; }
+            // check active_columns condition (there is no column left of the first one)
+            let cond1 = col == 0
+                || (!active_columns.is_active(idx_start, col - 1) && !active_columns.is_active(idx_end, col - 1));
+            let cond2 = !active_columns.is_active(idx_start, col) && !active_columns.is_active(idx_end, col);
+            if cond1 || cond2 { val *= 1.0 - cross_coeff[col]; }
+            x_col[idx_start..idx_end].fill(val);
             let base = (delta.max(0.06).max(0.75 * x)).powf(-2.0);
             let fc = (0.4 * base - 80.0).max(0.0);
-            for idx in idx_start..idx_end { fast_cross[col][idx] = fc; }
-            }
-            prev_time = next_time;
+            fc_col[idx_start..idx_end].fill(fc);
         }
+        prev_time = next_time;
     }
-
-    // compute X_base
-    let mut x_base = vec![0.0; n];
-    for i in 0..n {
-        let mut sum1 = 0.0;
-        for col in 0..=k { sum1 += x_ks[col][i] * cross_coeff[col]; }
-        let mut sum2 = 0.0;
-        for col in 0..k {
-            let v1 = fast_cross[col][i];
-            let v2 = fast_cross[col + 1][i];
-            let c1 = cross_coeff[col];
-            let c2 = cross_coeff[col + 1];
-            sum2 += (v1 * c1 * v2 * c2).sqrt();
-        }
-        x_base[i] = sum1 + sum2;
-    }
-
-    let xbar = smooth_on_corners(base_corners, &x_base, 500.0, 0.001, crate::algorithm::smoothing::SmoothMode::Sum);
-    xbar
 }
 
 /// Faster variant using precomputed active flags per column and index
 pub fn compute_xbar_flags(
     k: usize,
-    _t: i64,
+    _t: f64,
     x: f64,
     notes_by_column: &[Vec<Note>],
-    active_flags: &[Vec<bool>], // active_flags[col][idx]
+    active_flags: &ColumnMatrix<bool>, // key usage, active_flags[col][idx]
     base_corners: &[f64]
-) -> Vec<f64> {
-    let cross_matrix: Vec<Vec<f64>> = vec![
-        vec![-1.0],
-        vec![0.075, 0.075],
-        vec![0.125, 0.05, 0.125],
-        vec![0.125, 0.125, 0.125, 0.125],
-        vec![0.175, 0.25, 0.05, 0.25, 0.175],
-        vec![0.175, 0.25, 0.175, 0.175, 0.25, 0.175],
-        vec![0.225, 0.35, 0.25, 0.05, 0.25, 0.35, 0.225],
-        vec![0.225, 0.35, 0.25, 0.225, 0.225, 0.25, 0.35, 0.225],
-        vec![0.275, 0.45, 0.35, 0.25, 0.05, 0.25, 0.35, 0.45, 0.275],
-        vec![0.275, 0.45, 0.35, 0.25, 0.275, 0.275, 0.25, 0.35, 0.45, 0.275],
-        vec![0.325, 0.55, 0.45, 0.35, 0.25, 0.05, 0.25, 0.35, 0.45, 0.55, 0.325]
-    ];
-    let cross_coeff = cross_matrix.get(k).expect("cross_matrix[k] exists");
-    let mut cross_complement: Vec<f64> = Vec::with_capacity(cross_coeff.len());
-    for &c in cross_coeff.iter() { cross_complement.push(1.0 - c); }
+) -> StarRatingResult<Vec<f64>> {
+    let cross_coeff = cross_coefficients(k)?;
 
     let n = base_corners.len();
-    let mut x_ks: Vec<Vec<f64>> = vec![vec![0.0; n]; k + 1];
-    let mut fast_cross: Vec<Vec<f64>> = vec![vec![0.0; n]; k + 1];
+    let mut x_ks = ColumnMatrix::new(k + 1, n, 0.0);
+    let mut fast_cross = ColumnMatrix::new(k + 1, n, 0.0);
 
     for col in 0..=k {
         let (a, b) = if col == 0 {
@@ -179,11 +196,11 @@ pub fn compute_xbar_flags(
         let mut have_prev = false;
         let mut prev_time: f64 = 0.0;
         if ia < a.len() && (ib >= b.len() || a[ia].hit_time <= b[ib].hit_time) {
-            prev_time = a[ia].hit_time as f64;
+            prev_time = a[ia].hit_time;
             ia += 1;
             have_prev = true;
         } else if ib < b.len() {
-            prev_time = b[ib].hit_time as f64;
+            prev_time = b[ib].hit_time;
             ib += 1;
             have_prev = true;
         }
@@ -193,11 +210,11 @@ pub fn compute_xbar_flags(
         let mut idx_end = 0usize;
         loop {
             let next_time_opt = if ia < a.len() && (ib >= b.len() || a[ia].hit_time <= b[ib].hit_time) {
-                let t = a[ia].hit_time as f64;
+                let t = a[ia].hit_time;
                 ia += 1;
                 Some(t)
             } else if ib < b.len() {
-                let t = b[ib].hit_time as f64;
+                let t = b[ib].hit_time;
                 ib += 1;
                 Some(t)
             } else {
@@ -213,34 +230,57 @@ pub fn compute_xbar_flags(
                 let inv = 1.0 / (x.max(delta));
                 let mut val = 0.16 * inv * inv;
 
-                let cond1 = if col == 0 { false } else { !active_flags[col - 1][idx_start] && !active_flags[col - 1][idx_end.min(n - 1)] };
-                let cond2 = if col == k { false } else { !active_flags[col][idx_start] && !active_flags[col][idx_end.min(n - 1)] };
-                if cond1 || cond2 { val *= cross_complement[col]; }
-                for idx in idx_start..idx_end { x_ks[col][idx] = val; }
+                let cond1 = if col == 0 { false } else { !active_flags.get(col - 1, idx_start) && !active_flags.get(col - 1, idx_end.min(n - 1)) };
+                let cond2 = if col == k { false } else { !active_flags.get(col, idx_start) && !active_flags.get(col, idx_end.min(n - 1)) };
+                if cond1 || cond2 { val *= 1.0 - cross_coeff[col]; }
+                x_ks.column_mut(col)[idx_start..idx_end].fill(val);
                 let base = (delta.max(0.06).max(0.75 * x)).powf(-2.0);
                 let fc = (0.4 * base - 80.0).max(0.0);
-                for idx in idx_start..idx_end { fast_cross[col][idx] = fc; }
+                fast_cross.column_mut(col)[idx_start..idx_end].fill(fc);
             }
             prev_time = next_time;
         }
     }
 
-    let mut x_base = vec![0.0; n];
-    for i in 0..n {
-        let mut sum1 = 0.0;
-        for col in 0..=k { sum1 += x_ks[col][i] * cross_coeff[col]; }
-        let mut sum2 = 0.0;
-        for col in 0..k {
-            let v1 = fast_cross[col][i];
-            let v2 = fast_cross[col + 1][i];
-            let c1 = cross_coeff[col];
-            let c2 = cross_coeff[col + 1];
-            sum2 += (v1 * c1 * v2 * c2).sqrt();
+    let mut pool = BufferPool::new();
+    let x_base = combine_cross(k, cross_coeff, &x_ks, &fast_cross, &mut pool);
+
+    Ok(smooth_on_corners_with_pool(base_corners, &x_base, 500.0, 0.001, SmoothMode::Sum, &mut pool))
+}
+
+/// Combines per-column cross values into X_base, accumulating column by column
+fn combine_cross(k: usize, cross_coeff: &[f64], x_ks: &ColumnMatrix<f64>, fast_cross: &ColumnMatrix<f64>, pool: &mut BufferPool) -> Vec<f64> {
+    let n = x_ks.rows();
+    let mut sum1 = pool.take(n, 0.0);
+    for (col, x_col) in x_ks.iter_columns().enumerate() {
+        let coeff = cross_coeff[col];
+        for (acc, &v) in sum1.iter_mut().zip(x_col.iter()) { *acc += v * coeff; }
+    }
+    let mut sum2 = pool.take(n, 0.0);
+    for col in 0..k {
+        let (c1, c2) = (cross_coeff[col], cross_coeff[col + 1]);
+        for ((acc, &v1), &v2) in sum2.iter_mut().zip(fast_cross.column(col).iter()).zip(fast_cross.column(col + 1).iter()) {
+            *acc += (v1 * c1 * v2 * c2).sqrt();
         }
-        x_base[i] = sum1 + sum2;
     }
-
-    smooth_on_corners(base_corners, &x_base, 500.0, 0.001, crate::algorithm::smoothing::SmoothMode::Sum)
+    for (s1, &s2) in sum1.iter_mut().zip(sum2.iter()) {
+        *s1 += s2;
+    }
+    pool.give(sum2);
+    sum1
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
 
+    #[test]
+    fn key_counts_without_coefficients_are_errors() {
+        let base_corners = [0.0, 500.0, 1000.0];
+        for k in [MAX_KEY_COUNT + 1, 12] {
+            let notes_by_column = vec![Vec::new(); k];
+            let flags = ColumnMatrix::new(k, base_corners.len(), false);
+            assert!(compute_xbar_flags(k, 1000.0, 0.08, &notes_by_column, &flags, &base_corners).is_err(), "{}K", k);
+        }
+    }
+}
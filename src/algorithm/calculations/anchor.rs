@@ -1,4 +1,5 @@
-use std::collections::HashMap;
+use crate::algorithm::pool::BufferPool;
+use crate::types::ColumnMatrix;
 
 /// Computes anchor values based on key usage
 /// 
@@ -11,20 +12,29 @@ use std::collections::HashMap;
 /// Vector of anchor values for each time point
 pub fn compute_anchor(
     k: usize,
-    key_usage_400: &HashMap<usize, Vec<f64>>,
+    key_usage_400: &ColumnMatrix<f64>,
     base_corners: &[f64]
+) -> Vec<f64> {
+    compute_anchor_with_pool(k, key_usage_400, base_corners, &mut BufferPool::new())
+}
+
+/// Same as `compute_anchor`, with the output taken from `pool`
+pub fn compute_anchor_with_pool(
+    k: usize,
+    key_usage_400: &ColumnMatrix<f64>,
+    base_corners: &[f64],
+    pool: &mut BufferPool,
 ) -> Vec<f64> {
     let n = base_corners.len();
-    let mut anchor = vec![0.0; n];
-    for idx in 0..n {
-        // collect counts per column at this base corner
-        let mut counts: Vec<f64> = (0..k).map(|col| {
-            key_usage_400.get(&col).map(|v| v[idx]).unwrap_or(0.0)
-        }).collect();
+    let mut anchor = pool.take(n, 0.0);
+    // per-corner scratch, reused across corners
+    let mut nonzero = pool.take_empty(k);
+    for (idx, value) in anchor.iter_mut().enumerate() {
+        // collect nonzero counts per column at this base corner
+        nonzero.clear();
+        nonzero.extend((0..k.min(key_usage_400.columns())).map(|col| key_usage_400.get(col, idx)).filter(|&x| x != 0.0));
         // sort descending (counts[::-1].sort() in python after reversing)
-        counts.sort_by(|a, b| b.partial_cmp(a).expect("Valeurs finies attendues"));
-        // filter nonzero
-        let nonzero: Vec<f64> = counts.into_iter().filter(|&x| x != 0.0).collect();
+        nonzero.sort_by(|a, b| b.partial_cmp(a).expect("Valeurs finies attendues"));
         if nonzero.len() > 1 {
             let mut walk = 0.0;
             let mut max_walk = 0.0;
@@ -35,11 +45,12 @@ pub fn compute_anchor(
                 walk += term;
                 max_walk += a;
             }
-            anchor[idx] = if max_walk.abs() > 0.0 { walk / max_walk } else { 0.0 };
+            *value = if max_walk.abs() > 0.0 { walk / max_walk } else { 0.0 };
         } else {
-            anchor[idx] = 0.0;
+            *value = 0.0;
         }
     }
+    pool.give(nonzero);
     // anchor = 1 + np.minimum(anchor-0.18, 5*(anchor-0.22)**3)
     for v in anchor.iter_mut() {
         let a = *v - 0.18;
@@ -1,5 +1,5 @@
-use std::collections::HashMap;
-use crate::types::Note;
+use crate::algorithm::pool::BufferPool;
+use crate::types::{ColumnMatrix, Note};
 
 /// Computes C and Ks values for the star rating algorithm
 /// 
@@ -14,33 +14,48 @@ use crate::types::Note;
 /// Tuple (c_step, ks_step) - C and Ks values
 pub fn compute_c_and_ks(
     k: usize,
-    _t: i64,
+    _t: f64,
     notes: &[Note],
-    key_usage: &HashMap<usize, Vec<bool>>,
+    key_usage: &ColumnMatrix<bool>,
     base_corners: &[f64]
 ) -> (Vec<f64>, Vec<f64>) {
-    let mut note_hit_times: Vec<i64> = notes.iter().map(|note| note.hit_time).collect();
-    note_hit_times.sort_unstable();
+    compute_c_and_ks_with_pool(k, _t, notes, key_usage, base_corners, &mut BufferPool::new())
+}
+
+/// Same as `compute_c_and_ks`, with the corner-sized buffers taken from `pool`
+pub fn compute_c_and_ks_with_pool(
+    k: usize,
+    _t: f64,
+    notes: &[Note],
+    key_usage: &ColumnMatrix<bool>,
+    base_corners: &[f64],
+    pool: &mut BufferPool,
+) -> (Vec<f64>, Vec<f64>) {
+    let mut note_hit_times: Vec<f64> = notes.iter().map(|note| note.hit_time).collect();
+    note_hit_times.sort_unstable_by(f64::total_cmp);
     let n = base_corners.len();
-    let mut c_step = vec![0.0; n];
+    let mut c_step = pool.take(n, 0.0);
     for (i, &s) in base_corners.iter().enumerate() {
         let low = s - 500.0;
         let high = s + 500.0;
-        let left_high = note_hit_times.partition_point(|&t| (t as f64) < high);
-        let left_low = note_hit_times.partition_point(|&t| (t as f64) < low);
+        let left_high = note_hit_times.partition_point(|&t| t < high);
+        let left_low = note_hit_times.partition_point(|&t| t < low);
         let cnt = (left_high as i64 - left_low as i64) as f64;
         c_step[i] = cnt;
     }
-    let mut ks_step = vec![0.0; n];
-    for i in 0..n {
-        let mut cnt = 0usize;
-        for col in 0..k {
-            if key_usage.get(&col).map(|v| v[i]).unwrap_or(false) {
-                cnt += 1;
+    // Count active columns column by column, walking each contiguous column once
+    let mut counts = pool.take_indices(n);
+    counts.resize(n, 0);
+    for usage in key_usage.iter_columns().take(k) {
+        for (cnt, &used) in counts.iter_mut().zip(usage.iter()) {
+            if used {
+                *cnt += 1;
             }
         }
-        ks_step[i] = (cnt.max(1)) as f64;
     }
+    let mut ks_step = pool.take_empty(n);
+    ks_step.extend(counts.iter().map(|&cnt| (cnt.max(1)) as f64));
+    pool.give_indices(counts);
     (c_step, ks_step)
 }
 
@@ -1,3 +1,4 @@
+use crate::algorithm::pool::BufferPool;
 use crate::types::Note;
 
 /// Computes the different types of corners for the star rating algorithm
@@ -8,57 +9,89 @@ use crate::types::Note;
 /// 
 /// # Returns
 /// Returns a tuple (all_corners, base_corners, a_corners)
-pub fn get_corners(t: i64, notes: &[Note]) -> (Vec<f64>, Vec<f64>, Vec<f64>) {
+pub fn get_corners(t: f64, notes: &[Note]) -> (Vec<f64>, Vec<f64>, Vec<f64>) {
+    get_corners_with_pool(t, notes, &mut BufferPool::new())
+}
+
+/// Same as `get_corners`, with the corner buffers taken from `pool`
+pub fn get_corners_with_pool(t: f64, notes: &[Note], pool: &mut BufferPool) -> (Vec<f64>, Vec<f64>, Vec<f64>) {
     // Build base corners via vector + sort+dedup (faster than HashSet for this size)
-    let mut base_candidates: Vec<i64> = Vec::with_capacity(notes.len() * 4 + 4);
+    let mut base_candidates = pool.take_empty(notes.len() * 8 + 2);
     for note in notes.iter() {
         base_candidates.push(note.hit_time);
-        if note.tail_time >= 0 { base_candidates.push(note.tail_time); }
+        if note.tail_time >= 0.0 { base_candidates.push(note.tail_time); }
     }
     // expansions
     let snapshot_len = base_candidates.len();
     for i in 0..snapshot_len {
         let s = base_candidates[i];
-        base_candidates.push(s + 501);
-        base_candidates.push(s - 499);
-        base_candidates.push(s + 1);
+        base_candidates.push(s + 501.0);
+        base_candidates.push(s - 499.0);
+        base_candidates.push(s + 1.0);
     }
-    base_candidates.push(0);
+    base_candidates.push(0.0);
     base_candidates.push(t);
-    base_candidates.retain(|&s| 0 <= s && s <= t);
-    base_candidates.sort_unstable();
+    base_candidates.retain(|&s| 0.0 <= s && s <= t);
+    base_candidates.sort_unstable_by(f64::total_cmp);
     base_candidates.dedup();
     let corners_base_vec = base_candidates;
 
     // A corners
-    let mut a_candidates: Vec<i64> = Vec::with_capacity(notes.len() * 3 + 2);
+    let mut a_candidates = pool.take_empty(notes.len() * 6 + 2);
     for note in notes.iter() {
         a_candidates.push(note.hit_time);
-        if note.tail_time >= 0 { a_candidates.push(note.tail_time); }
+        if note.tail_time >= 0.0 { a_candidates.push(note.tail_time); }
     }
     let snapshot_a_len = a_candidates.len();
     for i in 0..snapshot_a_len {
         let s = a_candidates[i];
-        a_candidates.push(s + 1000);
-        a_candidates.push(s - 1000);
+        a_candidates.push(s + 1000.0);
+        a_candidates.push(s - 1000.0);
     }
-    a_candidates.push(0);
+    a_candidates.push(0.0);
     a_candidates.push(t);
-    a_candidates.retain(|&s| 0 <= s && s <= t);
-    a_candidates.sort_unstable();
+    a_candidates.retain(|&s| 0.0 <= s && s <= t);
+    a_candidates.sort_unstable_by(f64::total_cmp);
     a_candidates.dedup();
     let corners_a_vec = a_candidates;
 
-    let mut all_corners: Vec<i64> = Vec::with_capacity(corners_base_vec.len() + corners_a_vec.len());
+    let mut all_corners = pool.take_empty(corners_base_vec.len() + corners_a_vec.len());
     all_corners.extend_from_slice(&corners_base_vec);
     all_corners.extend_from_slice(&corners_a_vec);
-    all_corners.sort_unstable();
+    all_corners.sort_unstable_by(f64::total_cmp);
     all_corners.dedup();
 
-    (
-        all_corners.into_iter().map(|v| v as f64).collect(),
-        corners_base_vec.into_iter().map(|v| v as f64).collect(),
-        corners_a_vec.into_iter().map(|v| v as f64).collect(),
-    )
+    (all_corners, corners_base_vec, corners_a_vec)
 }
 
+/// Evenly spaced corners, used by the approximate mode instead of the note-driven sets
+///
+/// # Arguments
+/// * `t` - Total map time
+/// * `step` - Grid spacing in milliseconds (must be positive)
+///
+/// # Returns
+/// Returns a tuple (all_corners, base_corners, a_corners), the three sets being the
+/// same grid 0, step, 2*step, ... closed by `t`
+pub fn get_grid_corners(t: f64, step: i64) -> (Vec<f64>, Vec<f64>, Vec<f64>) {
+    get_grid_corners_with_pool(t, step, &mut BufferPool::new())
+}
+
+/// Same as `get_grid_corners`, with the corner buffers taken from `pool`
+pub fn get_grid_corners_with_pool(t: f64, step: i64, pool: &mut BufferPool) -> (Vec<f64>, Vec<f64>, Vec<f64>) {
+    let t = t.max(0.0);
+    let count = (t / step as f64) as usize + 1;
+    let mut grid = pool.take_empty(count + 1);
+    grid.extend((0..count).map(|i| (i as i64 * step) as f64));
+    if *grid.last().expect("grid starts at 0") < t {
+        grid.push(t);
+    }
+    let mut copy = |corners: &[f64]| {
+        let mut out = pool.take_empty(corners.len());
+        out.extend_from_slice(corners);
+        out
+    };
+    let base = copy(&grid);
+    let a = copy(&grid);
+    (grid, base, a)
+}
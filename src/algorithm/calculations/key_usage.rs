@@ -1,5 +1,5 @@
-use std::collections::HashMap;
-use crate::types::Note;
+use crate::algorithm::pool::BufferPool;
+use crate::types::{ColumnMatrix, Note};
 
 /// Computes key usage for each column
 /// 
@@ -10,24 +10,21 @@ use crate::types::Note;
 /// * `base_corners` - Reference time points
 /// 
 /// # Returns
-/// HashMap with boolean usage per column
+/// Column-major matrix with boolean usage per column
 pub fn get_key_usage(
     k: usize,
-    t: i64,
+    t: f64,
     notes: &[Note],
     base_corners: &[f64]
-) -> HashMap<usize, Vec<bool>> {
-    let mut key_usage: HashMap<usize, Vec<bool>> = HashMap::with_capacity(k);
-    for col in 0..k { key_usage.insert(col, vec![false; base_corners.len()]); }
-    for note in notes.iter() {
-        let start_time = (note.hit_time - 150).max(0);
-        let end_time = if note.tail_time < 0 { note.hit_time + 150 } else { (note.tail_time + 150).min(t - 1) };
-        let left_idx = base_corners.partition_point(|&v| v < start_time as f64);
-        let right_idx = base_corners.partition_point(|&v| v < end_time as f64);
-        if let Some(usage) = key_usage.get_mut(&note.column) {
-            for i in left_idx..right_idx {
-                usage[i] = true;
-            }
+) -> ColumnMatrix<bool> {
+    let mut key_usage = ColumnMatrix::new(k, base_corners.len(), false);
+    for note in notes.iter().filter(|note| note.column < k) {
+        let start_time = (note.hit_time - 150.0).max(0.0);
+        let end_time = if note.tail_time < 0.0 { note.hit_time + 150.0 } else { (note.tail_time + 150.0).min(t - 1.0) };
+        let left_idx = base_corners.partition_point(|&v| v < start_time);
+        let right_idx = base_corners.partition_point(|&v| v < end_time);
+        if left_idx < right_idx {
+            key_usage.column_mut(note.column)[left_idx..right_idx].fill(true);
         }
     }
     key_usage
@@ -42,40 +39,47 @@ pub fn get_key_usage(
 /// * `base_corners` - Reference time points
 /// 
 /// # Returns
-/// HashMap with weighted usage per column
+/// Column-major matrix with weighted usage per column
 pub fn get_key_usage_400(
     k: usize,
-    t: i64,
+    t: f64,
     notes: &[Note],
     base_corners: &[f64]
-) -> HashMap<usize, Vec<f64>> {
-    let mut key_usage_400: HashMap<usize, Vec<f64>> = HashMap::new();
-    for col in 0..k {
-        key_usage_400.insert(col, vec![0.0; base_corners.len()]);
-    }
-    for note in notes.iter() {
-        let start_time = note.hit_time.max(0);
-        let end_time = if note.tail_time < 0 { note.hit_time } else { (note.tail_time).min(t - 1) };
-        let left400_idx = base_corners.partition_point(|&v| v < (start_time - 400) as f64);
-        let left_idx = base_corners.partition_point(|&v| v < start_time as f64);
-        let right_idx = base_corners.partition_point(|&v| v < end_time as f64);
-        let right400_idx = base_corners.partition_point(|&v| v < (end_time + 400) as f64);
+) -> ColumnMatrix<f64> {
+    get_key_usage_400_with_pool(k, t, notes, base_corners, &mut BufferPool::new())
+}
+
+/// Same as `get_key_usage_400`, with the matrix storage taken from `pool`
+pub fn get_key_usage_400_with_pool(
+    k: usize,
+    t: f64,
+    notes: &[Note],
+    base_corners: &[f64],
+    pool: &mut BufferPool,
+) -> ColumnMatrix<f64> {
+    let n = base_corners.len();
+    let mut key_usage_400 = ColumnMatrix::from_vec(k, n, pool.take(k * n, 0.0));
+    for note in notes.iter().filter(|note| note.column < k) {
+        let start_time = note.hit_time.max(0.0);
+        let end_time = if note.tail_time < 0.0 { note.hit_time } else { (note.tail_time).min(t - 1.0) };
+        let left400_idx = base_corners.partition_point(|&v| v < start_time - 400.0);
+        let left_idx = base_corners.partition_point(|&v| v < start_time);
+        let right_idx = base_corners.partition_point(|&v| v < end_time);
+        let right400_idx = base_corners.partition_point(|&v| v < end_time + 400.0);
 
-        if let Some(usage) = key_usage_400.get_mut(&note.column) {
-            for i in left_idx..right_idx {
-                usage[i] += 3.75 + ((end_time - start_time).min(1500) as f64) / 150.0;
-            }
-            for i in left400_idx..left_idx {
-                let diff = base_corners[i] - start_time as f64;
-                usage[i] += 3.75 - 3.75 / (400.0 * 400.0) * diff * diff;
-            }
-            for i in right_idx..right400_idx {
-                let diff = (base_corners[i] - end_time as f64).abs();
-                usage[i] += 3.75 - 3.75 / (400.0 * 400.0) * diff * diff;
-            }
+        let usage = key_usage_400.column_mut(note.column);
+        for value in usage.iter_mut().take(right_idx).skip(left_idx) {
+            *value += 3.75 + (end_time - start_time).min(1500.0) / 150.0;
+        }
+        for i in left400_idx..left_idx {
+            let diff = base_corners[i] - start_time;
+            usage[i] += 3.75 - 3.75 / (400.0 * 400.0) * diff * diff;
+        }
+        for i in right_idx..right400_idx {
+            let diff = (base_corners[i] - end_time).abs();
+            usage[i] += 3.75 - 3.75 / (400.0 * 400.0) * diff * diff;
         }
     }
     key_usage_400
 }
 
-
@@ -0,0 +1,176 @@
+use std::borrow::Cow;
+use crate::algorithm::msd::skillsets::{calculate_msd, SCORE_GOAL_93};
+use crate::algorithm::process::normalize::{apply_rate, rebuild_groupings, recompute_total_duration};
+use crate::algorithm::process::calculator::Calculator;
+use crate::algorithm::strain::lazer::calculate_lazer;
+use crate::algorithm::strain::stable::calculate_stable;
+use crate::types::{CalculationContext, DifficultyResult, MapData, Skillset, StarRatingResult};
+
+/// A difficulty rating algorithm over `MapData`
+///
+/// Implementors rate the map as given, with `context.clock_rate` applied on top of it.
+/// Maps preprocessed with a mod already applied should be rated with the default context.
+pub trait DifficultyCalculator {
+    /// Short identifier of the algorithm
+    fn name(&self) -> &'static str;
+
+    /// Rates a map under the given mods/rate context
+    fn calculate(&self, map_data: &MapData, context: &CalculationContext) -> StarRatingResult<DifficultyResult>;
+}
+
+/// The map played at `clock_rate`, borrowed as is when the rate is 1
+pub(crate) fn at_clock_rate(map_data: &MapData, clock_rate: f64) -> Cow<'_, MapData> {
+    if clock_rate == 1.0 {
+        return Cow::Borrowed(map_data);
+    }
+    let mut rated = map_data.clone();
+    apply_rate(&mut rated, clock_rate);
+    rebuild_groupings(&mut rated);
+    recompute_total_duration(&mut rated);
+    Cow::Owned(rated)
+}
+
+/// Sunny's star rating rework (this crate's algorithm)
+#[deprecated(note = "use `Calculator`, the same calculator with pooled buffers")]
+pub type ReworkCalculator = Calculator;
+
+/// osu!stable mania strain calculator
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StableCalculator;
+
+/// osu!lazer mania strain calculator
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LazerCalculator;
+
+/// MSD-style 4K skillset calculator
+#[derive(Debug, Clone, Copy)]
+pub struct MsdCalculator {
+    /// Accuracy target
+    pub score_goal: f64,
+}
+
+impl Default for MsdCalculator {
+    fn default() -> Self {
+        Self { score_goal: SCORE_GOAL_93 }
+    }
+}
+
+impl DifficultyCalculator for StableCalculator {
+    fn name(&self) -> &'static str {
+        "stable"
+    }
+
+    fn calculate(&self, map_data: &MapData, context: &CalculationContext) -> StarRatingResult<DifficultyResult> {
+        let result = calculate_stable(map_data, context.clock_rate)?;
+        Ok(DifficultyResult {
+            calculator: self.name(),
+            clock_rate: context.clock_rate,
+            rating: result.rating,
+            skillsets: Vec::new(),
+        })
+    }
+}
+
+impl DifficultyCalculator for LazerCalculator {
+    fn name(&self) -> &'static str {
+        "lazer"
+    }
+
+    fn calculate(&self, map_data: &MapData, context: &CalculationContext) -> StarRatingResult<DifficultyResult> {
+        let result = calculate_lazer(map_data, context.clock_rate)?;
+        Ok(DifficultyResult {
+            calculator: self.name(),
+            clock_rate: context.clock_rate,
+            rating: result.rating,
+            skillsets: Vec::new(),
+        })
+    }
+}
+
+impl DifficultyCalculator for MsdCalculator {
+    fn name(&self) -> &'static str {
+        "msd"
+    }
+
+    fn calculate(&self, map_data: &MapData, context: &CalculationContext) -> StarRatingResult<DifficultyResult> {
+        let result = calculate_msd(map_data, context.clock_rate, self.score_goal)?;
+        Ok(DifficultyResult {
+            calculator: self.name(),
+            clock_rate: context.clock_rate,
+            rating: result.overall(),
+            skillsets: Skillset::ALL[1..].iter()
+                .map(|&skillset| (skillset.name().to_string(), result.get(skillset)))
+                .collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithm::process::preprocess::preprocess_file;
+    use crate::types::StarRatingError;
+
+    fn calculators() -> Vec<Box<dyn DifficultyCalculator>> {
+        vec![Box::new(Calculator::new()), Box::new(StableCalculator), Box::new(LazerCalculator), Box::new(MsdCalculator::default())]
+    }
+
+    #[test]
+    fn every_calculator_names_its_results() {
+        let map_data = preprocess_file("assets/test.osu", "None").unwrap();
+        let context = CalculationContext::from_mod("DT");
+        let names: Vec<&str> = calculators().iter().map(|calculator| calculator.name()).collect();
+        assert_eq!(names, ["ssrrr", "stable", "lazer", "msd"]);
+        for calculator in calculators() {
+            let result = calculator.calculate(&map_data, &context).unwrap();
+            assert_eq!((result.calculator, result.clock_rate), (calculator.name(), 1.5));
+            assert!(result.rating > 0.0, "{}", calculator.name());
+        }
+    }
+
+    #[test]
+    fn context_rates_match_the_underlying_functions() {
+        let map_data = preprocess_file("assets/test.osu", "None").unwrap();
+        for mod_name in ["None", "DT", "HT"] {
+            let context = CalculationContext::from_mod(mod_name);
+            let stable = StableCalculator.calculate(&map_data, &context).unwrap().rating;
+            let lazer = LazerCalculator.calculate(&map_data, &context).unwrap().rating;
+            assert_eq!(stable.to_bits(), calculate_stable(&map_data, context.clock_rate).unwrap().rating.to_bits());
+            assert_eq!(lazer.to_bits(), calculate_lazer(&map_data, context.clock_rate).unwrap().rating.to_bits());
+        }
+    }
+
+    #[test]
+    fn msd_results_list_every_skillset_but_overall() {
+        let map_data = preprocess_file("assets/test.osu", "None").unwrap();
+        let result = MsdCalculator::default().calculate(&map_data, &CalculationContext::default()).unwrap();
+        let names: Vec<&str> = result.skillsets.iter().map(|(name, _)| name.as_str()).collect();
+        let expected: Vec<&str> = Skillset::ALL[1..].iter().map(|skillset| skillset.name()).collect();
+        assert_eq!(names, expected);
+        let hardest = result.skillsets.iter().map(|&(_, value)| value).fold(0.0, f64::max);
+        assert_eq!(result.rating, hardest);
+
+        let strict = MsdCalculator { score_goal: 0.965 }.calculate(&map_data, &CalculationContext::default()).unwrap();
+        assert!(strict.rating > result.rating);
+    }
+
+    #[test]
+    fn calculator_errors_are_returned() {
+        let mut map_data = preprocess_file("assets/test.osu", "None").unwrap();
+        map_data.column_count = 7;
+        let result = MsdCalculator::default().calculate(&map_data, &CalculationContext::default());
+        assert!(matches!(result, Err(StarRatingError::InvalidFormat(_))));
+    }
+
+    #[test]
+    fn clock_rate_one_borrows_the_map() {
+        let map_data = preprocess_file("assets/test.osu", "None").unwrap();
+        assert!(matches!(at_clock_rate(&map_data, 1.0), Cow::Borrowed(_)));
+
+        let rated = at_clock_rate(&map_data, 2.0);
+        assert!(matches!(rated, Cow::Owned(_)));
+        assert_eq!(rated.notes.len(), map_data.notes.len());
+        assert_eq!(rated.notes[1].hit_time, map_data.notes[1].hit_time / 2.0);
+        assert!(rated.total_duration < map_data.total_duration);
+    }
+}
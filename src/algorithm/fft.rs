@@ -0,0 +1,86 @@
+use std::f64::consts::PI;
+
+/// In-place iterative radix-2 FFT over separate real/imaginary buffers
+///
+/// # Arguments
+/// * `re` - Real parts (length must be a power of two)
+/// * `im` - Imaginary parts (same length)
+/// * `inverse` - Computes the unnormalised inverse transform when true
+pub fn fft_in_place(re: &mut [f64], im: &mut [f64], inverse: bool) {
+    let n = re.len();
+    debug_assert!(n.is_power_of_two() && im.len() == n);
+
+    // Bit-reversal permutation
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    // Twiddle factors of the full size, shared by every stage
+    let sign = if inverse { 1.0 } else { -1.0 };
+    let twiddles: Vec<(f64, f64)> = (0..n / 2)
+        .map(|k| {
+            let (sin, cos) = (sign * 2.0 * PI * k as f64 / n as f64).sin_cos();
+            (cos, sin)
+        })
+        .collect();
+
+    let mut len = 2;
+    while len <= n {
+        let half = len / 2;
+        let step = n / len;
+        for start in (0..n).step_by(len) {
+            for k in 0..half {
+                let (w_re, w_im) = twiddles[k * step];
+                let (a, b) = (start + k, start + k + half);
+                let t_re = re[b] * w_re - im[b] * w_im;
+                let t_im = re[b] * w_im + im[b] * w_re;
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+/// Computes the linear autocorrelation of a histogram
+///
+/// # Arguments
+/// * `hist` - Histogram values
+///
+/// # Returns
+/// `result[d] = sum_x hist[x] * hist[x + d]` for every lag `d` in `0..hist.len()`
+pub fn autocorrelation(hist: &[f64]) -> Vec<f64> {
+    if hist.is_empty() {
+        return Vec::new();
+    }
+    // Zero padding to at least 2n - 1 avoids circular wrap-around
+    let size = (2 * hist.len() - 1).next_power_of_two();
+    let mut re = vec![0.0; size];
+    let mut im = vec![0.0; size];
+    re[..hist.len()].copy_from_slice(hist);
+
+    fft_in_place(&mut re, &mut im, false);
+    for (r, i) in re.iter_mut().zip(im.iter_mut()) {
+        *r = *r * *r + *i * *i;
+        *i = 0.0;
+    }
+    fft_in_place(&mut re, &mut im, true);
+
+    re.truncate(hist.len());
+    for value in re.iter_mut() {
+        *value /= size as f64;
+    }
+    re
+}
@@ -1,4 +1,51 @@
+use crate::algorithm::pool::BufferPool;
+use crate::algorithm::simd::lerp_bracketed;
+
 pub fn interp_values(new_x: &[f64], old_x: &[f64], old_vals: &[f64]) -> Vec<f64> {
+    interp_values_with_pool(new_x, old_x, old_vals, &mut BufferPool::new())
+}
+
+/// Same as `interp_values`, with the output taken from `pool`
+///
+/// Sorted queries (the corner timelines) are bracketed with a forward-only cursor and
+/// interpolated by a vectorized kernel; unsorted ones fall back to `interp_values_bisect`.
+pub fn interp_values_with_pool(new_x: &[f64], old_x: &[f64], old_vals: &[f64], pool: &mut BufferPool) -> Vec<f64> {
+    if new_x.is_empty() || !new_x.is_sorted() {
+        return interp_values_bisect(new_x, old_x, old_vals);
+    }
+    let mut new_vals = pool.take(new_x.len(), 0.0);
+    let first = old_vals[0];
+    let last = *old_vals.last().expect("Vecteur non vide attendu");
+
+    // Queries before old_x[0] take the first value, those from the last point on the last one
+    let mut right = pool.take_indices(new_x.len());
+    let mut idx = 0usize;
+    let mut start = new_x.len();
+    for (i, &nx) in new_x.iter().enumerate() {
+        while idx < old_x.len() && old_x[idx] <= nx {
+            idx += 1;
+        }
+        if idx == 0 {
+            new_vals[i] = first;
+        } else if idx >= old_x.len() {
+            new_vals[i] = last;
+        } else {
+            if right.is_empty() {
+                start = i;
+            }
+            right.push(idx);
+        }
+    }
+    let end = start + right.len();
+    if start < end {
+        lerp_bracketed(&new_x[start..end], old_x, old_vals, &right, &mut new_vals[start..end]);
+    }
+    pool.give_indices(right);
+    new_vals
+}
+
+/// Reference version of `interp_values`, bisecting `old_x` for every query
+pub(crate) fn interp_values_bisect(new_x: &[f64], old_x: &[f64], old_vals: &[f64]) -> Vec<f64> {
     let mut new_vals = Vec::with_capacity(new_x.len());
     for &nx in new_x.iter() {
         let idx = old_x.partition_point(|&ox| ox <= nx);
@@ -19,6 +66,29 @@ pub fn interp_values(new_x: &[f64], old_x: &[f64], old_vals: &[f64]) -> Vec<f64>
 }
 
 pub fn step_interp(new_x: &[f64], old_x: &[f64], old_vals: &[f64]) -> Vec<f64> {
+    step_interp_with_pool(new_x, old_x, old_vals, &mut BufferPool::new())
+}
+
+/// Same as `step_interp`, with the output taken from `pool`
+///
+/// Sorted queries are located with a forward-only cursor; unsorted ones fall back to `step_interp_bisect`.
+pub fn step_interp_with_pool(new_x: &[f64], old_x: &[f64], old_vals: &[f64], pool: &mut BufferPool) -> Vec<f64> {
+    if !new_x.is_sorted() {
+        return step_interp_bisect(new_x, old_x, old_vals);
+    }
+    let mut res = pool.take_empty(new_x.len());
+    let mut idx = 0usize;
+    for &nx in new_x.iter() {
+        while idx < old_x.len() && old_x[idx] <= nx {
+            idx += 1;
+        }
+        res.push(old_vals[idx.saturating_sub(1).min(old_vals.len() - 1)]);
+    }
+    res
+}
+
+/// Reference version of `step_interp`, bisecting `old_x` for every query
+pub(crate) fn step_interp_bisect(new_x: &[f64], old_x: &[f64], old_vals: &[f64]) -> Vec<f64> {
     let mut res = Vec::with_capacity(new_x.len());
     for &nx in new_x.iter() {
         let mut idx = old_x.partition_point(|&ox| ox <= nx);
@@ -27,4 +97,49 @@ pub fn step_interp(new_x: &[f64], old_x: &[f64], old_vals: &[f64]) -> Vec<f64> {
         res.push(old_vals[idx]);
     }
     res
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{assert_bits_eq, TestRng};
+
+    /// Query sets: random sorted, monotonic grid, the sample points themselves, out of range and unsorted
+    fn query_sets(rng: &mut TestRng, old_x: &[f64]) -> Vec<(&'static str, Vec<f64>)> {
+        let (first, last) = (old_x[0], old_x[old_x.len() - 1]);
+        let mut random: Vec<f64> = (0..1500).map(|_| first + rng.next_f64() * (last - first)).collect();
+        random.sort_by(f64::total_cmp);
+        let monotonic: Vec<f64> = (0..=1000).map(|i| first + (last - first) * i as f64 / 1000.0).collect();
+        let out_of_range = vec![first - 500.0, first - 1.0, first, last, last + 1.0, last + 500.0];
+        let unsorted: Vec<f64> = (0..300).map(|_| first - 50.0 + rng.next_f64() * (last - first + 100.0)).collect();
+        vec![
+            ("random", random),
+            ("monotonic", monotonic),
+            ("sample points", old_x.to_vec()),
+            ("out of range", out_of_range),
+            ("unsorted", unsorted),
+        ]
+    }
+
+    #[test]
+    fn cursor_interpolation_matches_bisect() {
+        let mut rng = TestRng::new(21);
+        for len in [1, 2, 3, 50, 777] {
+            // sorted_points repeats some x values
+            let old_x = rng.sorted_points(len);
+            let old_vals = rng.values(len, 40.0);
+            for (name, new_x) in query_sets(&mut rng, &old_x) {
+                assert_bits_eq(
+                    &format!("interp_values {} ({} points)", name, len),
+                    &interp_values(&new_x, &old_x, &old_vals),
+                    &interp_values_bisect(&new_x, &old_x, &old_vals),
+                );
+                assert_bits_eq(
+                    &format!("step_interp {} ({} points)", name, len),
+                    &step_interp(&new_x, &old_x, &old_vals),
+                    &step_interp_bisect(&new_x, &old_x, &old_vals),
+                );
+            }
+        }
+    }
+}
@@ -1,7 +1,14 @@
 pub mod sums;
+pub mod fft;
+pub mod pool;
+pub mod simd;
 pub mod smoothing;
 pub mod interpolation;
 pub mod utils;
 pub mod process;
 pub mod bars;
-pub mod calculations;
\ No newline at end of file
+pub mod calculations;
+pub mod analysis;
+pub mod strain;
+pub mod msd;
+pub mod calculator;
\ No newline at end of file
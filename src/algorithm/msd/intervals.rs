@@ -0,0 +1,253 @@
+use crate::types::Note;
+
+/// Length of one interval in seconds
+pub const INTERVAL_SPAN: f64 = 0.5;
+
+/// Column masks of the left and right hands on 4K
+pub const HAND_MASKS: [u8; 2] = [0b0011, 0b1100];
+
+/// Per-hand statistics of one interval
+#[derive(Debug, Clone, Default)]
+pub struct HandInterval {
+    /// Notes played by this hand
+    pub taps: usize,
+    /// Mean same-column repetition speed (notes/s) of the hand's fastest column
+    pub jack_speed: f64,
+}
+
+/// Pattern statistics of one interval
+#[derive(Debug, Clone, Default)]
+pub struct IntervalPatterns {
+    /// Left and right hand statistics
+    pub hands: [HandInterval; 2],
+    /// Number of rows (distinct timestamps)
+    pub rows: usize,
+    /// Total notes
+    pub taps: usize,
+    /// Notes in single-note rows
+    pub single_taps: usize,
+    /// Notes in 2-note rows
+    pub jump_taps: usize,
+    /// Notes in 3-note rows
+    pub hand_taps: usize,
+    /// Notes in 4-note rows
+    pub quad_taps: usize,
+    /// Rows sharing a column with the previous row
+    pub jacked_rows: usize,
+    /// Notes in chords sharing a column with the previous row
+    pub chordjack_taps: usize,
+    /// Coefficient of variation of the gaps between rows
+    pub rhythm_cv: f64,
+}
+
+impl IntervalPatterns {
+    fn prop(&self, taps: usize) -> f64 {
+        if self.taps == 0 { 0.0 } else { taps as f64 / self.taps as f64 }
+    }
+
+    fn jack_prop(&self) -> f64 {
+        if self.rows == 0 { 0.0 } else { self.jacked_rows as f64 / self.rows as f64 }
+    }
+
+    /// Stream: single notes with few jacks
+    pub fn stream_mod(&self) -> f64 {
+        if self.taps == 0 { return 0.0; }
+        (0.6 + 0.5 * self.prop(self.single_taps)).clamp(0.6, 1.0) * (1.0 - 0.4 * self.jack_prop())
+    }
+
+    /// Jumpstream: jumps mixed into streams, hands and quads are handstream/chordjack material
+    pub fn jumpstream_mod(&self) -> f64 {
+        if self.taps == 0 { return 0.0; }
+        (0.6 + 0.8 * self.prop(self.jump_taps)).clamp(0.6, 1.0)
+            * (1.0 - 0.2 * self.prop(self.hand_taps))
+            * (1.0 - 0.3 * self.prop(self.quad_taps))
+            * (1.0 - 0.3 * self.jack_prop())
+    }
+
+    /// Handstream: hands mixed into streams
+    pub fn handstream_mod(&self) -> f64 {
+        if self.taps == 0 { return 0.0; }
+        (0.6 + 1.2 * self.prop(self.hand_taps)).clamp(0.6, 1.0)
+            * (1.0 - 0.3 * self.prop(self.quad_taps))
+            * (1.0 - 0.3 * self.jack_prop())
+    }
+
+    /// Chordjack: chords repeating columns of the previous row
+    pub fn chordjack_mod(&self) -> f64 {
+        if self.taps == 0 { return 0.0; }
+        (0.5 + 0.8 * self.prop(self.chordjack_taps)).clamp(0.5, 1.0)
+    }
+
+    /// Technical: irregular rhythms
+    pub fn technical_mod(&self) -> f64 {
+        if self.taps == 0 { return 0.0; }
+        (0.75 + self.rhythm_cv).clamp(0.75, 1.1) * (1.0 - 0.3 * self.jack_prop())
+    }
+}
+
+/// Splits a 4K chart into intervals of `INTERVAL_SPAN` seconds and collects pattern statistics
+///
+/// # Arguments
+/// * `notes` - Notes sorted by hit time (columns 0..4)
+/// * `rate` - Music rate, note times are divided by it
+///
+/// # Returns
+/// Pattern statistics of every interval, from the start of the chart
+pub fn build_intervals(notes: &[Note], rate: f64) -> Vec<IntervalPatterns> {
+    // Rows: (time in seconds, column mask)
+    let mut rows: Vec<(f64, u8)> = Vec::new();
+    for note in notes.iter().filter(|note| note.column < 4) {
+        let time = note.hit_time / rate / 1000.0;
+        match rows.last_mut() {
+            Some(last) if (last.0 - time).abs() < 1e-9 => last.1 |= 1 << note.column,
+            _ => rows.push((time, 1 << note.column)),
+        }
+    }
+    let Some(&(last_time, _)) = rows.last() else { return Vec::new(); };
+
+    let count = (last_time.max(0.0) / INTERVAL_SPAN) as usize + 1;
+    let mut intervals = vec![IntervalPatterns::default(); count];
+    let mut jack_sums = vec![[(0.0f64, 0usize); 4]; count];
+    let mut gaps: Vec<Vec<f64>> = vec![Vec::new(); count];
+    let mut last_hit: [Option<f64>; 4] = [None; 4];
+    let mut previous: Option<(f64, u8)> = None;
+
+    for &(time, mask) in &rows {
+        let idx = ((time.max(0.0) / INTERVAL_SPAN) as usize).min(count - 1);
+        let itv = &mut intervals[idx];
+        let n = mask.count_ones() as usize;
+        itv.rows += 1;
+        itv.taps += n;
+        match n {
+            1 => itv.single_taps += n,
+            2 => itv.jump_taps += n,
+            3 => itv.hand_taps += n,
+            _ => itv.quad_taps += n,
+        }
+        for (hand, &hand_mask) in HAND_MASKS.iter().enumerate() {
+            itv.hands[hand].taps += (mask & hand_mask).count_ones() as usize;
+        }
+        if let Some((prev_time, prev_mask)) = previous {
+            if mask & prev_mask != 0 {
+                itv.jacked_rows += 1;
+                if n >= 2 {
+                    itv.chordjack_taps += n;
+                }
+            }
+            gaps[idx].push(time - prev_time);
+        }
+        for (col, last) in last_hit.iter_mut().enumerate() {
+            if mask & (1 << col) == 0 { continue; }
+            if let Some(last_time) = *last {
+                let gap = time - last_time;
+                if gap > 0.0 {
+                    jack_sums[idx][col].0 += 1.0 / gap;
+                    jack_sums[idx][col].1 += 1;
+                }
+            }
+            *last = Some(time);
+        }
+        previous = Some((time, mask));
+    }
+
+    for (idx, itv) in intervals.iter_mut().enumerate() {
+        for (hand, &hand_mask) in HAND_MASKS.iter().enumerate() {
+            itv.hands[hand].jack_speed = (0..4)
+                .filter(|&col| hand_mask & (1 << col) != 0)
+                .map(|col| {
+                    let (sum, cnt) = jack_sums[idx][col];
+                    if cnt > 0 { sum / cnt as f64 } else { 0.0 }
+                })
+                .fold(0.0, f64::max);
+        }
+        let itv_gaps = &gaps[idx];
+        if itv_gaps.len() >= 2 {
+            let mean = itv_gaps.iter().sum::<f64>() / itv_gaps.len() as f64;
+            let var = itv_gaps.iter().map(|g| (g - mean).powi(2)).sum::<f64>() / itv_gaps.len() as f64;
+            itv.rhythm_cv = if mean > 0.0 { var.sqrt() / mean } else { 0.0 };
+        }
+    }
+
+    intervals
+}
+
+/// Centered moving average over `2 * radius + 1` intervals (missing neighbours count as 0)
+pub fn smooth(values: &[f64], radius: usize) -> Vec<f64> {
+    let n = values.len();
+    let width = (2 * radius + 1) as f64;
+    let mut prefix = Vec::with_capacity(n + 1);
+    prefix.push(0.0);
+    for &v in values {
+        let last = *prefix.last().expect("Vecteur non vide attendu");
+        prefix.push(last + v);
+    }
+    (0..n)
+        .map(|i| (prefix[(i + radius + 1).min(n)] - prefix[i.saturating_sub(radius)]) / width)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rows_are_grouped_and_counted_per_interval() {
+        // A jump, a single note repeating its column, then a hand in the second interval
+        let notes = [
+            Note::simple(0, 0.0),
+            Note::simple(2, 0.0),
+            Note::simple(0, 250.0),
+            Note::simple(1, 600.0),
+            Note::simple(2, 600.0),
+            Note::simple(3, 600.0),
+        ];
+        let intervals = build_intervals(&notes, 1.0);
+        assert_eq!(intervals.len(), 2);
+
+        let first = &intervals[0];
+        assert_eq!((first.rows, first.taps, first.single_taps, first.jump_taps), (2, 3, 1, 2));
+        assert_eq!(first.jacked_rows, 1);
+        assert_eq!((first.hands[0].taps, first.hands[1].taps), (2, 1));
+        assert_eq!(first.hands[0].jack_speed, 4.0);
+
+        let second = &intervals[1];
+        assert_eq!((second.rows, second.hand_taps, second.jacked_rows), (1, 3, 0));
+        assert_eq!((second.hands[0].taps, second.hands[1].taps), (1, 2));
+    }
+
+    #[test]
+    fn chords_on_the_previous_columns_are_chordjacks() {
+        let notes = [Note::simple(0, 0.0), Note::simple(1, 0.0), Note::simple(0, 100.0), Note::simple(1, 100.0)];
+        let intervals = build_intervals(&notes, 1.0);
+        assert_eq!(intervals[0].chordjack_taps, 2);
+        assert_eq!(intervals[0].chordjack_mod(), 0.5 + 0.8 * 0.5);
+    }
+
+    #[test]
+    fn rate_divides_the_note_times() {
+        let notes: Vec<Note> = (0..8).map(|i| Note::simple(i % 4, 200.0 * i as f64)).collect();
+        let halved: Vec<Note> = notes.iter().map(|note| Note::simple(note.column, note.hit_time / 2.0)).collect();
+        let rated = build_intervals(&notes, 2.0);
+        let expected = build_intervals(&halved, 1.0);
+        assert_eq!(rated.len(), expected.len());
+        for (a, b) in rated.iter().zip(&expected) {
+            assert_eq!((a.rows, a.taps, a.rhythm_cv.to_bits()), (b.rows, b.taps, b.rhythm_cv.to_bits()));
+        }
+    }
+
+    #[test]
+    fn regular_rhythms_have_no_variation() {
+        let notes: Vec<Note> = (0..5).map(|i| Note::simple(i % 4, 100.0 * i as f64)).collect();
+        assert!(build_intervals(&notes, 1.0)[0].rhythm_cv < 1e-12);
+
+        let irregular = [Note::simple(0, 0.0), Note::simple(1, 50.0), Note::simple(2, 250.0)];
+        assert!((build_intervals(&irregular, 1.0)[0].rhythm_cv - 0.6).abs() < 1e-12);
+    }
+
+    #[test]
+    fn smoothing_treats_missing_neighbours_as_zero() {
+        assert_eq!(smooth(&[3.0, 0.0, 0.0, 6.0], 1), vec![1.0, 1.0, 2.0, 2.0]);
+        assert_eq!(smooth(&[2.0, 4.0], 0), vec![2.0, 4.0]);
+        assert!(smooth(&[], 2).is_empty());
+    }
+}
@@ -0,0 +1,3 @@
+pub mod intervals;
+pub mod skillsets;
+pub mod solver;
@@ -0,0 +1,180 @@
+use crate::algorithm::msd::intervals::{build_intervals, smooth, IntervalPatterns, INTERVAL_SPAN};
+use crate::algorithm::msd::solver::{chisel, StaminaModel, PATTERN_STAMINA, STAMINA_SKILLSET};
+use crate::types::{MapData, MsdRating, Skillset, StarRatingError, StarRatingResult};
+
+/// Accuracy target of the displayed MSD
+pub const SCORE_GOAL_93: f64 = 0.93;
+/// Accuracy target of the high-accuracy variant
+pub const SCORE_GOAL_965: f64 = 0.965;
+
+/// Converts notes per second of one hand to the MSD scale
+const BASE_SCALER: f64 = 3.4;
+/// Converts same-column notes per second to the MSD scale
+const JACK_SCALER: f64 = 2.9;
+/// Per-skillset scalers, indexed like `Skillset::ALL`
+const SKILLSET_SCALERS: [f64; 8] = [0.0, 0.97, 0.92, 0.83, 0.94, 0.7, 0.84, 0.75];
+/// Smoothing radius (in intervals) of the per-hand note density
+const DENSITY_RADIUS: usize = 1;
+/// Smoothing radius (in intervals) of the sustained density used by Stamina
+const STAMINA_RADIUS: usize = 8;
+
+/// Computes MSD-style 4K skillset values
+///
+/// The chart is cut into 0.5s intervals; each hand gets a per-interval difficulty for every
+/// skillset (notes per second scaled by a pattern modifier), and each skillset value is the
+/// skill at which a player keeps `score_goal` of the points. This follows the structure of
+/// Etterna's MinaCalc without reproducing its pattern modifiers exactly.
+///
+/// # Arguments
+/// * `map_data` - Parsed 4K map data
+/// * `rate` - Music rate (1.0 = normal speed)
+/// * `score_goal` - Accuracy target, e.g. `SCORE_GOAL_93`
+///
+/// # Returns
+/// Skillset values for the given rate and accuracy target
+pub fn calculate_msd(map_data: &MapData, rate: f64, score_goal: f64) -> StarRatingResult<MsdRating> {
+    if map_data.column_count != 4 {
+        return Err(StarRatingError::InvalidFormat(format!(
+            "MSD calculator only supports 4K, got {}K",
+            map_data.column_count
+        )));
+    }
+    if rate <= 0.0 {
+        return Err(StarRatingError::InvalidFormat(format!("Invalid rate: {}", rate)));
+    }
+
+    let intervals = build_intervals(&map_data.notes, rate);
+    let mut values = [0.0; 8];
+    if intervals.is_empty() {
+        return Ok(MsdRating { rate, score_goal, values });
+    }
+
+    let points: [Vec<f64>; 2] = [0, 1].map(|hand| intervals.iter().map(|itv| itv.hands[hand].taps as f64).collect());
+    let nps: [Vec<f64>; 2] = [0, 1].map(|hand| {
+        smooth(&intervals.iter().map(|itv| itv.hands[hand].taps as f64 / INTERVAL_SPAN).collect::<Vec<_>>(), DENSITY_RADIUS)
+    });
+    let jack: [Vec<f64>; 2] = [0, 1].map(|hand| smooth(&intervals.iter().map(|itv| itv.hands[hand].jack_speed).collect::<Vec<_>>(), DENSITY_RADIUS));
+
+    let pattern_diffs = |modifier: fn(&IntervalPatterns) -> f64| -> [Vec<f64>; 2] {
+        [0, 1].map(|hand| {
+            intervals.iter().zip(nps[hand].iter())
+                .map(|(itv, &n)| n * modifier(itv) * BASE_SCALER)
+                .collect()
+        })
+    };
+    let rate_skillset = |diffs: &[Vec<f64>; 2], skillset: Skillset, stamina: StaminaModel| -> f64 {
+        chisel(diffs, &points, score_goal, stamina) * SKILLSET_SCALERS[skillset as usize]
+    };
+
+    let stream = pattern_diffs(IntervalPatterns::stream_mod);
+    let jumpstream = pattern_diffs(IntervalPatterns::jumpstream_mod);
+    let handstream = pattern_diffs(IntervalPatterns::handstream_mod);
+    let chordjack = pattern_diffs(IntervalPatterns::chordjack_mod);
+    let technical = pattern_diffs(IntervalPatterns::technical_mod);
+    let jackspeed: [Vec<f64>; 2] = [0, 1].map(|hand| jack[hand].iter().map(|&j| j * JACK_SCALER).collect());
+
+    // Stamina runs on the sustained (long-window) hardest pattern reading, so bursts count less
+    let sustained: [Vec<f64>; 2] = [0, 1].map(|hand| {
+        let hardest: Vec<f64> = (0..intervals.len())
+            .map(|i| {
+                [&stream, &jumpstream, &handstream, &chordjack, &technical]
+                    .iter()
+                    .map(|diffs| diffs[hand][i])
+                    .fold(0.0, f64::max)
+            })
+            .collect();
+        smooth(&hardest, STAMINA_RADIUS)
+    });
+
+    values[Skillset::Stream as usize] = rate_skillset(&stream, Skillset::Stream, PATTERN_STAMINA);
+    values[Skillset::Jumpstream as usize] = rate_skillset(&jumpstream, Skillset::Jumpstream, PATTERN_STAMINA);
+    values[Skillset::Handstream as usize] = rate_skillset(&handstream, Skillset::Handstream, PATTERN_STAMINA);
+    values[Skillset::Stamina as usize] = rate_skillset(&sustained, Skillset::Stamina, STAMINA_SKILLSET);
+    values[Skillset::JackSpeed as usize] = rate_skillset(&jackspeed, Skillset::JackSpeed, PATTERN_STAMINA);
+    values[Skillset::Chordjack as usize] = rate_skillset(&chordjack, Skillset::Chordjack, PATTERN_STAMINA);
+    values[Skillset::Technical as usize] = rate_skillset(&technical, Skillset::Technical, PATTERN_STAMINA);
+    values[Skillset::Overall as usize] = values[1..].iter().copied().fold(0.0, f64::max);
+
+    Ok(MsdRating { rate, score_goal, values })
+}
+
+/// Computes the skillset values for both accuracy targets (93% and 96.5%)
+pub fn calculate_msd_targets(map_data: &MapData, rate: f64) -> StarRatingResult<[MsdRating; 2]> {
+    Ok([
+        calculate_msd(map_data, rate, SCORE_GOAL_93)?,
+        calculate_msd(map_data, rate, SCORE_GOAL_965)?,
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithm::process::preprocess::preprocess_file;
+    use crate::types::Note;
+
+    fn map(column_count: usize, notes: Vec<Note>) -> MapData {
+        let mut map_data = MapData::new();
+        map_data.column_count = column_count;
+        map_data.notes = notes;
+        map_data
+    }
+
+    /// `seconds` of 16th notes at `bpm`, cycling through `rows`
+    fn pattern(bpm: f64, seconds: f64, rows: &[&[usize]]) -> MapData {
+        let step = 60_000.0 / bpm / 4.0;
+        let notes = (0..(seconds * 1000.0 / step) as usize)
+            .flat_map(|i| rows[i % rows.len()].iter().map(move |&column| Note::simple(column, i as f64 * step)))
+            .collect();
+        map(4, notes)
+    }
+
+    fn hardest(rating: &MsdRating) -> Skillset {
+        Skillset::ALL[1..].iter().copied().max_by(|&a, &b| rating.get(a).total_cmp(&rating.get(b))).unwrap()
+    }
+
+    #[test]
+    fn only_4k_maps_and_positive_rates_are_rated() {
+        let stream = pattern(150.0, 30.0, &[&[0], &[1], &[2], &[3]]);
+        assert!(matches!(calculate_msd(&map(7, stream.notes.clone()), 1.0, SCORE_GOAL_93), Err(StarRatingError::InvalidFormat(_))));
+        assert!(matches!(calculate_msd(&stream, 0.0, SCORE_GOAL_93), Err(StarRatingError::InvalidFormat(_))));
+        assert_eq!(calculate_msd(&map(4, Vec::new()), 1.0, SCORE_GOAL_93).unwrap().values, [0.0; 8]);
+    }
+
+    #[test]
+    fn overall_is_the_hardest_skillset() {
+        let rating = calculate_msd(&preprocess_file("assets/test.osu", "None").unwrap(), 1.0, SCORE_GOAL_93).unwrap();
+        assert!(rating.overall() > 0.0);
+        assert_eq!(rating.overall(), rating.get(hardest(&rating)));
+    }
+
+    #[test]
+    fn patterns_land_in_their_skillset() {
+        // Short charts, so that Stamina does not take over
+        let stream = pattern(180.0, 10.0, &[&[0], &[2], &[1], &[3]]);
+        assert_eq!(hardest(&calculate_msd(&stream, 1.0, SCORE_GOAL_93).unwrap()), Skillset::Stream);
+
+        let jumpstream = pattern(180.0, 10.0, &[&[0, 2], &[1], &[0, 3], &[2], &[1, 3], &[0], &[1, 2], &[3]]);
+        assert_eq!(hardest(&calculate_msd(&jumpstream, 1.0, SCORE_GOAL_93).unwrap()), Skillset::Jumpstream);
+
+        let chordjack = pattern(120.0, 10.0, &[&[0, 1, 2], &[1, 2, 3], &[0, 2, 3], &[0, 1, 3]]);
+        assert_eq!(hardest(&calculate_msd(&chordjack, 1.0, SCORE_GOAL_93).unwrap()), Skillset::Chordjack);
+    }
+
+    #[test]
+    fn faster_rates_and_higher_goals_are_harder() {
+        let map_data = preprocess_file("assets/test.osu", "None").unwrap();
+        let [normal, high_accuracy] = calculate_msd_targets(&map_data, 1.0).unwrap();
+        let faster = calculate_msd(&map_data, 1.2, SCORE_GOAL_93).unwrap();
+        assert!(high_accuracy.overall() > normal.overall());
+        assert!(faster.overall() > normal.overall());
+    }
+
+    #[test]
+    fn rate_matches_a_map_with_scaled_times() {
+        let map_data = preprocess_file("assets/test.osu", "None").unwrap();
+        let scaled = map(4, map_data.notes.iter().map(|note| Note::simple(note.column, note.hit_time / 2.0)).collect());
+        let rated = calculate_msd(&map_data, 2.0, SCORE_GOAL_93).unwrap();
+        let expected = calculate_msd(&scaled, 1.0, SCORE_GOAL_93).unwrap();
+        assert_eq!(rated.values.map(f64::to_bits), expected.values.map(f64::to_bits));
+    }
+}
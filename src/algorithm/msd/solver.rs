@@ -0,0 +1,82 @@
+/// Exponent of the points-lost curve below an interval's difficulty
+const POINTS_WEIGHTING: f64 = 1.7;
+
+/// Stamina model: difficulty drifts up while the player is pushed above a fraction of their skill
+#[derive(Debug, Clone, Copy)]
+pub struct StaminaModel {
+    /// Fraction of the player's skill above which fatigue builds up
+    pub prop: f64,
+    /// Inertia of the fatigue multiplier (higher = slower)
+    pub fscale: f64,
+    /// Lowest multiplier
+    pub floor: f64,
+    /// Highest multiplier
+    pub ceil: f64,
+}
+
+/// Mild stamina drift applied to every pattern skillset
+pub const PATTERN_STAMINA: StaminaModel = StaminaModel { prop: 0.69, fscale: 500.0, floor: 0.5, ceil: 1.075 };
+
+/// Stronger drift used for the Stamina skillset
+pub const STAMINA_SKILLSET: StaminaModel = StaminaModel { prop: 0.6, fscale: 250.0, floor: 0.5, ceil: 1.15 };
+
+/// Applies the stamina multiplier for a given player skill
+fn stamina_adjust(x: f64, diffs: &[f64], model: StaminaModel, out: &mut Vec<f64>) {
+    out.clear();
+    let mut multiplier: f64 = 1.0;
+    let mut previous = 0.0;
+    for &diff in diffs {
+        let avg = (previous + diff) / 2.0;
+        previous = diff;
+        multiplier += (avg / (model.prop * x) - 1.0) / model.fscale;
+        multiplier = multiplier.clamp(model.floor, model.ceil);
+        out.push(diff * multiplier);
+    }
+}
+
+/// Points a player of skill `x` keeps over both hands
+fn achieved_points(x: f64, diffs: &[Vec<f64>; 2], points: &[Vec<f64>; 2], stamina: StaminaModel, scratch: &mut Vec<f64>) -> f64 {
+    let mut achieved = 0.0;
+    for hand in 0..2 {
+        stamina_adjust(x, &diffs[hand], stamina, scratch);
+        for (&diff, &pts) in scratch.iter().zip(points[hand].iter()) {
+            achieved += pts;
+            if x < diff {
+                achieved -= pts * (1.0 - (x / diff).powf(POINTS_WEIGHTING));
+            }
+        }
+    }
+    achieved
+}
+
+/// Finds the lowest skill at which a player keeps `score_goal` of the points
+///
+/// Coarse-to-fine search as in MinaCalc's Chisel: step up until the goal is reached,
+/// step back once and halve the resolution.
+///
+/// # Arguments
+/// * `diffs` - Per-interval difficulty of each hand
+/// * `points` - Per-interval points (notes) of each hand
+/// * `score_goal` - Accuracy target (0..1)
+/// * `stamina` - Stamina model
+///
+/// # Returns
+/// Skill value reaching the goal
+pub fn chisel(diffs: &[Vec<f64>; 2], points: &[Vec<f64>; 2], score_goal: f64, stamina: StaminaModel) -> f64 {
+    let total: f64 = points.iter().flat_map(|hand| hand.iter()).sum();
+    if total <= 0.0 {
+        return 0.0;
+    }
+    let required = total * score_goal;
+    let mut scratch = Vec::with_capacity(diffs[0].len());
+    let mut skill: f64 = 0.0;
+    let mut resolution = 10.24;
+    for _ in 0..8 {
+        while skill < 100.0 && achieved_points(skill.max(1e-3), diffs, points, stamina, &mut scratch) < required {
+            skill += resolution;
+        }
+        skill = (skill - resolution).max(0.0);
+        resolution /= 2.0;
+    }
+    skill + 2.0 * resolution
+}
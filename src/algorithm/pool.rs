@@ -0,0 +1,176 @@
+use std::ops::{Deref, DerefMut};
+use std::sync::{Mutex, PoisonError};
+
+/// Free list of scratch buffers reused across calculations
+///
+/// Buffers handed out by `take` are always reset to the requested length and
+/// fill value, so pooled and freshly allocated code paths compute the same values.
+#[derive(Debug, Default)]
+pub struct BufferPool {
+    values: Vec<Vec<f64>>,
+    indices: Vec<Vec<usize>>,
+}
+
+impl BufferPool {
+    /// Creates an empty pool
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pops the smallest buffer holding `capacity` values, or the largest one if none does
+    fn pop_fitting(&mut self, capacity: usize) -> Option<Vec<f64>> {
+        let best = self.values.iter()
+            .enumerate()
+            .min_by_key(|(_, buffer)| {
+                let cap = buffer.capacity();
+                if cap >= capacity { (0, cap) } else { (1, usize::MAX - cap) }
+            })
+            .map(|(idx, _)| idx)?;
+        Some(self.values.swap_remove(best))
+    }
+
+    /// Takes a buffer of `len` values set to `value`
+    pub fn take(&mut self, len: usize, value: f64) -> Vec<f64> {
+        match self.pop_fitting(len) {
+            Some(mut buffer) => {
+                buffer.clear();
+                buffer.resize(len, value);
+                buffer
+            }
+            None => vec![value; len],
+        }
+    }
+
+    /// Takes an empty buffer with at least `capacity` reserved
+    pub fn take_empty(&mut self, capacity: usize) -> Vec<f64> {
+        match self.pop_fitting(capacity) {
+            Some(mut buffer) => {
+                buffer.clear();
+                buffer.reserve(capacity);
+                buffer
+            }
+            None => Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Returns a buffer to the pool
+    pub fn give(&mut self, buffer: Vec<f64>) {
+        if buffer.capacity() > 0 {
+            self.values.push(buffer);
+        }
+    }
+
+    /// Returns several buffers to the pool
+    pub fn give_all<I: IntoIterator<Item = Vec<f64>>>(&mut self, buffers: I) {
+        for buffer in buffers {
+            self.give(buffer);
+        }
+    }
+
+    /// Takes an empty index buffer with at least `capacity` reserved
+    pub fn take_indices(&mut self, capacity: usize) -> Vec<usize> {
+        match self.indices.pop() {
+            Some(mut buffer) => {
+                buffer.clear();
+                buffer.reserve(capacity);
+                buffer
+            }
+            None => Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Returns an index buffer to the pool
+    pub fn give_indices(&mut self, buffer: Vec<usize>) {
+        if buffer.capacity() > 0 {
+            self.indices.push(buffer);
+        }
+    }
+
+    /// Moves every buffer of `other` into this pool
+    pub fn absorb(&mut self, other: BufferPool) {
+        self.values.extend(other.values);
+        self.indices.extend(other.indices);
+    }
+
+    /// Number of buffers currently held by the pool
+    pub fn len(&self) -> usize {
+        self.values.len() + self.indices.len()
+    }
+
+    /// Returns true if the pool holds no buffer
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Buffer pools shared between tasks that may run concurrently
+///
+/// Each task leases a whole pool for its duration; when tasks run one after the
+/// other they keep reusing the same pool, when they overlap extra pools are created
+/// and merged back by `into_inner`.
+#[derive(Debug, Default)]
+pub struct SharedPool {
+    pools: Mutex<Vec<BufferPool>>,
+}
+
+impl SharedPool {
+    /// Wraps a pool so that it can be leased from several tasks
+    pub fn new(pool: BufferPool) -> Self {
+        Self { pools: Mutex::new(vec![pool]) }
+    }
+
+    /// Leases a pool until the returned guard is dropped
+    pub fn lease(&self) -> PoolLease<'_> {
+        let pool = self.pools.lock().unwrap_or_else(PoisonError::into_inner).pop().unwrap_or_default();
+        PoolLease { owner: self, pool: Some(pool) }
+    }
+
+    /// Number of buffers held by the pools not currently leased
+    pub fn len(&self) -> usize {
+        self.pools.lock().unwrap_or_else(PoisonError::into_inner).iter().map(BufferPool::len).sum()
+    }
+
+    /// Returns true if the pools not currently leased hold no buffer
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Merges every pool back into one
+    pub fn into_inner(self) -> BufferPool {
+        let mut pools = self.pools.into_inner().unwrap_or_else(PoisonError::into_inner).into_iter();
+        let mut merged = pools.next().unwrap_or_default();
+        for pool in pools {
+            merged.absorb(pool);
+        }
+        merged
+    }
+}
+
+/// Pool leased from a `SharedPool`, given back on drop
+#[derive(Debug)]
+pub struct PoolLease<'a> {
+    owner: &'a SharedPool,
+    pool: Option<BufferPool>,
+}
+
+impl Deref for PoolLease<'_> {
+    type Target = BufferPool;
+
+    fn deref(&self) -> &BufferPool {
+        self.pool.as_ref().expect("leased pool is present until drop")
+    }
+}
+
+impl DerefMut for PoolLease<'_> {
+    fn deref_mut(&mut self) -> &mut BufferPool {
+        self.pool.as_mut().expect("leased pool is present until drop")
+    }
+}
+
+impl Drop for PoolLease<'_> {
+    fn drop(&mut self) {
+        if let Some(pool) = self.pool.take() {
+            self.owner.pools.lock().unwrap_or_else(PoisonError::into_inner).push(pool);
+        }
+    }
+}
@@ -0,0 +1,197 @@
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
+use std::thread;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::task::JoinError;
+use super::preprocess::preprocess_reader_with_options;
+use super::process::calculate;
+use crate::types::{CalculationError, MapData, ParseOptions, StarRating, StarRatingError, StarRatingResult};
+
+/// Async front end offloading parsing and rating to tokio's blocking pool
+///
+/// At most `limit` files are read, parsed or rated at once; further calls wait for
+/// a slot. Clones share the same limit.
+#[derive(Debug, Clone)]
+pub struct AsyncCalculator {
+    semaphore: Arc<Semaphore>,
+}
+
+impl AsyncCalculator {
+    /// Creates a calculator running at most `limit` jobs at once (0 = available parallelism)
+    pub fn new(limit: usize) -> Self {
+        let limit = if limit == 0 { default_limit() } else { limit };
+        Self { semaphore: Arc::new(Semaphore::new(limit)) }
+    }
+
+    /// Number of jobs that can start right now
+    pub fn available_slots(&self) -> usize {
+        self.semaphore.available_permits()
+    }
+
+    /// Reads and preprocesses a .osu file
+    ///
+    /// # Arguments
+    /// * `file_path` - Path to the .osu file
+    /// * `mod_name` - Mod to apply ("DT", "HT", anything else = none)
+    ///
+    /// # Returns
+    /// Parsed map data
+    pub async fn preprocess_file<P: AsRef<Path>>(&self, file_path: P, mod_name: &str) -> StarRatingResult<MapData> {
+        self.preprocess_file_with_options(file_path, mod_name, &ParseOptions::default()).await
+    }
+
+    /// Reads and preprocesses a .osu file with the given parsing options
+    ///
+    /// # Arguments
+    /// * `file_path` - Path to the .osu file
+    /// * `mod_name` - Mod to apply ("DT", "HT", anything else = none)
+    /// * `options` - Parsing options
+    ///
+    /// # Returns
+    /// Parsed map data; a file that cannot be read gives a `FileError`
+    pub async fn preprocess_file_with_options<P: AsRef<Path>>(
+        &self,
+        file_path: P,
+        mod_name: &str,
+        options: &ParseOptions,
+    ) -> StarRatingResult<MapData> {
+        let permit = self.acquire().await?;
+        let file_path = file_path.as_ref();
+        let content = tokio::fs::read(file_path)
+            .await
+            .map_err(|e| StarRatingError::io(file_path.display(), e))?;
+        let mod_name = mod_name.to_string();
+        let options = options.clone();
+        tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            preprocess_reader_with_options(&content[..], &mod_name, &options)
+        })
+        .await
+        .map_err(blocking_error)?
+    }
+
+    /// Star rating of a map, computed on the blocking pool
+    ///
+    /// # Arguments
+    /// * `map_data` - Parsed map data (shared, so callers can keep it)
+    ///
+    /// # Returns
+    /// Detailed star rating calculation result
+    pub async fn calculate<M: Into<Arc<MapData>>>(&self, map_data: M) -> StarRatingResult<StarRating> {
+        let permit = self.acquire().await?;
+        let map_data = map_data.into();
+        tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            calculate(&map_data)
+        })
+        .await
+        .map_err(blocking_error)?
+    }
+
+    /// Waits for a slot; the permit moves into the blocking task so that it is held
+    /// until the work really ends, even if the calling future is dropped
+    async fn acquire(&self) -> StarRatingResult<OwnedSemaphorePermit> {
+        Arc::clone(&self.semaphore)
+            .acquire_owned()
+            .await
+            .map_err(|e| CalculationError::Aborted(format!("async calculator closed: {}", e)).into())
+    }
+}
+
+impl Default for AsyncCalculator {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+/// Reads and preprocesses a .osu file without blocking the async runtime
+///
+/// Shares a process-wide limit of one job per available core with `calculate_async`.
+///
+/// # Arguments
+/// * `file_path` - Path to the .osu file
+/// * `mod_name` - Mod to apply ("DT", "HT", anything else = none)
+///
+/// # Returns
+/// Parsed map data
+pub async fn preprocess_file_async<P: AsRef<Path>>(file_path: P, mod_name: &str) -> StarRatingResult<MapData> {
+    shared_calculator().preprocess_file(file_path, mod_name).await
+}
+
+/// Reads and preprocesses a .osu file with the given parsing options without blocking
+/// the async runtime
+///
+/// Shares the process-wide limit of `preprocess_file_async`.
+///
+/// # Arguments
+/// * `file_path` - Path to the .osu file
+/// * `mod_name` - Mod to apply ("DT", "HT", anything else = none)
+/// * `options` - Parsing options
+///
+/// # Returns
+/// Parsed map data
+pub async fn preprocess_file_with_options_async<P: AsRef<Path>>(
+    file_path: P,
+    mod_name: &str,
+    options: &ParseOptions,
+) -> StarRatingResult<MapData> {
+    shared_calculator().preprocess_file_with_options(file_path, mod_name, options).await
+}
+
+/// Star rating of a map without blocking the async runtime
+///
+/// Shares a process-wide limit of one job per available core with `preprocess_file_async`.
+///
+/// # Arguments
+/// * `map_data` - Parsed map data (shared, so callers can keep it)
+///
+/// # Returns
+/// Detailed star rating calculation result
+pub async fn calculate_async<M: Into<Arc<MapData>>>(map_data: M) -> StarRatingResult<StarRating> {
+    shared_calculator().calculate(map_data).await
+}
+
+/// Calculator behind the free functions
+fn shared_calculator() -> &'static AsyncCalculator {
+    static SHARED: OnceLock<AsyncCalculator> = OnceLock::new();
+    SHARED.get_or_init(AsyncCalculator::default)
+}
+
+/// One job per available core
+fn default_limit() -> usize {
+    thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+}
+
+/// Turns a failed or cancelled blocking task into a calculation error
+fn blocking_error(error: JoinError) -> StarRatingError {
+    CalculationError::Aborted(format!("blocking task failed: {}", error)).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithm::process::preprocess::preprocess_file_with_options;
+    use std::io;
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(future)
+    }
+
+    #[test]
+    fn missing_file_is_a_file_error() {
+        let result = block_on(AsyncCalculator::new(1).preprocess_file("assets/missing.osu", "None"));
+        match result {
+            Err(StarRatingError::FileError(e)) => assert_eq!(e.kind(), io::ErrorKind::NotFound),
+            other => panic!("expected a FileError, got {:?}", other.map(|map_data| map_data.notes.len())),
+        }
+    }
+
+    #[test]
+    fn options_reach_the_parser() {
+        let options = ParseOptions::strict();
+        let expected = preprocess_file_with_options("assets/test.osu", "DT", &options).unwrap();
+        let map_data = block_on(AsyncCalculator::new(1).preprocess_file_with_options("assets/test.osu", "DT", &options)).unwrap();
+        assert_eq!(map_data.notes, expected.notes);
+        assert_eq!(map_data.overall_difficulty.to_bits(), expected.overall_difficulty.to_bits());
+    }
+}
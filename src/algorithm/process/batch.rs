@@ -0,0 +1,262 @@
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use super::calculator::Calculator;
+use super::preprocess::preprocess_file_with_options;
+use crate::types::{
+    BatchOptions, BatchProgress, BatchRating, BatchResult, CalculationError, MapData, ParseOptions, StarRatingResult,
+};
+
+/// Rates a list of files on a pool of worker threads
+///
+/// Each worker owns a pooled `Calculator`. A file that fails to parse, fails to
+/// rate or panics is reported as an error result and does not stop the batch.
+///
+/// # Arguments
+/// * `paths` - Files to rate
+/// * `options` - Workers, mod, cancellation and progress callback
+///
+/// # Returns
+/// Stream of results in completion order
+pub fn rate_files(paths: Vec<PathBuf>, options: &BatchOptions) -> BatchStream {
+    let total = paths.len();
+    let workers = if options.workers == 0 {
+        thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+    } else {
+        options.workers
+    }
+    .min(total.max(1));
+
+    let paths: Arc<[PathBuf]> = paths.into();
+    let next = Arc::new(AtomicUsize::new(0));
+    let completed = Arc::new(AtomicUsize::new(0));
+    let (sender, receiver) = mpsc::channel();
+
+    let handles = (0..workers)
+        .map(|_| {
+            let paths = Arc::clone(&paths);
+            let next = Arc::clone(&next);
+            let completed = Arc::clone(&completed);
+            let sender = sender.clone();
+            let options = options.clone();
+            thread::spawn(move || {
+                let mut calculator = Calculator::new();
+                // only the rating is kept, the sections would be dropped unread
+                let parse_options = ParseOptions::new().with_keep_sections(false);
+                while !options.cancellation.is_cancelled() {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(path) = paths.get(index) else { break };
+                    let outcome = rate_isolated(
+                        || preprocess_file_with_options(&path.to_string_lossy(), &options.mod_name, &parse_options),
+                        &mut calculator,
+                    );
+                    if let Some(progress) = &options.progress {
+                        progress(&BatchProgress {
+                            completed: completed.fetch_add(1, Ordering::Relaxed) + 1,
+                            total,
+                            path: path.clone(),
+                            succeeded: outcome.is_ok(),
+                        });
+                    }
+                    let result = BatchResult { index, path: path.clone(), outcome };
+                    if sender.send(result).is_err() {
+                        // the stream was dropped, nobody wants the remaining results
+                        break;
+                    }
+                }
+            })
+        })
+        .collect();
+
+    BatchStream {
+        receiver: Some(receiver),
+        handles,
+        total,
+    }
+}
+
+/// Rates every .osu file under a directory, recursively
+///
+/// # Arguments
+/// * `dir` - Root directory
+/// * `options` - Workers, mod, cancellation and progress callback
+///
+/// # Returns
+/// Stream of results in completion order, or an error if `dir` cannot be read
+pub fn rate_directory<P: AsRef<Path>>(dir: P, options: &BatchOptions) -> StarRatingResult<BatchStream> {
+    Ok(rate_files(collect_osu_files(dir)?, options))
+}
+
+/// Lists the .osu files under a directory, recursively, in path order
+///
+/// Unreadable subdirectories are skipped; only the root must be readable.
+///
+/// # Arguments
+/// * `dir` - Root directory
+///
+/// # Returns
+/// Sorted paths of the .osu files
+pub fn collect_osu_files<P: AsRef<Path>>(dir: P) -> StarRatingResult<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.as_ref().to_path_buf()];
+    let mut root = true;
+    while let Some(current) = pending.pop() {
+        let entries = match fs::read_dir(&current) {
+            Ok(entries) => entries,
+            Err(e) if root => return Err(e.into()),
+            Err(_) => continue,
+        };
+        root = false;
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Ok(file_type) = entry.file_type() else { continue };
+            if file_type.is_dir() {
+                pending.push(path);
+            } else if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("osu")) {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Loads and rates one map, turning a panic in either step into an error result
+///
+/// # Arguments
+/// * `load` - Reads the map
+/// * `calculator` - Pooled calculator, replaced by a new one after a panic
+///
+/// # Returns
+/// Rating and note counts, or the error (or panic) that prevented them
+pub(crate) fn rate_isolated<F>(load: F, calculator: &mut Calculator) -> StarRatingResult<BatchRating>
+where
+    F: FnOnce() -> StarRatingResult<MapData>,
+{
+    let outcome = panic::catch_unwind(AssertUnwindSafe(|| {
+        let map_data = load()?;
+        let star_rating = calculator.calculate(&map_data)?;
+        Ok(BatchRating {
+            rating: star_rating.rating,
+            column_count: map_data.column_count,
+            note_count: map_data.note_count(),
+            long_note_count: map_data.long_note_count(),
+        })
+    }));
+    outcome.unwrap_or_else(|payload| {
+        // the pool may hold half-written buffers after a panic
+        *calculator = Calculator::new();
+        let message = payload.downcast_ref::<&str>().map(|s| s.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".to_string());
+        Err(CalculationError::Aborted(format!("panicked: {}", message)).into())
+    })
+}
+
+/// Results of a running batch, yielded as files finish
+///
+/// Dropping the stream stops the workers after their current file.
+pub struct BatchStream {
+    receiver: Option<Receiver<BatchResult>>,
+    handles: Vec<JoinHandle<()>>,
+    total: usize,
+}
+
+impl BatchStream {
+    /// Number of files in the batch
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    /// Waits for the whole batch and returns the results in input order
+    pub fn into_sorted(self) -> Vec<BatchResult> {
+        let mut results: Vec<BatchResult> = self.collect();
+        results.sort_by_key(|result| result.index);
+        results
+    }
+}
+
+impl Iterator for BatchStream {
+    type Item = BatchResult;
+
+    fn next(&mut self) -> Option<BatchResult> {
+        self.receiver.as_ref()?.recv().ok()
+    }
+}
+
+impl Drop for BatchStream {
+    fn drop(&mut self) {
+        // close the channel first so that workers stop at their next send
+        self.receiver.take();
+        for handle in self.handles.drain(..) {
+            let _ = handle.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use crate::types::{CancellationToken, StarRatingError};
+
+    #[test]
+    fn failing_files_do_not_stop_the_batch() {
+        let paths: Vec<PathBuf> = ["assets/test.osu", "assets/missing.osu", "assets/benchmark.osu"].map(PathBuf::from).into();
+        let progress = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&progress);
+        let options = BatchOptions::new()
+            .with_workers(2)
+            .with_progress(move |update| recorded.lock().unwrap().push((update.completed, update.total, update.path.clone(), update.succeeded)));
+
+        let results = rate_files(paths.clone(), &options).into_sorted();
+        let outcomes: Vec<(usize, bool)> = results.iter().map(|result| (result.index, result.outcome.is_ok())).collect();
+        assert_eq!(outcomes, [(0, true), (1, false), (2, true)]);
+        assert!(matches!(results[1].outcome, Err(StarRatingError::FileError(_))));
+
+        let mut progress = progress.lock().unwrap().clone();
+        progress.sort_by_key(|update| update.0);
+        assert_eq!(progress.iter().map(|update| (update.0, update.1)).collect::<Vec<_>>(), [(1, 3), (2, 3), (3, 3)]);
+        for (path, succeeded) in [(&paths[0], true), (&paths[1], false), (&paths[2], true)] {
+            assert!(progress.iter().any(|update| &update.2 == path && update.3 == succeeded), "{:?}", path);
+        }
+    }
+
+    #[test]
+    fn cancelling_stops_before_the_next_file() {
+        let cancellation = CancellationToken::new();
+        let token = cancellation.clone();
+        let options = BatchOptions::new()
+            .with_workers(1)
+            .with_cancellation(cancellation)
+            .with_progress(move |_| token.cancel());
+
+        let stream = rate_files(vec![PathBuf::from("assets/test.osu"); 5], &options);
+        assert_eq!(stream.total(), 5);
+        let results = stream.into_sorted();
+        // the file being rated when the token was cancelled is still reported
+        let [result] = &results[..] else { panic!("expected one result, got {}", results.len()) };
+        assert_eq!(result.index, 0);
+        assert!(result.outcome.is_ok());
+    }
+
+    #[test]
+    fn panics_become_errors_and_reset_the_pool() {
+        let mut calculator = Calculator::new();
+        let good = || crate::algorithm::process::preprocess::preprocess_file("assets/test.osu", "None");
+        let expected = rate_isolated(good, &mut calculator).unwrap();
+        assert!(calculator.pooled_buffers() > 0);
+
+        let outcome = rate_isolated(|| panic!("broken map"), &mut calculator);
+        match outcome {
+            Err(StarRatingError::CalculationError(CalculationError::Aborted(message))) => assert_eq!(message, "panicked: broken map"),
+            other => panic!("expected Aborted, got {:?}", other),
+        }
+        assert_eq!(calculator.pooled_buffers(), 0);
+        assert_eq!(rate_isolated(good, &mut calculator).unwrap(), expected);
+    }
+}
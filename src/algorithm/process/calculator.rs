@@ -0,0 +1,171 @@
+use crate::algorithm::bars::skill::Skill;
+use crate::algorithm::calculator::{at_clock_rate, DifficultyCalculator};
+use crate::algorithm::pool::{BufferPool, SharedPool};
+use crate::algorithm::process::approximate::calculate_approximate_internal;
+use crate::algorithm::process::chunked::calculate_chunked_internal;
+use crate::algorithm::process::process::calculate_internal;
+use crate::types::{CalculationContext, DifficultyResult, MapData, StarRating, StarRatingResult};
+
+/// Reusable star rating calculator (Sunny's rework, this crate's algorithm)
+///
+/// Owns the scratch buffers of the calculation and reuses them from one map to
+/// the next, so rating many maps in a row stops allocating corner-sized vectors
+/// once the pool is warm. Ratings are bit-identical to `process::calculate`.
+///
+/// Also usable through `DifficultyCalculator` as "ssrrr"; calls through `&self`
+/// lease the pool, so concurrent calls each get their own.
+#[derive(Default)]
+pub struct Calculator {
+    pool: SharedPool,
+    extra_skills: Vec<Box<dyn Skill>>,
+}
+
+impl Calculator {
+    /// Creates a calculator with an empty buffer pool
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers an extra skill, evaluated on the same corners as the built-in bars
+    /// and folded into the S/T/D combination
+    pub fn register_skill(&mut self, skill: Box<dyn Skill>) -> &mut Self {
+        self.extra_skills.push(skill);
+        self
+    }
+
+    /// Registered extra skills
+    pub fn extra_skills(&self) -> &[Box<dyn Skill>] {
+        &self.extra_skills
+    }
+
+    /// Star rating of a map, reusing the pooled buffers
+    ///
+    /// # Arguments
+    /// * `map_data` - Parsed map data
+    ///
+    /// # Returns
+    /// Detailed star rating calculation result
+    pub fn calculate(&self, map_data: &MapData) -> StarRatingResult<StarRating> {
+        let rating = calculate_internal(map_data, &self.extra_skills, &mut self.pool.lease())?;
+
+        Ok(StarRating::rating_only(rating))
+    }
+
+    /// Star rating computed window by window (see `chunked::calculate_chunked`), reusing the pooled buffers
+    ///
+    /// # Arguments
+    /// * `map_data` - Parsed map data
+    /// * `window_ms` - Length of each window in milliseconds
+    ///
+    /// # Returns
+    /// Detailed star rating calculation result
+    pub fn calculate_chunked(&self, map_data: &MapData, window_ms: i64) -> StarRatingResult<StarRating> {
+        let rating = calculate_chunked_internal(map_data, &self.extra_skills, window_ms, &mut self.pool.lease())?;
+
+        Ok(StarRating::rating_only(rating))
+    }
+
+    /// Approximate star rating on a fixed time grid (see `approximate::calculate_approximate`), reusing the pooled buffers
+    ///
+    /// # Arguments
+    /// * `map_data` - Parsed map data
+    /// * `grid_ms` - Grid spacing in milliseconds
+    ///
+    /// # Returns
+    /// Detailed star rating calculation result
+    pub fn calculate_approximate(&self, map_data: &MapData, grid_ms: i64) -> StarRatingResult<StarRating> {
+        let rating = calculate_approximate_internal(map_data, &self.extra_skills, grid_ms, &mut self.pool.lease())?;
+
+        Ok(StarRating::rating_only(rating))
+    }
+
+    /// Number of buffers currently held by the pool
+    pub fn pooled_buffers(&self) -> usize {
+        self.pool.len()
+    }
+
+    /// Drops every pooled buffer
+    pub fn clear(&mut self) {
+        self.pool = SharedPool::new(BufferPool::new());
+    }
+}
+
+impl DifficultyCalculator for Calculator {
+    fn name(&self) -> &'static str {
+        "ssrrr"
+    }
+
+    fn calculate(&self, map_data: &MapData, context: &CalculationContext) -> StarRatingResult<DifficultyResult> {
+        let rated = at_clock_rate(map_data, context.clock_rate);
+        let rating = calculate_internal(&rated, &self.extra_skills, &mut self.pool.lease())?;
+        Ok(DifficultyResult {
+            calculator: self.name(),
+            clock_rate: context.clock_rate,
+            rating,
+            skillsets: Vec::new(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithm::process::preprocess::preprocess_file;
+    use crate::algorithm::process::process::calculate;
+
+    /// Every .osu file of assets/, sorted
+    fn asset_maps() -> Vec<String> {
+        let mut paths: Vec<String> = std::fs::read_dir("assets")
+            .expect("assets directory")
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "osu"))
+            .map(|path| path.to_string_lossy().into_owned())
+            .collect();
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn pooled_ratings_are_bit_identical_to_calculate() {
+        let calculator = Calculator::new();
+        for path in asset_maps() {
+            for mod_name in ["None", "DT", "HT"] {
+                let map_data = preprocess_file(&path, mod_name).unwrap();
+                let expected = calculate(&map_data).unwrap().rating;
+                let pooled = calculator.calculate(&map_data).unwrap().rating;
+                assert_eq!(pooled.to_bits(), expected.to_bits(), "{} {}", path, mod_name);
+            }
+        }
+        assert!(calculator.pooled_buffers() > 0);
+    }
+
+    #[test]
+    fn trait_ratings_match_preprocessed_mods() {
+        let calculator = Calculator::new();
+        for path in asset_maps() {
+            let map_data = preprocess_file(&path, "None").unwrap();
+            for mod_name in ["None", "DT", "HT"] {
+                let expected = calculate(&preprocess_file(&path, mod_name).unwrap()).unwrap().rating;
+                let result = DifficultyCalculator::calculate(&calculator, &map_data, &CalculationContext::from_mod(mod_name)).unwrap();
+                assert_eq!(result.rating.to_bits(), expected.to_bits(), "{} {}", path, mod_name);
+            }
+        }
+    }
+
+    #[test]
+    fn registered_skills_reach_every_entry_point() {
+        use crate::algorithm::bars::skill::JackSkill;
+        use crate::algorithm::process::process::calculate_with_skills;
+
+        let mut calculator = Calculator::new();
+        calculator.register_skill(Box::new(JackSkill));
+        assert_eq!(calculator.extra_skills().len(), 1);
+
+        let map_data = preprocess_file("assets/test.osu", "None").unwrap();
+        let expected = calculate_with_skills(&map_data, calculator.extra_skills()).unwrap().rating;
+        assert_ne!(expected.to_bits(), calculate(&map_data).unwrap().rating.to_bits());
+        assert_eq!(calculator.calculate(&map_data).unwrap().rating.to_bits(), expected.to_bits());
+        let result = DifficultyCalculator::calculate(&calculator, &map_data, &CalculationContext::default()).unwrap();
+        assert_eq!((result.calculator, result.rating.to_bits()), ("ssrrr", expected.to_bits()));
+    }
+}
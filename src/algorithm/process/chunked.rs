@@ -0,0 +1,296 @@
+use crate::algorithm::bars::skill::Skill;
+use crate::algorithm::pool::BufferPool;
+use crate::types::{CalculationError, MapData, Note, StarRating, StarRatingResult};
+use super::normalize::rebuild_groupings;
+use super::process::{corner_difficulties, phase5, sort_by_difficulty_with_pool, weighted_percentiles};
+
+/// Default length (ms) of the timeline window rated at once
+pub const DEFAULT_CHUNK_MS: i64 = 60_000;
+
+/// Margin (ms) around each window whose corners are computed but not kept
+///
+/// Covers the widest smoothing window (±500 ms) used by the bars.
+const WINDOW_MARGIN_MS: f64 = 1_000.0;
+
+/// Extra range (ms) of notes loaded around the margin
+///
+/// Every corner candidate lies within ±1000 ms of a note, so the corners inside the
+/// margin are the same as in the one-shot computation.
+const NOTE_PADDING_MS: f64 = 1_000.0;
+
+/// Pairs held by `DifficultySummary` before it is compacted (4 MiB, about 50 minutes of dense corners)
+const SUMMARY_CAPACITY: usize = 1 << 18;
+
+/// Star rating computed window by window, with a peak memory bounded by the window size
+///
+/// The timeline is cut into windows of `window_ms`. Each window is rated on a reduced
+/// map holding the notes around it (plus the neighbouring notes every bar looks up),
+/// and only the difficulty and weight of the corners inside the window are kept, in a
+/// summary of bounded size (see `DifficultySummary`). Maps of up to `SUMMARY_CAPACITY`
+/// corners are summarized exactly and agree with `process::calculate` up to
+/// floating-point rounding.
+///
+/// # Arguments
+/// * `map_data` - Parsed map data
+/// * `window_ms` - Length of each window in milliseconds (e.g. `DEFAULT_CHUNK_MS`)
+///
+/// # Returns
+/// Detailed star rating calculation result
+pub fn calculate_chunked(map_data: &MapData, window_ms: i64) -> StarRatingResult<StarRating> {
+    let rating = calculate_chunked_internal(map_data, &[], window_ms, &mut BufferPool::new())?;
+
+    Ok(StarRating::rating_only(rating))
+}
+
+/// Chunked calculation reusing `pool` from one window to the next
+pub(crate) fn calculate_chunked_internal(
+    map_data: &MapData,
+    extra_skills: &[Box<dyn Skill>],
+    window_ms: i64,
+    pool: &mut BufferPool,
+) -> StarRatingResult<f64> {
+    if window_ms <= 0 {
+        return Err(CalculationError::InvalidNumber("chunk window (ms)".to_string(), window_ms as f64).into());
+    }
+
+    // Corners span [0, T]; the last window also keeps T itself
+    let total_duration = map_data.total_duration;
+    let window_ms = window_ms as f64;
+    let mut summary = DifficultySummary::new();
+    let mut cursor = WindowCursor::default();
+    let mut window_start = 0.0;
+    while window_start <= total_duration {
+        let window_end = window_start + window_ms;
+        let last = window_end > total_duration;
+        let window_map = window_map_data(
+            map_data,
+            &mut cursor,
+            window_start - WINDOW_MARGIN_MS - NOTE_PADDING_MS,
+            window_end + WINDOW_MARGIN_MS + NOTE_PADDING_MS,
+        );
+
+        let (all_corners, d_all, weights) = corner_difficulties(&window_map, extra_skills, pool)?;
+        let from = all_corners.partition_point(|&c| c < window_start);
+        let to = if last { all_corners.len() } else { all_corners.partition_point(|&c| c < window_end) };
+        summary.add(&d_all[from..to], &weights[from..to], pool);
+        pool.give_all([all_corners, d_all, weights]);
+
+        window_start = window_end;
+    }
+
+    let (percentile_93, percentile_83, weighted_mean) = summary.finish(pool);
+    Ok(phase5(percentile_93, percentile_83, weighted_mean, &map_data.notes, &map_data.long_notes))
+}
+
+/// (difficulty, weight) pairs of the kept corners, sorted by difficulty, in bounded memory
+///
+/// The pairs of each window are sorted and merged in. Past `SUMMARY_CAPACITY` pairs,
+/// neighbouring pairs are combined into one holding both weights and the higher
+/// difficulty: the cumulative weight up to every remaining value stays exact, so a
+/// percentile can only move up to the next remaining value. The power mean is
+/// accumulated from the pairs as they come, before any compaction.
+struct DifficultySummary {
+    d_sorted: Vec<f64>,
+    w_sorted: Vec<f64>,
+    power_sum: f64,
+    weight_sum: f64,
+}
+
+impl DifficultySummary {
+    fn new() -> Self {
+        Self { d_sorted: Vec::new(), w_sorted: Vec::new(), power_sum: 0.0, weight_sum: 0.0 }
+    }
+
+    /// Merges the pairs of one window
+    fn add(&mut self, d_all: &[f64], weights: &[f64], pool: &mut BufferPool) {
+        for (&d, &w) in d_all.iter().zip(weights) {
+            self.power_sum += d.powf(5.0) * w;
+            self.weight_sum += w;
+        }
+
+        let (run_d, run_w) = sort_by_difficulty_with_pool(d_all, weights, pool);
+        let len = self.d_sorted.len() + run_d.len();
+        let mut merged_d = pool.take_empty(len);
+        let mut merged_w = pool.take_empty(len);
+        let (mut i, mut j) = (0, 0);
+        while merged_d.len() < len {
+            if j == run_d.len() || (i < self.d_sorted.len() && self.d_sorted[i] <= run_d[j]) {
+                merged_d.push(self.d_sorted[i]);
+                merged_w.push(self.w_sorted[i]);
+                i += 1;
+            } else {
+                merged_d.push(run_d[j]);
+                merged_w.push(run_w[j]);
+                j += 1;
+            }
+        }
+        let old_d = std::mem::replace(&mut self.d_sorted, merged_d);
+        let old_w = std::mem::replace(&mut self.w_sorted, merged_w);
+        pool.give_all([old_d, old_w, run_d, run_w]);
+
+        if self.d_sorted.len() > SUMMARY_CAPACITY {
+            self.compact();
+        }
+    }
+
+    /// Halves the pairs, combining each even pair with the next one
+    fn compact(&mut self) {
+        let len = self.d_sorted.len();
+        for k in 0..len / 2 {
+            self.d_sorted[k] = self.d_sorted[2 * k + 1];
+            self.w_sorted[k] = self.w_sorted[2 * k] + self.w_sorted[2 * k + 1];
+        }
+        if len % 2 == 1 {
+            self.d_sorted[len / 2] = self.d_sorted[len - 1];
+            self.w_sorted[len / 2] = self.w_sorted[len - 1];
+        }
+        self.d_sorted.truncate(len.div_ceil(2));
+        self.w_sorted.truncate(len.div_ceil(2));
+    }
+
+    /// Tuple (percentile_93, percentile_83, weighted_mean), as `process::aggregate_difficulties`
+    fn finish(self, pool: &mut BufferPool) -> (f64, f64, f64) {
+        let (percentile_93, percentile_83) = weighted_percentiles(&self.d_sorted, &self.w_sorted, pool);
+        let weighted_mean = (self.power_sum / self.weight_sum).powf(1.0 / 5.0);
+        pool.give_all([self.d_sorted, self.w_sorted]);
+        (percentile_93, percentile_83, weighted_mean)
+    }
+}
+
+/// Long notes seen by the previous windows, so each window only scans the notes it adds
+///
+/// Windows move forward, and so does the start of their note range.
+#[derive(Default)]
+struct WindowCursor {
+    /// Notes before this index have been scanned
+    scanned: usize,
+    /// Long notes before `scanned` still held at the start of the last range
+    held: Vec<usize>,
+}
+
+/// Reduced map whose corners and bar values match the full map between `lo` and `hi`
+///
+/// Holds, besides the notes hit in [lo, hi]:
+/// * every note hit between the last note before `lo` and the first after `hi`
+///   (±1000 ms), so the note pairs and local usage around the range are the same
+/// * the long notes overlapping that range (LN bodies, key usage)
+/// * the last note before and the first after it in each column (jack and
+///   cross-column deltas span breaks)
+/// * the last tail before and the first tail after it, with the next note in
+///   their column (release pairs)
+///
+/// `total_duration` and the other map-wide values stay those of the full map. Calls
+/// sharing `cursor` must come with nondecreasing `lo`.
+fn window_map_data(map_data: &MapData, cursor: &mut WindowCursor, lo: f64, hi: f64) -> MapData {
+    let notes = &map_data.notes;
+    let first_in = notes.partition_point(|note| note.hit_time < lo);
+    let end_in = notes.partition_point(|note| note.hit_time <= hi);
+    let range_lo = if first_in > 0 { notes[first_in - 1].hit_time - NOTE_PADDING_MS } else { lo };
+    let range_hi = if end_in < notes.len() { notes[end_in].hit_time + NOTE_PADDING_MS } else { hi };
+
+    let start = notes.partition_point(|note| note.hit_time < range_lo);
+    let end = notes.partition_point(|note| note.hit_time <= range_hi);
+    let mut indices: Vec<usize> = (start..end).collect();
+
+    // Long notes started before the range and still held in it
+    cursor.held.extend((cursor.scanned..start).filter(|&i| notes[i].is_long_note()));
+    cursor.scanned = cursor.scanned.max(start);
+    cursor.held.retain(|&i| notes[i].tail_time >= range_lo);
+    indices.extend_from_slice(&cursor.held);
+
+    // Column neighbours of the range
+    let mut neighbours: Vec<Note> = Vec::new();
+    for column_notes in &map_data.notes_by_column {
+        let before = column_notes.partition_point(|note| note.hit_time < range_lo);
+        if before > 0 {
+            neighbours.push(column_notes[before - 1]);
+        }
+        let after = column_notes.partition_point(|note| note.hit_time <= range_hi);
+        if let Some(&note) = column_notes.get(after) {
+            neighbours.push(note);
+        }
+    }
+
+    // Tails around the range and the note following each of them in its column
+    let tails = &map_data.tail_sequence;
+    let tail_before = tails.partition_point(|note| note.tail_time < range_lo);
+    let tail_after = tails.partition_point(|note| note.tail_time <= range_hi);
+    let tail_neighbours = tail_before.checked_sub(1).map(|i| tails[i]).into_iter().chain(tails.get(tail_after).copied());
+    for tail in tail_neighbours {
+        neighbours.push(tail);
+        if let Some(column_notes) = map_data.notes_by_column.get(tail.column) {
+            let next = column_notes.partition_point(|note| note.hit_time <= tail.hit_time);
+            if let Some(&note) = column_notes.get(next) {
+                neighbours.push(note);
+            }
+        }
+    }
+
+    // Map the neighbours back to their position in the sorted note list
+    indices.extend(neighbours.iter().map(|neighbour| {
+        notes.partition_point(|note| (note.hit_time, note.column) < (neighbour.hit_time, neighbour.column))
+    }));
+    indices.sort_unstable();
+    indices.dedup();
+
+    let mut window = MapData {
+        hit_leniency: map_data.hit_leniency,
+        column_count: map_data.column_count,
+        total_duration: map_data.total_duration,
+        notes: indices.iter().map(|&i| notes[i]).collect(),
+        notes_by_column: Vec::new(),
+        long_notes: Vec::new(),
+        tail_sequence: Vec::new(),
+        long_notes_by_column: Vec::new(),
+        overall_difficulty: map_data.overall_difficulty,
+        warnings: Vec::new(),
+        time_precision: map_data.time_precision,
+        clock_rate: map_data.clock_rate,
+        source_sections: Vec::new(),
+    };
+    rebuild_groupings(&mut window);
+    window
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithm::process::preprocess::preprocess_file;
+    use crate::algorithm::process::process::{aggregate_difficulties, calculate};
+    use crate::test_support::TestRng;
+
+    #[test]
+    fn chunked_ratings_match_calculate() {
+        for file in ["assets/benchmark.osu", "assets/test.osu"] {
+            for mod_name in ["None", "DT"] {
+                let map_data = preprocess_file(file, mod_name).unwrap();
+                let expected = calculate(&map_data).unwrap().rating;
+                for window_ms in [5_000, DEFAULT_CHUNK_MS] {
+                    let rating = calculate_chunked(&map_data, window_ms).unwrap().rating;
+                    assert!((rating - expected).abs() < 1e-9, "{} {} {}: {} != {}", file, mod_name, window_ms, rating, expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn summary_stays_bounded_and_close() {
+        let mut rng = TestRng::new(41);
+        let mut pool = BufferPool::new();
+        let mut summary = DifficultySummary::new();
+        let (mut d_all, mut weights) = (Vec::new(), Vec::new());
+        for _ in 0..12 {
+            let d = rng.values(SUMMARY_CAPACITY / 4, 10.0);
+            let w = rng.values(d.len(), 5.0);
+            summary.add(&d, &w, &mut pool);
+            assert!(summary.d_sorted.len() <= SUMMARY_CAPACITY);
+            d_all.extend(d);
+            weights.extend(w);
+        }
+        let (p93, p83, mean) = summary.finish(&mut pool);
+        let (exact_93, exact_83, exact_mean) = aggregate_difficulties(&d_all, &weights, &mut pool);
+        assert!(p93 >= exact_93 && p93 - exact_93 < 1e-3, "{} vs {}", p93, exact_93);
+        assert!(p83 >= exact_83 && p83 - exact_83 < 1e-3, "{} vs {}", p83, exact_83);
+        assert!((mean - exact_mean).abs() < 1e-9, "{} vs {}", mean, exact_mean);
+    }
+}
@@ -1,3 +1,9 @@
 pub mod preprocess;
 pub mod process;
-pub mod normalize;
\ No newline at end of file
+pub mod normalize;
+pub mod calculator;
+pub mod batch;
+pub mod async_api;
+pub mod chunked;
+pub mod approximate;
+pub mod osz;
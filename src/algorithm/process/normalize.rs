@@ -2,27 +2,37 @@ use crate::types::MapData;
 
 /// Applique les mods (DT/HT) sur les notes
 pub fn apply_mods(map_data: &mut MapData, mod_name: &str) {
-    if mod_name == "DT" {
-        for note in &mut map_data.notes {
-            note.hit_time = ((note.hit_time as f64) * 2.0 / 3.0).floor() as i64;
-            if note.is_long_note() {
-                note.tail_time = ((note.tail_time as f64) * 2.0 / 3.0).floor() as i64;
-            }
-        }
-    } else if mod_name == "HT" {
-        for note in &mut map_data.notes {
-            note.hit_time = ((note.hit_time as f64) * 4.0 / 3.0).floor() as i64;
-            if note.is_long_note() {
-                note.tail_time = ((note.tail_time as f64) * 4.0 / 3.0).floor() as i64;
-            }
+    apply_rate(map_data, clock_rate(mod_name));
+}
+
+/// Rescales note times for a playback rate (floored to the millisecond in integer precision)
+pub fn apply_rate(map_data: &mut MapData, rate: f64) {
+    if rate == 1.0 {
+        return;
+    }
+    let precision = map_data.time_precision;
+    for note in &mut map_data.notes {
+        note.hit_time = precision.rated(note.hit_time, rate);
+        if note.is_long_note() {
+            note.tail_time = precision.rated(note.tail_time, rate);
         }
     }
+    map_data.clock_rate *= rate;
+}
+
+/// Returns the clock rate of a mod (DT = 1.5, HT = 0.75, anything else = 1.0)
+pub fn clock_rate(mod_name: &str) -> f64 {
+    match mod_name {
+        "DT" => 1.5,
+        "HT" => 0.75,
+        _ => 1.0,
+    }
 }
 
 /// Sorts and rebuilds per-column groupings and LN sequences
 pub fn rebuild_groupings(map_data: &mut MapData) {
     // Trier les notes par temps de hit puis par colonne
-    map_data.notes.sort_by(|a, b| match a.hit_time.cmp(&b.hit_time) {
+    map_data.notes.sort_by(|a, b| match a.hit_time.total_cmp(&b.hit_time) {
         std::cmp::Ordering::Equal => a.column.cmp(&b.column),
         other => other,
     });
@@ -43,7 +53,7 @@ pub fn rebuild_groupings(map_data: &mut MapData) {
 
     // Recompute tail sequence
     map_data.tail_sequence = map_data.long_notes.clone();
-    map_data.tail_sequence.sort_by(|a, b| a.tail_time.cmp(&b.tail_time));
+    map_data.tail_sequence.sort_by(|a, b| a.tail_time.total_cmp(&b.tail_time));
 
     // Rebuild long notes per column
     map_data.long_notes_by_column = vec![Vec::new(); map_data.column_count];
@@ -65,8 +75,50 @@ pub fn recompute_hit_leniency(map_data: &mut MapData) {
 pub fn recompute_total_duration(map_data: &mut MapData) {
     map_data.total_duration = map_data.notes.iter()
         .map(|note| note.hit_time.max(note.tail_time))
-        .max()
-        .unwrap_or(0) + 1;
+        .reduce(f64::max)
+        .unwrap_or(0.0) + 1.0;
 }
 
 
+
+#[cfg(test)]
+mod tests {
+    use crate::algorithm::process::preprocess::{preprocess_file_with_options, preprocess_with_options};
+    use crate::algorithm::process::process::calculate;
+    use crate::types::{ParseOptions, TimePrecision};
+
+    const FRACTIONAL_MAP: &str = "osu file format v14\n\n[General]\nMode: 3\n\n[Difficulty]\nCircleSize:4\nOverallDifficulty:8\n\n[HitObjects]\n64,192,1000.5,1,0,0:0:0:0:\n192,192,1250.25,128,0,1700.75:0:0:0:0:\n320,192,1500,1,0,0:0:0:0:\n";
+
+    fn times(precision: TimePrecision, mod_name: &str) -> Vec<(f64, f64)> {
+        let options = ParseOptions::new().with_time_precision(precision);
+        let map_data = preprocess_with_options(FRACTIONAL_MAP, mod_name, &options).unwrap();
+        assert_eq!(map_data.time_precision, precision);
+        map_data.notes.iter().map(|note| (note.hit_time, note.tail_time)).collect()
+    }
+
+    #[test]
+    fn fractional_precision_keeps_times_through_rate_changes() {
+        assert_eq!(times(TimePrecision::Fractional, "None"), [(1000.5, -1.0), (1250.25, 1700.75), (1500.0, -1.0)]);
+        assert_eq!(times(TimePrecision::Fractional, "DT"), [(1000.5 / 1.5, -1.0), (1250.25 / 1.5, 1700.75 / 1.5), (1000.0, -1.0)]);
+    }
+
+    #[test]
+    fn integer_precision_truncates_and_floors_times() {
+        assert_eq!(times(TimePrecision::Integer, "None"), [(1000.0, -1.0), (1250.0, 1700.0), (1500.0, -1.0)]);
+        assert_eq!(times(TimePrecision::Integer, "DT"), [(666.0, -1.0), (833.0, 1133.0), (1000.0, -1.0)]);
+        assert_eq!(times(TimePrecision::Integer, "HT"), [(1333.0, -1.0), (1666.0, 2266.0), (2000.0, -1.0)]);
+    }
+
+    #[test]
+    fn precisions_only_differ_once_times_become_fractional() {
+        let rating = |precision: TimePrecision, mod_name: &str| {
+            let options = ParseOptions::new().with_time_precision(precision);
+            calculate(&preprocess_file_with_options("assets/test.osu", mod_name, &options).unwrap()).unwrap().rating
+        };
+        // assets/test.osu has whole-millisecond times
+        assert_eq!(rating(TimePrecision::Fractional, "None").to_bits(), rating(TimePrecision::Integer, "None").to_bits());
+        let (fractional, integer) = (rating(TimePrecision::Fractional, "DT"), rating(TimePrecision::Integer, "DT"));
+        assert_ne!(fractional.to_bits(), integer.to_bits());
+        assert!((fractional - integer).abs() < 0.05, "{} vs {}", fractional, integer);
+    }
+}
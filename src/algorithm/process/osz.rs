@@ -0,0 +1,156 @@
+use std::fs::File;
+use std::io::{BufReader, Read, Seek};
+use std::path::Path;
+use zip::result::ZipError;
+use zip::ZipArchive;
+use super::batch::rate_isolated;
+use super::calculator::Calculator;
+use super::preprocess::finish;
+use crate::file_parser::Parser;
+use crate::types::{BeatmapMetadata, MapsetDifficulty, MapsetRating, ParseError, ParseOptions, StarRatingError, StarRatingResult};
+
+/// Rates the osu!mania difficulties of a .osz mapset archive
+///
+/// # Arguments
+/// * `path` - Path to the .osz file
+/// * `mod_name` - Mod applied to every difficulty
+///
+/// # Returns
+/// Mapset info and one rating (or error) per mania difficulty
+pub fn rate_osz<P: AsRef<Path>>(path: P, mod_name: &str) -> StarRatingResult<MapsetRating> {
+    let path = path.as_ref();
+    let file = File::open(path)
+        .map_err(|e| StarRatingError::io(path.display(), e))?;
+    rate_osz_reader(BufReader::new(file), mod_name, &ParseOptions::default())
+}
+
+/// Rates the osu!mania difficulties of a .osz archive read from any seekable source
+///
+/// Only the .osu entries are decompressed, straight into the parser; audio, images
+/// and other entries are skipped and nothing is written to disk. Difficulties of
+/// other game modes are listed in `skipped`, and only read up to their [General]
+/// Mode. A difficulty that fails to parse (even before its Mode is known), fails to
+/// rate or panics is reported with its error and does not stop the others.
+///
+/// # Arguments
+/// * `reader` - Archive content (file, in-memory cursor)
+/// * `mod_name` - Mod applied to every difficulty
+/// * `options` - Parsing options used for every difficulty
+///
+/// # Returns
+/// Mapset info and one rating (or error) per mania difficulty
+pub fn rate_osz_reader<R: Read + Seek>(
+    reader: R,
+    mod_name: &str,
+    options: &ParseOptions,
+) -> StarRatingResult<MapsetRating> {
+    let mut archive = ZipArchive::new(reader).map_err(archive_error)?;
+    let mut calculator = Calculator::new();
+    // only the ratings are kept, so the sections are not either
+    let options = options.clone().with_mania_only(true).with_keep_sections(false);
+    let mut mapset = MapsetRating::default();
+
+    for index in 0..archive.len() {
+        let is_osu = archive
+            .name_for_index(index)
+            .is_some_and(|name| name.to_ascii_lowercase().ends_with(".osu"));
+        if !is_osu {
+            continue;
+        }
+        let entry = archive.by_index(index).map_err(archive_error)?;
+        if entry.is_dir() {
+            continue;
+        }
+        let file_name = entry.name().to_string();
+
+        let mut parser = Parser::new(&file_name).with_options(options.clone());
+        let mut metadata = BeatmapMetadata::default();
+        // the mode is only known once the parse succeeded; an entry failing before
+        // (corrupt data, malformed [General]) is reported, not skipped
+        let mut mode_known = false;
+        let outcome = rate_isolated(
+            || {
+                let processed = parser.process_reader(BufReader::new(entry));
+                metadata = parser.metadata().clone();
+                processed?;
+                mode_known = true;
+                if !metadata.is_mania() {
+                    // skipped below; only the metadata was read
+                    return Err(ParseError::InvalidValue("Mode".to_string(), metadata.mode.to_string()).into());
+                }
+                finish(parser, mod_name)
+            },
+            &mut calculator,
+        );
+        if mode_known && !metadata.is_mania() {
+            mapset.skipped.push(file_name);
+            continue;
+        }
+
+        if mapset.difficulties.is_empty() {
+            mapset.title = metadata.title.clone();
+            mapset.artist = metadata.artist.clone();
+            mapset.creator = metadata.creator.clone();
+        }
+        mapset.difficulties.push(MapsetDifficulty { file_name, metadata, outcome });
+    }
+
+    Ok(mapset)
+}
+
+/// Maps archive errors: I/O failures stay I/O errors, the rest is a format error
+fn archive_error(error: ZipError) -> StarRatingError {
+    match error {
+        ZipError::Io(e) => StarRatingError::FileError(e),
+        other => StarRatingError::InvalidFormat(format!(".osz archive: {}", other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+    use zip::write::SimpleFileOptions;
+    use zip::ZipWriter;
+
+    fn archive(entries: &[(&str, &str)]) -> Cursor<Vec<u8>> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, content) in entries {
+            writer.start_file(*name, SimpleFileOptions::default()).unwrap();
+            writer.write_all(content.as_bytes()).unwrap();
+        }
+        let mut cursor = writer.finish().unwrap();
+        cursor.set_position(0);
+        cursor
+    }
+
+    #[test]
+    fn other_modes_are_skipped_after_general() {
+        let mania = std::fs::read_to_string("assets/test.osu").unwrap();
+        // the rest of a non-mania difficulty is not read, malformed or not
+        let standard = "osu file format v14\n\n[General]\nMode: 0\n\n[Difficulty]\nCircleSize:100\n\n[HitObjects]\nnot,a,hit,object\n";
+        let reader = archive(&[("mania.osu", &mania), ("standard.osu", standard), ("audio.mp3", "")]);
+        let mapset = rate_osz_reader(reader, "None", &ParseOptions::strict()).unwrap();
+        assert_eq!(mapset.skipped, ["standard.osu"]);
+        let [difficulty] = &mapset.difficulties[..] else { panic!("expected one difficulty") };
+        assert_eq!(difficulty.file_name, "mania.osu");
+        assert!(difficulty.outcome.is_ok());
+    }
+
+    #[test]
+    fn corrupt_entries_are_failed_difficulties() {
+        let mania = std::fs::read_to_string("assets/test.osu").unwrap();
+        let mut bytes = archive(&[("corrupt.osu", &mania), ("mania.osu", &mania)]).into_inner();
+        // first deflate block of the first entry, right after its local header:
+        // BTYPE 11 is reserved, so inflating fails on the first read
+        let data_start = 30 + u16::from_le_bytes([bytes[26], bytes[27]]) as usize + u16::from_le_bytes([bytes[28], bytes[29]]) as usize;
+        bytes[data_start] = 0x07;
+
+        let mapset = rate_osz_reader(Cursor::new(bytes), "None", &ParseOptions::default()).unwrap();
+        assert!(mapset.skipped.is_empty());
+        let [corrupt, good] = &mapset.difficulties[..] else { panic!("expected two difficulties") };
+        assert_eq!(corrupt.file_name, "corrupt.osu");
+        assert!(matches!(corrupt.outcome, Err(StarRatingError::FileError(_))), "{:?}", corrupt.outcome.as_ref().err());
+        assert!(good.outcome.is_ok());
+    }
+}
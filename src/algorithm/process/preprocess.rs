@@ -1,9 +1,10 @@
-
 // Use the actual parser from file_parser module
 use crate::file_parser::Parser;
-use crate::types::{MapData, StarRatingResult};
-use super::normalize::{apply_mods, rebuild_groupings, recompute_hit_leniency, recompute_total_duration};
-use std::fs;
+use crate::file_parser::stepmania::{parse_stepmania, parse_stepmania_file};
+use crate::types::{MapData, ParseOptions, StarRatingError, StarRatingResult, StepmaniaSimfile};
+use super::normalize::{apply_mods, clock_rate, rebuild_groupings, recompute_hit_leniency, recompute_total_duration};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
 
 
 /// Parse a .osu file and return data as MapData
@@ -11,9 +12,17 @@ pub fn preprocess_file(
     file_path: &str,
     mod_name: &str,
 ) -> StarRatingResult<MapData> {
-    let content = fs::read_to_string(file_path)
-        .map_err(|e| crate::types::ParseError::FileNotFound(format!("{}: {}", file_path, e)))?;
-    preprocess(&content, mod_name)
+    preprocess_file_with_options(file_path, mod_name, &ParseOptions::default())
+}
+
+/// Parse a .osu file with the given options and return data as MapData
+pub fn preprocess_file_with_options(
+    file_path: &str,
+    mod_name: &str,
+    options: &ParseOptions,
+) -> StarRatingResult<MapData> {
+    let file = File::open(file_path).map_err(|e| StarRatingError::io(file_path, e))?;
+    preprocess_reader_with_options(BufReader::new(file), mod_name, options)
 }
 
 /// Parse in-memory .osu content and return data as MapData
@@ -21,9 +30,79 @@ pub fn preprocess(
     osu_content: &str,
     mod_name: &str,
 ) -> StarRatingResult<MapData> {
-    let mut parser = Parser::new("");
+    preprocess_with_options(osu_content, mod_name, &ParseOptions::default())
+}
+
+/// Parse in-memory .osu content with the given options and return data as MapData
+pub fn preprocess_with_options(
+    osu_content: &str,
+    mod_name: &str,
+    options: &ParseOptions,
+) -> StarRatingResult<MapData> {
+    let mut parser = Parser::new("").with_options(options.clone());
     parser.process_content(osu_content)?;
-    let mut map_data = parser.get_map_data()?;
+    finish(parser, mod_name)
+}
+
+/// Parse .osu content streamed from a reader (stdin, a byte buffer, ...) and return data as MapData
+pub fn preprocess_reader<R: BufRead>(
+    reader: R,
+    mod_name: &str,
+) -> StarRatingResult<MapData> {
+    preprocess_reader_with_options(reader, mod_name, &ParseOptions::default())
+}
+
+/// Parse .osu content streamed from a reader with the given options and return data as MapData
+pub fn preprocess_reader_with_options<R: BufRead>(
+    reader: R,
+    mod_name: &str,
+    options: &ParseOptions,
+) -> StarRatingResult<MapData> {
+    let mut parser = Parser::new("").with_options(options.clone());
+    parser.process_reader(reader)?;
+    finish(parser, mod_name)
+}
+
+/// Parse a .sm/.ssc file and apply the mod to each of its charts
+pub fn preprocess_stepmania_file(
+    file_path: &str,
+    mod_name: &str,
+) -> StarRatingResult<StepmaniaSimfile> {
+    let mut simfile = parse_stepmania_file(file_path)?;
+    apply_stepmania_mods(&mut simfile, mod_name);
+    Ok(simfile)
+}
+
+/// Parse in-memory .sm/.ssc content and apply the mod to each of its charts
+pub fn preprocess_stepmania(
+    content: &str,
+    mod_name: &str,
+) -> StarRatingResult<StepmaniaSimfile> {
+    let mut simfile = parse_stepmania(content)?;
+    apply_stepmania_mods(&mut simfile, mod_name);
+    Ok(simfile)
+}
+
+/// Applies the mod to the notes, mines and fakes of every chart
+fn apply_stepmania_mods(simfile: &mut StepmaniaSimfile, mod_name: &str) {
+    let rate = clock_rate(mod_name);
+    for chart in &mut simfile.charts {
+        let precision = chart.map_data.time_precision;
+        apply_mods(&mut chart.map_data, mod_name);
+        rebuild_groupings(&mut chart.map_data);
+        recompute_hit_leniency(&mut chart.map_data);
+        recompute_total_duration(&mut chart.map_data);
+        if rate != 1.0 {
+            for note in chart.mines.iter_mut().chain(chart.fakes.iter_mut()) {
+                note.hit_time = precision.rated(note.hit_time, rate);
+            }
+        }
+    }
+}
+
+/// Builds the map from the parsed buffers and applies the mod
+pub(crate) fn finish(parser: Parser, mod_name: &str) -> StarRatingResult<MapData> {
+    let mut map_data = parser.into_map_data()?;
     apply_mods(&mut map_data, mod_name);
     rebuild_groupings(&mut map_data);
     recompute_hit_leniency(&mut map_data);
@@ -33,3 +112,60 @@ pub fn preprocess(
 }
 
 // supprimé: alias inutile
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_same_map(name: &str, actual: &MapData, expected: &MapData) {
+        assert_eq!(actual.notes, expected.notes, "{}: notes", name);
+        assert_eq!(actual.column_count, expected.column_count, "{}: column count", name);
+        assert_eq!(actual.overall_difficulty.to_bits(), expected.overall_difficulty.to_bits(), "{}: OD", name);
+        assert_eq!(actual.total_duration.to_bits(), expected.total_duration.to_bits(), "{}: duration", name);
+        assert_eq!(actual.source_sections, expected.source_sections, "{}: sections", name);
+    }
+
+    #[test]
+    fn readers_content_and_files_parse_alike() {
+        for file in ["assets/test.osu", "assets/benchmark.osu"] {
+            let bytes = std::fs::read(file).unwrap();
+            let content = String::from_utf8(bytes.clone()).unwrap();
+            for mod_name in ["None", "DT"] {
+                let expected = preprocess_file(file, mod_name).unwrap();
+                assert_same_map(file, &preprocess_reader(&bytes[..], mod_name).unwrap(), &expected);
+                assert_same_map(file, &preprocess(&content, mod_name).unwrap(), &expected);
+            }
+        }
+    }
+
+    #[test]
+    fn readers_accept_bom_crlf_and_utf16() {
+        let content = std::fs::read_to_string("assets/test.osu").unwrap();
+        let expected = preprocess(&content, "None").unwrap();
+
+        let crlf = format!("\u{FEFF}{}", content.replace('\n', "\r\n"));
+        let utf16_le: Vec<u8> = [0xFF, 0xFE].into_iter().chain(content.encode_utf16().flat_map(u16::to_le_bytes)).collect();
+        let utf16_be: Vec<u8> = [0xFE, 0xFF].into_iter().chain(content.encode_utf16().flat_map(u16::to_be_bytes)).collect();
+        assert_same_map("BOM + CRLF", &preprocess_reader(crlf.as_bytes(), "None").unwrap(), &expected);
+        assert_same_map("UTF-16 LE", &preprocess_reader(&utf16_le[..], "None").unwrap(), &expected);
+        assert_same_map("UTF-16 BE", &preprocess_reader(&utf16_be[..], "None").unwrap(), &expected);
+    }
+
+    #[test]
+    fn small_reader_buffers_parse_alike() {
+        let bytes = std::fs::read("assets/test.osu").unwrap();
+        let expected = preprocess_file("assets/test.osu", "None").unwrap();
+        for capacity in [1, 7, 64] {
+            let reader = BufReader::with_capacity(capacity, &bytes[..]);
+            assert_same_map(&format!("capacity {}", capacity), &preprocess_reader(reader, "None").unwrap(), &expected);
+        }
+    }
+
+    #[test]
+    fn truncated_streams_are_errors() {
+        let bytes = std::fs::read("assets/test.osu").unwrap();
+        let content = String::from_utf8_lossy(&bytes);
+        let cut = content.find("[HitObjects]").unwrap();
+        assert!(preprocess_reader(&bytes[..cut], "None").is_err());
+    }
+}
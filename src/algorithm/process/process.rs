@@ -1,18 +1,16 @@
 use crate::algorithm::smoothing::rescale_high;
-use crate::algorithm::interpolation::{interp_values, step_interp};
-use crate::types::{MapData, StarRating, StarRatingComponents, StarRatingResult};
+use crate::algorithm::interpolation::step_interp_with_pool;
+use crate::algorithm::pool::BufferPool;
+use crate::types::{ActiveColumns, CalculationError, ColumnMatrix, MapData, StarRating, StarRatingResult, MAX_KEY_COUNT};
 
 // Import des modules décomposés
-use crate::algorithm::calculations::corners::get_corners;
-use crate::algorithm::calculations::key_usage::{get_key_usage, get_key_usage_400};
-use crate::algorithm::calculations::anchor::compute_anchor;
-use crate::algorithm::calculations::ln::ln_bodies_count_sparse_representation;
-use crate::algorithm::bars::jbar::compute_jbar;
-use crate::algorithm::bars::xbar::compute_xbar;
-use crate::algorithm::bars::pbar::compute_pbar;
-use crate::algorithm::bars::abar::compute_abar;
-use crate::algorithm::bars::rbar::compute_rbar;
-use crate::algorithm::calculations::ck::compute_c_and_ks;
+use crate::algorithm::calculations::corners::get_corners_with_pool;
+use crate::algorithm::calculations::key_usage::{get_key_usage, get_key_usage_400_with_pool};
+use crate::algorithm::calculations::anchor::compute_anchor_with_pool;
+use crate::algorithm::bars::skill::{
+    CrossColumnSkill, JackSkill, PressingSkill, ReleaseSkill, Skill, SkillContext, SkillTarget, UnevennessSkill,
+};
+use crate::algorithm::calculations::ck::compute_c_and_ks_with_pool;
 
 
 /// Main star rating calculation function
@@ -23,64 +21,132 @@ use crate::algorithm::calculations::ck::compute_c_and_ks;
 /// # Returns
 /// Detailed star rating calculation result
 pub fn calculate(map_data: &MapData) -> StarRatingResult<StarRating> {
-    let rating = calculate_internal(map_data);
-
-    Ok(StarRating {
-        rating,
-        components: StarRatingComponents::new(
-            Vec::new(), // TODO: Extract components from algorithm
-            Vec::new(),
-            Vec::new(),
-            Vec::new(),
-            0.0,
-            0.0,
-            0.0,
-        ),
-    })
+    calculate_with_skills(map_data, &[])
+}
+
+/// Star rating calculation with extra skills folded into the S/T/D combination
+/// 
+/// # Arguments
+/// * `map_data` - Parsed map data
+/// * `extra_skills` - Skills evaluated alongside the five built-in bars
+/// 
+/// # Returns
+/// Detailed star rating calculation result
+pub fn calculate_with_skills(map_data: &MapData, extra_skills: &[Box<dyn Skill>]) -> StarRatingResult<StarRating> {
+    let rating = calculate_internal(map_data, extra_skills, &mut BufferPool::new())?;
+
+    Ok(StarRating::rating_only(rating))
 }
 
 /// Internal calculation function
-fn calculate_internal(map_data: &MapData) -> f64 {
-    let (all_corners, base_corners, a_corners, key_usage, active_columns, key_usage_400, anchor) = phase1(map_data);
-    let (jbar, xbar, pbar, abar, rbar, c_arr, ks_arr) = phase2(map_data, &active_columns, &a_corners, &base_corners, &all_corners, &anchor);
-    let (_s_all, _t_all, d_all) = phase3(&jbar, &xbar, &pbar, &abar, &rbar, &c_arr, &ks_arr);
-    let (percentile_93, percentile_83, weighted_mean) = phase4(&d_all, &c_arr, &all_corners);
-    phase5(percentile_93, percentile_83, weighted_mean, &map_data.notes, &map_data.long_notes)
+///
+/// Every corner-sized buffer is taken from `pool` and given back before returning,
+/// so a pool reused across maps stops allocating once it is warm.
+pub(crate) fn calculate_internal(map_data: &MapData, extra_skills: &[Box<dyn Skill>], pool: &mut BufferPool) -> StarRatingResult<f64> {
+    let (all_corners, d_all, weights) = corner_difficulties(map_data, extra_skills, pool)?;
+    let (percentile_93, percentile_83, weighted_mean) = aggregate_difficulties(&d_all, &weights, pool);
+    let rating = phase5(percentile_93, percentile_83, weighted_mean, &map_data.notes, &map_data.long_notes);
+
+    pool.give_all([all_corners, d_all, weights]);
+    Ok(rating)
+}
+
+/// Phases 1 to 3 plus the effective weights: difficulty and weight at every corner
+///
+/// # Returns
+/// Tuple (all_corners, d_all, effective_weights), taken from `pool`
+pub(crate) fn corner_difficulties(map_data: &MapData, extra_skills: &[Box<dyn Skill>], pool: &mut BufferPool) -> StarRatingResult<(Vec<f64>, Vec<f64>, Vec<f64>)> {
+    let (all_corners, base_corners, a_corners) = get_corners_with_pool(map_data.total_duration, &map_data.notes, pool);
+    corner_difficulties_on(map_data, all_corners, base_corners, a_corners, false, extra_skills, pool)
+}
+
+/// Same as `corner_difficulties`, on given corner sets instead of those of `get_corners`
+/// (`on_grid` when they are the fixed grid of the approximate mode)
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn corner_difficulties_on(
+    map_data: &MapData,
+    all_corners: Vec<f64>,
+    base_corners: Vec<f64>,
+    a_corners: Vec<f64>,
+    on_grid: bool,
+    extra_skills: &[Box<dyn Skill>],
+    pool: &mut BufferPool,
+) -> StarRatingResult<(Vec<f64>, Vec<f64>, Vec<f64>)> {
+    let (key_usage, active_columns, key_usage_400, anchor) = phase1_on_corners(map_data, &base_corners, pool)?;
+    let (jbar, xbar, pbar, abar, rbar, c_arr, ks_arr, extras) = compute_all_bars(map_data, &key_usage, &active_columns, &a_corners, &base_corners, &all_corners, &anchor, on_grid, extra_skills, pool)?;
+    let (s_all, t_all, d_all) = compute_final_values(&jbar, &xbar, &pbar, &abar, &rbar, &c_arr, &ks_arr, &extras, pool);
+    let weights = compute_effective_weights_with_pool(&c_arr, &all_corners, pool);
+
+    pool.give_all([base_corners, a_corners, key_usage_400.into_vec(), anchor]);
+    pool.give_all([jbar, xbar, pbar, abar, rbar, c_arr, ks_arr, s_all, t_all]);
+    pool.give_all(extras.into_iter().map(|(_, _, values)| values));
+    Ok((all_corners, d_all, weights))
 }
 
 /// Phase 1: Data preparation
+///
+/// Fails with `EmptyData` for a map without notes and with `IndexOutOfBounds` when
+/// the map has more than `MAX_KEY_COUNT` columns, before any bar is computed.
+#[allow(clippy::type_complexity)]
 pub fn phase1(
     map_data: &MapData,
-) -> (
+) -> StarRatingResult<(
     Vec<f64>, // all_corners
     Vec<f64>, // base_corners
     Vec<f64>, // a_corners
-    std::collections::HashMap<usize, Vec<bool>>, // key_usage
-    Vec<Vec<usize>>, // active_columns
-    std::collections::HashMap<usize, Vec<f64>>, // key_usage_400
+    ColumnMatrix<bool>, // key_usage
+    ActiveColumns, // active_columns
+    ColumnMatrix<f64>, // key_usage_400
     Vec<f64>, // anchor
-) {
-    let (all_corners, base_corners, a_corners) = get_corners(map_data.total_duration, &map_data.notes);
-    let key_usage = get_key_usage(map_data.column_count, map_data.total_duration, &map_data.notes, &base_corners);
-    let active_columns = compute_active_columns(&key_usage, map_data.column_count, base_corners.len());
-    let key_usage_400 = get_key_usage_400(map_data.column_count, map_data.total_duration, &map_data.notes, &base_corners);
-    let anchor = compute_anchor(map_data.column_count, &key_usage_400, &base_corners);
-    (all_corners, base_corners, a_corners, key_usage, active_columns, key_usage_400, anchor)
+)> {
+    phase1_with_pool(map_data, &mut BufferPool::new())
+}
+
+/// Phase 1 with the corner-sized buffers taken from `pool`
+#[allow(clippy::type_complexity)]
+fn phase1_with_pool(
+    map_data: &MapData,
+    pool: &mut BufferPool,
+) -> StarRatingResult<(Vec<f64>, Vec<f64>, Vec<f64>, ColumnMatrix<bool>, ActiveColumns, ColumnMatrix<f64>, Vec<f64>)> {
+    let (all_corners, base_corners, a_corners) = get_corners_with_pool(map_data.total_duration, &map_data.notes, pool);
+    let (key_usage, active_columns, key_usage_400, anchor) = phase1_on_corners(map_data, &base_corners, pool)?;
+    Ok((all_corners, base_corners, a_corners, key_usage, active_columns, key_usage_400, anchor))
+}
+
+/// Key usage, active columns, 400ms key usage and anchor on given base corners
+fn phase1_on_corners(
+    map_data: &MapData,
+    base_corners: &[f64],
+    pool: &mut BufferPool,
+) -> StarRatingResult<(ColumnMatrix<bool>, ActiveColumns, ColumnMatrix<f64>, Vec<f64>)> {
+    if map_data.notes.is_empty() {
+        return Err(CalculationError::EmptyData("map notes".to_string()).into());
+    }
+    if map_data.column_count > MAX_KEY_COUNT {
+        return Err(CalculationError::IndexOutOfBounds("key count".to_string(), map_data.column_count, MAX_KEY_COUNT).into());
+    }
+    let key_usage = get_key_usage(map_data.column_count, map_data.total_duration, &map_data.notes, base_corners);
+    let active_columns = ActiveColumns::from_key_usage(&key_usage)?;
+    let key_usage_400 = get_key_usage_400_with_pool(map_data.column_count, map_data.total_duration, &map_data.notes, base_corners, pool);
+    let anchor = compute_anchor_with_pool(map_data.column_count, &key_usage_400, base_corners, pool);
+    Ok((key_usage, active_columns, key_usage_400, anchor))
 }
 
 /// Phase 2: Bar calculations
+#[allow(clippy::type_complexity)]
 pub fn phase2(
     map_data: &MapData,
-    active_columns: &[Vec<usize>],
+    key_usage: &ColumnMatrix<bool>,
+    active_columns: &ActiveColumns,
     a_corners: &[f64],
     base_corners: &[f64],
     all_corners: &[f64],
     anchor: &[f64],
-) -> (Vec<f64>, Vec<f64>, Vec<f64>, Vec<f64>, Vec<f64>, Vec<f64>, Vec<f64>) {
-    let (jbar, xbar, pbar, abar, rbar, c_arr, ks_arr) = compute_all_bars(
-        map_data, active_columns, a_corners, base_corners, all_corners, anchor,
-    );
-    (jbar, xbar, pbar, abar, rbar, c_arr, ks_arr)
+) -> StarRatingResult<(Vec<f64>, Vec<f64>, Vec<f64>, Vec<f64>, Vec<f64>, Vec<f64>, Vec<f64>)> {
+    let (jbar, xbar, pbar, abar, rbar, c_arr, ks_arr, _) = compute_all_bars(
+        map_data, key_usage, active_columns, a_corners, base_corners, all_corners, anchor, false, &[], &mut BufferPool::new(),
+    )?;
+    Ok((jbar, xbar, pbar, abar, rbar, c_arr, ks_arr))
 }
 
 /// Phase 3: Final value calculations
@@ -93,7 +159,7 @@ pub fn phase3(
     c_arr: &[f64],
     ks_arr: &[f64],
 ) -> (Vec<f64>, Vec<f64>, Vec<f64>) {
-    compute_final_values(jbar, xbar, pbar, abar, rbar, c_arr, ks_arr)
+    compute_final_values(jbar, xbar, pbar, abar, rbar, c_arr, ks_arr, &[], &mut BufferPool::new())
 }
 
 /// Phase 4: Weighted aggregation and percentiles
@@ -102,7 +168,7 @@ pub fn phase4(
     c_arr: &[f64],
     all_corners: &[f64],
 ) -> (f64, f64, f64) {
-    compute_weighted_aggregation(d_all, c_arr, all_corners)
+    compute_weighted_aggregation(d_all, c_arr, all_corners, &mut BufferPool::new())
 }
 
 /// Phase 5: Final star rating calculation
@@ -116,56 +182,69 @@ pub fn phase5(
     compute_final_star_rating(percentile_93, percentile_83, weighted_mean, notes, long_notes)
 }
 
-/// Computes active columns for each time point
-#[inline]
-fn compute_active_columns(key_usage: &std::collections::HashMap<usize, Vec<bool>>, k: usize, n: usize) -> Vec<Vec<usize>> {
-    (0..n)
-        .map(|i| {
-            let mut active = Vec::with_capacity(k);
-            for col in 0..k {
-                if key_usage.get(&col).map_or(false, |v| v[i]) {
-                    active.push(col);
-                }
-            }
-            active
-        })
-        .collect()
-}
+/// Evaluated extra skill: combination target, weight and values on all corners
+pub type ExtraSkillValues = (SkillTarget, f64, Vec<f64>);
 
-/// Calculates all bars (jbar, xbar, pbar, abar, rbar) and c/ks arrays
+/// Calculates all bars (jbar, xbar, pbar, abar, rbar), c/ks arrays and extra skills
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
 fn compute_all_bars(
     map_data: &MapData,
-    active_columns: &[Vec<usize>],
+    key_usage: &ColumnMatrix<bool>,
+    active_columns: &ActiveColumns,
     a_corners: &[f64],
     base_corners: &[f64],
     all_corners: &[f64],
     anchor: &[f64],
-) -> (Vec<f64>, Vec<f64>, Vec<f64>, Vec<f64>, Vec<f64>, Vec<f64>, Vec<f64>) {
-    let (delta_ks, mut jbar) = compute_jbar(map_data.column_count, map_data.total_duration, map_data.hit_leniency, &map_data.notes_by_column, base_corners);
-    jbar = interp_values(all_corners, base_corners, &jbar);
-
-    let mut xbar = compute_xbar(map_data.column_count, map_data.total_duration, map_data.hit_leniency, &map_data.notes_by_column, active_columns, base_corners);
-    xbar = interp_values(all_corners, base_corners, &xbar);
-
-    let ln_rep = ln_bodies_count_sparse_representation(&map_data.long_notes, map_data.total_duration);
-    let mut pbar = compute_pbar(map_data.column_count, map_data.total_duration, map_data.hit_leniency, &map_data.notes, &ln_rep, anchor, base_corners);
-    pbar = interp_values(all_corners, base_corners, &pbar);
-
-    let mut abar = compute_abar(map_data.column_count, map_data.total_duration, map_data.hit_leniency, &map_data.notes_by_column, active_columns, &delta_ks, a_corners, base_corners);
-    abar = interp_values(all_corners, a_corners, &abar);
-
-    let mut rbar = compute_rbar(map_data.column_count, map_data.total_duration, map_data.hit_leniency, &map_data.notes_by_column, &map_data.tail_sequence, base_corners);
-    rbar = interp_values(all_corners, base_corners, &rbar);
-
-    let key_usage = get_key_usage(map_data.column_count, map_data.total_duration, &map_data.notes, base_corners);
-    let (c_step, ks_step) = compute_c_and_ks(map_data.column_count, map_data.total_duration, &map_data.notes, &key_usage, base_corners);
-    let c_arr = step_interp(all_corners, base_corners, &c_step);
-    let ks_arr = step_interp(all_corners, base_corners, &ks_step);
+    on_grid: bool,
+    extra_skills: &[Box<dyn Skill>],
+    pool: &mut BufferPool,
+) -> StarRatingResult<(Vec<f64>, Vec<f64>, Vec<f64>, Vec<f64>, Vec<f64>, Vec<f64>, Vec<f64>, Vec<ExtraSkillValues>)> {
+    // The skills share the pool through their context for the duration of the evaluation
+    let context = SkillContext::with_pool(map_data, key_usage, active_columns, a_corners, base_corners, all_corners, anchor, std::mem::take(pool))
+        .with_grid_corners(on_grid);
+    #[cfg(not(feature = "parallel"))]
+    let (jbar, xbar, pbar, abar, rbar) = (
+        JackSkill.evaluate(&context),
+        CrossColumnSkill.evaluate(&context),
+        PressingSkill.evaluate(&context),
+        UnevennessSkill.evaluate(&context),
+        ReleaseSkill.evaluate(&context),
+    );
+    // The bars only read the context, so they can run concurrently
+    #[cfg(feature = "parallel")]
+    let ((jbar, xbar), ((pbar, abar), rbar)) = rayon::join(
+        || rayon::join(|| JackSkill.evaluate(&context), || CrossColumnSkill.evaluate(&context)),
+        || rayon::join(
+            || rayon::join(|| PressingSkill.evaluate(&context), || UnevennessSkill.evaluate(&context)),
+            || ReleaseSkill.evaluate(&context),
+        ),
+    );
 
-    (jbar, xbar, pbar, abar, rbar, c_arr, ks_arr)
+    #[cfg(not(feature = "parallel"))]
+    let extras: StarRatingResult<Vec<ExtraSkillValues>> = extra_skills.iter()
+        .map(|skill| Ok((skill.target(), skill.weight(), skill.evaluate(&context)?)))
+        .collect();
+    #[cfg(feature = "parallel")]
+    let extras: StarRatingResult<Vec<ExtraSkillValues>> = {
+        use rayon::prelude::*;
+        extra_skills.par_iter()
+            .map(|skill| Ok((skill.target(), skill.weight(), skill.evaluate(&context)?)))
+            .collect()
+    };
+    // The pool comes back before any error is returned
+    *pool = context.into_pool();
+    let (jbar, xbar, pbar, abar, rbar, extras) = (jbar?, xbar?, pbar?, abar?, rbar?, extras?);
+
+    let (c_step, ks_step) = compute_c_and_ks_with_pool(map_data.column_count, map_data.total_duration, &map_data.notes, key_usage, base_corners, pool);
+    let c_arr = step_interp_with_pool(all_corners, base_corners, &c_step, pool);
+    let ks_arr = step_interp_with_pool(all_corners, base_corners, &ks_step, pool);
+    pool.give_all([c_step, ks_step]);
+
+    Ok((jbar, xbar, pbar, abar, rbar, c_arr, ks_arr, extras))
 }
 
 /// Computes final S, T and D values
+#[allow(clippy::too_many_arguments)]
 fn compute_final_values(
     jbar: &[f64],
     xbar: &[f64],
@@ -174,32 +253,58 @@ fn compute_final_values(
     rbar: &[f64],
     c_arr: &[f64],
     ks_arr: &[f64],
+    extras: &[ExtraSkillValues],
+    pool: &mut BufferPool,
 ) -> (Vec<f64>, Vec<f64>, Vec<f64>) {
-    let s_all: Vec<f64> = jbar.iter()
-        .zip(xbar.iter())
+    // Extra skills: strain ones join the S power sum, technical ones are added to Xbar
+    let mut strain_extra = pool.take(jbar.len(), 0.0);
+    let mut technical_extra = pool.take(jbar.len(), 0.0);
+    for (target, weight, values) in extras {
+        match target {
+            SkillTarget::Strain => {
+                for (acc, &v) in strain_extra.iter_mut().zip(values.iter()) {
+                    *acc += weight * v.max(0.0).powf(1.5);
+                }
+            }
+            SkillTarget::Technical => {
+                for (acc, &v) in technical_extra.iter_mut().zip(values.iter()) {
+                    *acc += weight * v;
+                }
+            }
+        }
+    }
+
+    let mut s_all = pool.take_empty(jbar.len());
+    s_all.extend(jbar.iter()
         .zip(pbar.iter())
         .zip(abar.iter())
         .zip(rbar.iter())
         .zip(c_arr.iter())
         .zip(ks_arr.iter())
-        .map(|((((((&j, &_x), &p), &a), &r), &c), &ks)| {
+        .zip(strain_extra.iter())
+        .map(|((((((&j, &p), &a), &r), &c), &ks), &extra)| {
             ((0.4 * (a.powf(3.0 / ks) * (j.min(8.0 + 0.85 * j))).powf(1.5))
-                + (0.6 * (a.powf(2.0 / 3.0) * (0.8 * p + r * 35.0 / (c + 8.0))).powf(1.5)))
+                + (0.6 * (a.powf(2.0 / 3.0) * (0.8 * p + r * 35.0 / (c + 8.0))).powf(1.5))
+                + extra)
                 .powf(2.0 / 3.0)
-        })
-        .collect();
+        }));
 
-    let t_all: Vec<f64> = s_all.iter()
+    let mut t_all = pool.take_empty(s_all.len());
+    t_all.extend(s_all.iter()
         .zip(xbar.iter())
         .zip(abar.iter())
         .zip(ks_arr.iter())
-        .map(|(((&s_val, &x), &a), &ks)| (a.powf(3.0 / ks) * x) / (x + s_val + 1.0))
-        .collect();
-
-    let d_all: Vec<f64> = s_all.iter()
+        .zip(technical_extra.iter())
+        .map(|((((&s_val, &x), &a), &ks), &extra)| {
+            let x = x + extra;
+            (a.powf(3.0 / ks) * x) / (x + s_val + 1.0)
+        }));
+
+    let mut d_all = pool.take_empty(s_all.len());
+    d_all.extend(s_all.iter()
         .zip(t_all.iter())
-        .map(|(&s_val, &t_val)| 2.7 * s_val.sqrt() * t_val.powf(1.5) + s_val * 0.27)
-        .collect();
+        .map(|(&s_val, &t_val)| 2.7 * s_val.sqrt() * t_val.powf(1.5) + s_val * 0.27));
+    pool.give_all([strain_extra, technical_extra]);
 
     (s_all, t_all, d_all)
 }
@@ -209,65 +314,128 @@ fn compute_weighted_aggregation(
     d_all: &[f64],
     c_arr: &[f64],
     all_corners: &[f64],
+    pool: &mut BufferPool,
 ) -> (f64, f64, f64) {
-    // Calculate gaps
-    let gaps = compute_gaps(all_corners);
-    
-    // Calculate effective weights
-    let effective_weights: Vec<f64> = c_arr.iter()
-        .zip(gaps.iter())
-        .map(|(c, g)| c * g)
-        .collect();
+    let effective_weights = compute_effective_weights_with_pool(c_arr, all_corners, pool);
+    let aggregation = aggregate_difficulties(d_all, &effective_weights, pool);
+    pool.give(effective_weights);
+    aggregation
+}
 
-    // Sort and calculate percentiles - use unstable sort for better performance
-    let mut indices: Vec<usize> = (0..d_all.len()).collect();
-    indices.sort_unstable_by(|&i, &j| d_all[i].partial_cmp(&d_all[j]).expect("Valeurs finies attendues"));
+/// Weighted percentiles and power mean of (difficulty, weight) pairs
+///
+/// Shared by the one-shot and the chunked calculations, which only differ in how
+/// the pairs are produced.
+///
+/// # Arguments
+/// * `d_all` - Difficulty at each corner
+/// * `weights` - Effective weight of each corner
+///
+/// # Returns
+/// Tuple (percentile_93, percentile_83, weighted_mean)
+pub(crate) fn aggregate_difficulties(d_all: &[f64], weights: &[f64], pool: &mut BufferPool) -> (f64, f64, f64) {
+    // Sort and calculate percentiles
+    let (d_sorted, w_sorted) = sort_by_difficulty_with_pool(d_all, weights, pool);
+    let (percentile_93, percentile_83) = weighted_percentiles(&d_sorted, &w_sorted, pool);
+
+    // Calculate weighted mean
+    let (num, den) = d_sorted.iter()
+        .zip(w_sorted.iter())
+        .fold((0.0, 0.0), |(num, den), (&d, &w)| {
+            (num + d.powf(5.0) * w, den + w)
+        });
+    let weighted_mean = (num / den).powf(1.0 / 5.0);
 
-    let d_sorted: Vec<f64> = indices.iter().map(|&i| d_all[i]).collect();
-    let w_sorted: Vec<f64> = indices.iter().map(|&i| effective_weights[i]).collect();
+    pool.give_all([d_sorted, w_sorted]);
+    (percentile_93, percentile_83, weighted_mean)
+}
 
+/// Weighted 93rd and 83rd percentiles of difficulties sorted ascending
+///
+/// # Arguments
+/// * `d_sorted` - Difficulties, ascending
+/// * `w_sorted` - Weight of each difficulty
+///
+/// # Returns
+/// Tuple (percentile_93, percentile_83)
+pub(crate) fn weighted_percentiles(d_sorted: &[f64], w_sorted: &[f64], pool: &mut BufferPool) -> (f64, f64) {
     // Calculate cumulative weights
-    let cum_weights: Vec<f64> = w_sorted.iter()
+    let mut norm_cum_weights = pool.take_empty(w_sorted.len());
+    norm_cum_weights.extend(w_sorted.iter()
         .scan(0.0, |acc, &w| {
             *acc += w;
             Some(*acc)
-        })
-        .collect();
+        }));
 
-    let total_weight = cum_weights.last().unwrap_or(&1.0);
-    let norm_cum_weights: Vec<f64> = cum_weights.iter()
-        .map(|cw| cw / total_weight)
-        .collect();
+    let total_weight = norm_cum_weights.last().copied().unwrap_or(1.0);
+    for cw in norm_cum_weights.iter_mut() {
+        *cw /= total_weight;
+    }
 
     // Calculate percentiles
     let target_percentiles = [0.945, 0.935, 0.925, 0.915, 0.845, 0.835, 0.825, 0.815];
-    let indices: Vec<usize> = target_percentiles.iter()
-        .filter_map(|&p| norm_cum_weights.iter().position(|&v| v >= p))
-        .collect();
+    let mut indices = pool.take_indices(target_percentiles.len());
+    indices.extend(target_percentiles.iter()
+        .filter_map(|&p| norm_cum_weights.iter().position(|&v| v >= p)));
 
     let percentile_93 = indices[..4].iter().map(|&i| d_sorted[i]).sum::<f64>() / 4.0;
     let percentile_83 = indices[4..8].iter().map(|&i| d_sorted[i]).sum::<f64>() / 4.0;
 
-    // Calculate weighted mean
-    let (num, den) = d_sorted.iter()
-        .zip(w_sorted.iter())
-        .fold((0.0, 0.0), |(num, den), (&d, &w)| {
-            (num + d.powf(5.0) * w, den + w)
-        });
-    let weighted_mean = (num / den).powf(1.0 / 5.0);
+    pool.give(norm_cum_weights);
+    pool.give_indices(indices);
+    (percentile_93, percentile_83)
+}
 
-    (percentile_93, percentile_83, weighted_mean)
+/// Computes effective weights (c * corner gap) on all corners
+pub(crate) fn compute_effective_weights(c_arr: &[f64], all_corners: &[f64]) -> Vec<f64> {
+    compute_effective_weights_with_pool(c_arr, all_corners, &mut BufferPool::new())
+}
+
+/// Effective weights computed in place over the pooled corner gaps
+fn compute_effective_weights_with_pool(c_arr: &[f64], all_corners: &[f64], pool: &mut BufferPool) -> Vec<f64> {
+    let mut weights = compute_gaps_with_pool(all_corners, pool);
+    for (g, &c) in weights.iter_mut().zip(c_arr.iter()) {
+        *g *= c;
+    }
+    weights.truncate(c_arr.len());
+    weights
+}
+
+/// Sorts difficulty values ascending, carrying their weights along
+pub(crate) fn sort_by_difficulty(d_all: &[f64], weights: &[f64]) -> (Vec<f64>, Vec<f64>) {
+    sort_by_difficulty_with_pool(d_all, weights, &mut BufferPool::new())
+}
+
+/// Same as `sort_by_difficulty`, with the index and output buffers taken from `pool`
+pub(crate) fn sort_by_difficulty_with_pool(d_all: &[f64], weights: &[f64], pool: &mut BufferPool) -> (Vec<f64>, Vec<f64>) {
+    // Use unstable sort for better performance
+    let mut indices = pool.take_indices(d_all.len());
+    indices.extend(0..d_all.len());
+    indices.sort_unstable_by(|&i, &j| d_all[i].partial_cmp(&d_all[j]).expect("Valeurs finies attendues"));
+
+    let mut d_sorted = pool.take_empty(indices.len());
+    d_sorted.extend(indices.iter().map(|&i| d_all[i]));
+    let mut w_sorted = pool.take_empty(indices.len());
+    w_sorted.extend(indices.iter().map(|&i| weights[i]));
+    pool.give_indices(indices);
+    (d_sorted, w_sorted)
 }
 
 /// Computes gaps between corners
 #[inline]
-fn compute_gaps(all_corners: &[f64]) -> Vec<f64> {
+pub(crate) fn compute_gaps(all_corners: &[f64]) -> Vec<f64> {
+    compute_gaps_with_pool(all_corners, &mut BufferPool::new())
+}
+
+/// Same as `compute_gaps`, with the output taken from `pool`
+#[inline]
+fn compute_gaps_with_pool(all_corners: &[f64], pool: &mut BufferPool) -> Vec<f64> {
     let n = all_corners.len();
     if n < 2 {
-        return vec![0.0; n];
+        return pool.take(n, 0.0);
     }
 
-    let mut gaps = Vec::with_capacity(n);
+    let mut gaps = pool.take_empty(n);
     gaps.push((all_corners[1] - all_corners[0]) / 2.0);
     
     for i in 1..n - 1 {
@@ -293,7 +461,7 @@ fn compute_final_star_rating(
         + 0.5 * long_notes.iter()
             .map(|note| {
                 let duration = note.duration();
-                ((duration).min(1000).max(0) as f64) / 200.0
+                duration.clamp(0.0, 1000.0) / 200.0
             })
             .sum::<f64>();
 
@@ -303,3 +471,82 @@ fn compute_final_star_rating(
 
     sr
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithm::process::preprocess::preprocess_file;
+    use crate::types::StarRatingError;
+
+    /// Ratings of the assets/ maps recorded with the sequential build, as f64 bits
+    const SEQUENTIAL_RATINGS: &[(&str, &str, u64)] = &[
+        ("Various Artist - Moonlight 1.25x (flashnias) [Synthesis pattern].osu", "None", 0x401acbde10ae759c), // 6.6991
+        ("Various Artist - Moonlight 1.25x (flashnias) [Synthesis pattern].osu", "DT", 0x402366970e12fa72), // 9.7004
+        ("Various Artist - Moonlight 1.25x (flashnias) [Synthesis pattern].osu", "HT", 0x4014e4c86beea248), // 5.2234
+        ("benchmark.osu", "None", 0x4020b744d83dca10), // 8.3579
+        ("benchmark.osu", "DT", 0x4025890c9a1ad163), // 10.7677
+        ("benchmark.osu", "HT", 0x401b1ac6b1c31f75), // 6.7761
+        ("test.osu", "None", 0x40178c0196a08bc4), // 5.8867
+        ("test.osu", "DT", 0x4021b91b46b4d536), // 8.8615
+        ("test.osu", "HT", 0x401217507394db0b), // 4.5228
+    ];
+
+    fn assert_matches_sequential_ratings() {
+        for &(file, mod_name, bits) in SEQUENTIAL_RATINGS {
+            let map_data = preprocess_file(&format!("assets/{}", file), mod_name).unwrap();
+            let rating = calculate(&map_data).unwrap().rating;
+            assert_eq!(rating.to_bits(), bits, "{} {}: {} != {}", file, mod_name, rating, f64::from_bits(bits));
+        }
+    }
+
+    /// A k-key map with a chord-free stream over every column
+    fn stream_map(column_count: usize) -> MapData {
+        use crate::algorithm::process::normalize::{rebuild_groupings, recompute_hit_leniency, recompute_total_duration};
+        use crate::types::Note;
+
+        let mut map_data = MapData::new();
+        map_data.column_count = column_count;
+        map_data.overall_difficulty = 8.0;
+        map_data.notes = (0..200).map(|i| Note::simple(i % column_count, 1000.0 + 90.0 * i as f64)).collect();
+        rebuild_groupings(&mut map_data);
+        recompute_hit_leniency(&mut map_data);
+        recompute_total_duration(&mut map_data);
+        map_data
+    }
+
+    #[test]
+    fn key_counts_above_the_cross_matrix_are_errors() {
+        assert!(calculate(&stream_map(MAX_KEY_COUNT)).is_ok());
+        for column_count in [11, 12, 70] {
+            match calculate(&stream_map(column_count)) {
+                Err(StarRatingError::CalculationError(CalculationError::IndexOutOfBounds(_, count, max))) => {
+                    assert_eq!((count, max), (column_count, MAX_KEY_COUNT));
+                }
+                other => panic!("{}K: expected IndexOutOfBounds, got {:?}", column_count, other.map(|rating| rating.rating)),
+            }
+        }
+    }
+
+    #[test]
+    fn maps_without_notes_are_errors() {
+        let mut map_data = stream_map(4);
+        map_data.notes.clear();
+        assert!(matches!(
+            calculate(&map_data),
+            Err(StarRatingError::CalculationError(CalculationError::EmptyData(_)))
+        ));
+    }
+
+    /// Keeps the recorded ratings in sync with the sequential code
+    #[cfg(not(feature = "parallel"))]
+    #[test]
+    fn sequential_ratings_match_recorded() {
+        assert_matches_sequential_ratings();
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn parallel_ratings_match_sequential() {
+        assert_matches_sequential_ratings();
+    }
+}
@@ -0,0 +1,206 @@
+//! Element-wise kernels of the smoothing, cumulative sum and interpolation passes
+//!
+//! Each kernel has a scalar version and an AVX2 version written with 4-lane `f64`
+//! intrinsics, picked at runtime (other targets use the scalar version). The AVX2
+//! versions do the same operations in the same order, without FMA, and handle the
+//! last `len % 4` elements with the scalar code, so both return bit-identical values.
+
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::{_mm256_add_pd, _mm256_div_pd, _mm256_loadu_pd, _mm256_mul_pd, _mm256_set1_pd, _mm256_set_pd, _mm256_storeu_pd, _mm256_sub_pd};
+
+/// Elements handled by one AVX2 vector
+#[cfg(target_arch = "x86_64")]
+const LANES: usize = 4;
+
+/// Writes `out[i] = f[i] * (x[i + 1] - x[i])` for every step of `x`
+///
+/// # Arguments
+/// * `x` - Sorted time points
+/// * `f` - Values at each time point
+/// * `out` - Output, at least `x.len() - 1` values
+pub fn weighted_steps(x: &[f64], f: &[f64], out: &mut [f64]) {
+    #[cfg(target_arch = "x86_64")]
+    if std::arch::is_x86_feature_detected!("avx2") {
+        // SAFETY: AVX2 support was checked at runtime
+        return unsafe { weighted_steps_avx2(x, f, out) };
+    }
+    weighted_steps_scalar(x, f, out)
+}
+
+/// Scalar version of `weighted_steps`
+#[inline(always)]
+pub(crate) fn weighted_steps_scalar(x: &[f64], f: &[f64], out: &mut [f64]) {
+    let steps = x.len().saturating_sub(1);
+    let (x0, x1) = (&x[..steps], &x[1..=steps]);
+    for (((out, &a), &b), &v) in out[..steps].iter_mut().zip(x0).zip(x1).zip(&f[..steps]) {
+        *out = v * (b - a);
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+fn weighted_steps_avx2(x: &[f64], f: &[f64], out: &mut [f64]) {
+    let steps = x.len().saturating_sub(1);
+    let (x0, x1, f, out) = (&x[..steps], &x[1..=steps], &f[..steps], &mut out[..steps]);
+    let vectorized = steps - steps % LANES;
+    for i in (0..vectorized).step_by(LANES) {
+        // SAFETY: i + LANES <= steps, the length of the four slices
+        unsafe {
+            let a = _mm256_loadu_pd(x0.as_ptr().add(i));
+            let b = _mm256_loadu_pd(x1.as_ptr().add(i));
+            let v = _mm256_loadu_pd(f.as_ptr().add(i));
+            _mm256_storeu_pd(out.as_mut_ptr().add(i), _mm256_mul_pd(v, _mm256_sub_pd(b, a)));
+        }
+    }
+    weighted_steps_scalar(&x[vectorized..], &f[vectorized..], &mut out[vectorized..])
+}
+
+/// Writes `lo[i] = scale * (hi[i] - lo[i])`
+///
+/// # Arguments
+/// * `hi` - Upper values
+/// * `lo` - Lower values, replaced by the scaled differences
+/// * `scale` - Factor applied to each difference
+pub fn scaled_difference(hi: &[f64], lo: &mut [f64], scale: f64) {
+    #[cfg(target_arch = "x86_64")]
+    if std::arch::is_x86_feature_detected!("avx2") {
+        // SAFETY: AVX2 support was checked at runtime
+        return unsafe { scaled_difference_avx2(hi, lo, scale) };
+    }
+    scaled_difference_scalar(hi, lo, scale)
+}
+
+/// Scalar version of `scaled_difference`
+#[inline(always)]
+pub(crate) fn scaled_difference_scalar(hi: &[f64], lo: &mut [f64], scale: f64) {
+    for (lo, &hi) in lo.iter_mut().zip(hi) {
+        *lo = scale * (hi - *lo);
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+fn scaled_difference_avx2(hi: &[f64], lo: &mut [f64], scale: f64) {
+    let len = hi.len().min(lo.len());
+    let vectorized = len - len % LANES;
+    let factor = _mm256_set1_pd(scale);
+    for i in (0..vectorized).step_by(LANES) {
+        // SAFETY: i + LANES <= len, the length of the shorter slice
+        unsafe {
+            let high = _mm256_loadu_pd(hi.as_ptr().add(i));
+            let low = _mm256_loadu_pd(lo.as_ptr().add(i));
+            _mm256_storeu_pd(lo.as_mut_ptr().add(i), _mm256_mul_pd(factor, _mm256_sub_pd(high, low)));
+        }
+    }
+    scaled_difference_scalar(&hi[vectorized..], &mut lo[vectorized..], scale)
+}
+
+/// Linear interpolation of `old_vals` at `new_x`, given the right bracket index of each point
+///
+/// # Arguments
+/// * `new_x` - Query points
+/// * `old_x` - Sorted sample points
+/// * `old_vals` - Sample values
+/// * `right` - For each query, an index `i` with `old_x[i - 1] <= x < old_x[i]` (`1 <= i < old_x.len()`)
+/// * `out` - Output, one value per query
+pub fn lerp_bracketed(new_x: &[f64], old_x: &[f64], old_vals: &[f64], right: &[usize], out: &mut [f64]) {
+    #[cfg(target_arch = "x86_64")]
+    if std::arch::is_x86_feature_detected!("avx2") {
+        // SAFETY: AVX2 support was checked at runtime
+        return unsafe { lerp_bracketed_avx2(new_x, old_x, old_vals, right, out) };
+    }
+    lerp_bracketed_scalar(new_x, old_x, old_vals, right, out)
+}
+
+/// Scalar version of `lerp_bracketed`
+#[inline(always)]
+pub(crate) fn lerp_bracketed_scalar(new_x: &[f64], old_x: &[f64], old_vals: &[f64], right: &[usize], out: &mut [f64]) {
+    for ((out, &nx), &idx) in out.iter_mut().zip(new_x).zip(right) {
+        let x0 = old_x[idx - 1];
+        let x1 = old_x[idx];
+        let y0 = old_vals[idx - 1];
+        let y1 = old_vals[idx];
+        let t = (nx - x0) / (x1 - x0);
+        *out = y0 + t * (y1 - y0);
+    }
+}
+
+/// The bracket values are gathered with bounds-checked scalar reads (queries do not
+/// share brackets in any pattern worth a hardware gather); the arithmetic is 4 lanes wide.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+fn lerp_bracketed_avx2(new_x: &[f64], old_x: &[f64], old_vals: &[f64], right: &[usize], out: &mut [f64]) {
+    let len = out.len().min(new_x.len()).min(right.len());
+    let vectorized = len - len % LANES;
+    for i in (0..vectorized).step_by(LANES) {
+        let r = &right[i..i + LANES];
+        let x0 = _mm256_set_pd(old_x[r[3] - 1], old_x[r[2] - 1], old_x[r[1] - 1], old_x[r[0] - 1]);
+        let x1 = _mm256_set_pd(old_x[r[3]], old_x[r[2]], old_x[r[1]], old_x[r[0]]);
+        let y0 = _mm256_set_pd(old_vals[r[3] - 1], old_vals[r[2] - 1], old_vals[r[1] - 1], old_vals[r[0] - 1]);
+        let y1 = _mm256_set_pd(old_vals[r[3]], old_vals[r[2]], old_vals[r[1]], old_vals[r[0]]);
+        // SAFETY: i + LANES <= len, which bounds both new_x and out
+        unsafe {
+            let nx = _mm256_loadu_pd(new_x.as_ptr().add(i));
+            let t = _mm256_div_pd(_mm256_sub_pd(nx, x0), _mm256_sub_pd(x1, x0));
+            _mm256_storeu_pd(out.as_mut_ptr().add(i), _mm256_add_pd(y0, _mm256_mul_pd(t, _mm256_sub_pd(y1, y0))));
+        }
+    }
+    lerp_bracketed_scalar(&new_x[vectorized..], old_x, old_vals, &right[vectorized..], &mut out[vectorized..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{assert_bits_eq, TestRng};
+
+    /// Lengths around the 4-lane width, so that the remainder loops are covered
+    const LENGTHS: [usize; 8] = [1, 2, 3, 4, 5, 7, 64, 1001];
+
+    #[test]
+    fn weighted_steps_matches_scalar() {
+        let mut rng = TestRng::new(1);
+        for len in LENGTHS {
+            let x = rng.sorted_points(len);
+            let f = rng.values(len, 40.0);
+            let mut fast = vec![0.0; len - 1];
+            let mut scalar = vec![0.0; len - 1];
+            weighted_steps(&x, &f, &mut fast);
+            weighted_steps_scalar(&x, &f, &mut scalar);
+            assert_bits_eq("weighted_steps", &fast, &scalar);
+        }
+    }
+
+    #[test]
+    fn scaled_difference_matches_scalar() {
+        let mut rng = TestRng::new(2);
+        for len in LENGTHS {
+            let hi = rng.values(len, 1e4);
+            let lo = rng.values(len, 1e4);
+            let (mut fast, mut scalar) = (lo.clone(), lo);
+            scaled_difference(&hi, &mut fast, 0.001);
+            scaled_difference_scalar(&hi, &mut scalar, 0.001);
+            assert_bits_eq("scaled_difference", &fast, &scalar);
+        }
+    }
+
+    #[test]
+    fn lerp_bracketed_matches_scalar() {
+        let mut rng = TestRng::new(3);
+        for len in LENGTHS {
+            let mut old_x = rng.sorted_points(len + 1);
+            old_x.dedup();
+            if old_x.len() < 2 {
+                continue;
+            }
+            let old_vals = rng.values(old_x.len(), 40.0);
+            let span = old_x[old_x.len() - 1] - old_x[0];
+            let new_x: Vec<f64> = (0..len).map(|_| old_x[0] + rng.next_f64() * span).collect();
+            let right: Vec<usize> = new_x.iter().map(|&nx| old_x.partition_point(|&ox| ox <= nx).clamp(1, old_x.len() - 1)).collect();
+            let mut fast = vec![0.0; len];
+            let mut scalar = vec![0.0; len];
+            lerp_bracketed(&new_x, &old_x, &old_vals, &right, &mut fast);
+            lerp_bracketed_scalar(&new_x, &old_x, &old_vals, &right, &mut scalar);
+            assert_bits_eq("lerp_bracketed", &fast, &scalar);
+        }
+    }
+}
@@ -1,4 +1,8 @@
-use crate::algorithm::sums::{cumulative_sum, query_cumsum};
+use crate::algorithm::pool::BufferPool;
+use crate::algorithm::simd::scaled_difference;
+use crate::algorithm::sums::{cumulative_sum_into, CumsumCursor};
+#[cfg(test)]
+use crate::algorithm::sums::{cumulative_sum_into_scalar, query_cumsum};
 
 #[derive(Clone, Copy)]
 pub enum SmoothMode {
@@ -8,7 +12,49 @@ pub enum SmoothMode {
 
 #[inline]
 pub fn smooth_on_corners(x: &[f64], f: &[f64], window: f64, scale: f64, mode: SmoothMode) -> Vec<f64> {
-    let f_cumsum = cumulative_sum(x, f);
+    smooth_on_corners_with_pool(x, f, window, scale, mode, &mut BufferPool::new())
+}
+
+/// Same as `smooth_on_corners`, with the cumulative sum and the output taken from `pool`
+///
+/// Window bounds grow with the corners, so both ends are found with forward-only cursors.
+#[inline]
+pub fn smooth_on_corners_with_pool(x: &[f64], f: &[f64], window: f64, scale: f64, mode: SmoothMode, pool: &mut BufferPool) -> Vec<f64> {
+    let mut f_cumsum = pool.take(x.len(), 0.0);
+    cumulative_sum_into(x, f, &mut f_cumsum);
+    let mut g = pool.take(f.len(), 0.0);
+    if x.is_empty() {
+        pool.give(f_cumsum);
+        return g;
+    }
+    let first = x[0];
+    let last = *x.last().expect("Vecteur non vide attendu");
+    let mut upper = pool.take(x.len(), 0.0);
+    let mut lo_cursor = CumsumCursor::new(x, &f_cumsum, f);
+    let mut hi_cursor = CumsumCursor::new(x, &f_cumsum, f);
+    for ((lo, hi), &s) in g.iter_mut().zip(upper.iter_mut()).zip(x.iter()) {
+        *lo = lo_cursor.query((s - window).max(first));
+        *hi = hi_cursor.query((s + window).min(last));
+    }
+    match mode {
+        SmoothMode::Sum => scaled_difference(&upper, &mut g, scale),
+        SmoothMode::Avg => {
+            for ((value, &hi), &s) in g.iter_mut().zip(upper.iter()).zip(x.iter()) {
+                let a = (s - window).max(first);
+                let b = (s + window).min(last);
+                *value = if (b - a) > 0.0 { (hi - *value) / (b - a) } else { 0.0 };
+            }
+        }
+    }
+    pool.give_all([f_cumsum, upper]);
+    g
+}
+
+/// Reference version of `smooth_on_corners`, bisecting every window bound
+#[cfg(test)]
+pub(crate) fn smooth_on_corners_bisect(x: &[f64], f: &[f64], window: f64, scale: f64, mode: SmoothMode) -> Vec<f64> {
+    let mut f_cumsum = vec![0.0; x.len()];
+    cumulative_sum_into_scalar(x, f, &mut f_cumsum);
     let mut g = vec![0.0; f.len()];
     for (i, &s) in x.iter().enumerate() {
         let a = (s - window).max(x[0]);
@@ -28,3 +74,26 @@ pub fn rescale_high(sr: f64) -> f64 {
     } 
     9.0 + (sr - 9.0) * (1.0 / 1.2)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{assert_bits_eq, TestRng};
+
+    #[test]
+    fn cursor_smoothing_matches_bisect() {
+        let mut rng = TestRng::new(31);
+        for len in [1, 2, 5, 64, 1001] {
+            // sorted_points repeats some x values
+            let x = rng.sorted_points(len);
+            let f = rng.values(len, 40.0);
+            for (mode, window, scale) in [(SmoothMode::Sum, 500.0, 0.001), (SmoothMode::Avg, 250.0, 1.0), (SmoothMode::Sum, 0.0, 1.0)] {
+                assert_bits_eq(
+                    &format!("smooth_on_corners ({} points, window {})", len, window),
+                    &smooth_on_corners(&x, &f, window, scale, mode),
+                    &smooth_on_corners_bisect(&x, &f, window, scale, mode),
+                );
+            }
+        }
+    }
+}
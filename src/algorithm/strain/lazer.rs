@@ -0,0 +1,232 @@
+use crate::algorithm::strain::{apply_decay, weighted_peak_sum};
+use crate::types::{MapData, StarRatingResult, StrainStarRating};
+
+const DIFFICULTY_MULTIPLIER: f64 = 0.018;
+const INDIVIDUAL_DECAY_BASE: f64 = 0.125;
+const OVERALL_DECAY_BASE: f64 = 0.30;
+const RELEASE_THRESHOLD: f64 = 30.0;
+const SECTION_LENGTH: f64 = 400.0;
+const DECAY_WEIGHT: f64 = 0.9;
+
+/// `a` is bigger than `b` by more than 1ms (osu!framework's Precision.DefinitelyBigger)
+#[inline]
+fn definitely_bigger(a: f64, b: f64) -> bool {
+    a - b > 1.0
+}
+
+/// Strain skill state, as in osu!lazer's mania Strain skill
+struct LazerStrain {
+    start_times: Vec<f64>,
+    end_times: Vec<f64>,
+    individual_strains: Vec<f64>,
+    individual_strain: f64,
+    overall_strain: f64,
+}
+
+impl LazerStrain {
+    fn new(k: usize) -> Self {
+        Self {
+            start_times: vec![0.0; k],
+            end_times: vec![0.0; k],
+            individual_strains: vec![0.0; k],
+            individual_strain: 0.0,
+            overall_strain: 1.0,
+        }
+    }
+
+    /// Strain after processing one note
+    fn strain_value_of(&mut self, start_time: f64, end_time: f64, delta_time: f64, column: usize) -> f64 {
+        let mut is_overlapping = false;
+        let mut closest_end_time = (end_time - start_time).abs();
+        let mut hold_factor = 1.0;
+        let mut hold_addition = 0.0;
+
+        for i in 0..self.end_times.len() {
+            // The current note is overlapped if a previous note or end is overlapping the current note body
+            is_overlapping |= definitely_bigger(self.end_times[i], start_time)
+                && definitely_bigger(end_time, self.end_times[i])
+                && definitely_bigger(start_time, self.start_times[i]);
+
+            // Slight bonus to everything if something is held meanwhile
+            if definitely_bigger(self.end_times[i], end_time) && definitely_bigger(start_time, self.start_times[i]) {
+                hold_factor = 1.25;
+            }
+
+            closest_end_time = closest_end_time.min((end_time - self.end_times[i]).abs());
+        }
+
+        // Releasing several notes together is as easy as releasing one, so the addition
+        // only applies when no other release is close
+        if is_overlapping {
+            hold_addition = 1.0 / (1.0 + (0.27 * (RELEASE_THRESHOLD - closest_end_time)).exp());
+        }
+
+        self.individual_strains[column] = apply_decay(
+            self.individual_strains[column],
+            start_time - self.start_times[column],
+            INDIVIDUAL_DECAY_BASE,
+        );
+        self.individual_strains[column] += 2.0 * hold_factor;
+
+        // In a chord the individual strain is the hardest of the chord's columns
+        self.individual_strain = if delta_time <= 1.0 {
+            self.individual_strain.max(self.individual_strains[column])
+        } else {
+            self.individual_strains[column]
+        };
+
+        self.overall_strain = apply_decay(self.overall_strain, delta_time, OVERALL_DECAY_BASE);
+        self.overall_strain += (1.0 + hold_addition) * hold_factor;
+
+        self.start_times[column] = start_time;
+        self.end_times[column] = end_time;
+
+        self.individual_strain + self.overall_strain
+    }
+
+    /// Strain at the start of a new section, decayed from the previous note
+    fn initial_strain(&self, offset: f64, previous_start_time: f64) -> f64 {
+        apply_decay(self.individual_strain, offset - previous_start_time, INDIVIDUAL_DECAY_BASE)
+            + apply_decay(self.overall_strain, offset - previous_start_time, OVERALL_DECAY_BASE)
+    }
+}
+
+/// Computes the osu!lazer mania star rating
+///
+/// Same individual/overall strain model as stable, with lazer's overlap-aware hold
+/// addition, chord handling and 400ms sections aligned on the first processed note.
+/// The first note only serves as the previous object of the second one.
+///
+/// # Arguments
+/// * `map_data` - Parsed map data
+/// * `clock_rate` - Playback rate (1.5 for DT, 0.75 for HT). Use 1.0 when the map was
+///   preprocessed with the mod already applied.
+///
+/// # Returns
+/// Lazer star rating and the strain peak of every section
+pub fn calculate_lazer(map_data: &MapData, clock_rate: f64) -> StarRatingResult<StrainStarRating> {
+    let k = map_data.column_count;
+    let notes: Vec<_> = map_data.notes.iter().filter(|note| note.column < k).collect();
+
+    let mut skill = LazerStrain::new(k);
+    let mut strain_peaks = Vec::new();
+    let mut current_section_peak: f64 = 0.0;
+    let mut current_section_end = 0.0;
+
+    for (index, pair) in notes.windows(2).enumerate() {
+        let (previous, current) = (pair[0], pair[1]);
+        let previous_start = previous.hit_time / clock_rate;
+        let start_time = current.hit_time / clock_rate;
+        let end_time = if current.is_long_note() { current.tail_time / clock_rate } else { start_time };
+
+        if index == 0 {
+            current_section_end = (start_time / SECTION_LENGTH).ceil() * SECTION_LENGTH;
+        }
+        while start_time > current_section_end {
+            strain_peaks.push(current_section_peak);
+            current_section_peak = skill.initial_strain(current_section_end, previous_start);
+            current_section_end += SECTION_LENGTH;
+        }
+
+        let strain = skill.strain_value_of(start_time, end_time, start_time - previous_start, current.column);
+        current_section_peak = current_section_peak.max(strain);
+    }
+    strain_peaks.push(current_section_peak);
+
+    // Empty sections do not contribute
+    let positive: Vec<f64> = strain_peaks.iter().copied().filter(|&p| p > 0.0).collect();
+    let rating = weighted_peak_sum(&positive, DECAY_WEIGHT) * DIFFICULTY_MULTIPLIER;
+    Ok(StrainStarRating { rating, strain_peaks })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithm::process::preprocess::preprocess_file;
+    use crate::types::Note;
+
+    fn map(column_count: usize, notes: Vec<Note>) -> MapData {
+        let mut map_data = MapData::new();
+        map_data.column_count = column_count;
+        map_data.notes = notes;
+        map_data
+    }
+
+    /// Strain peak of the section holding the third note, after a seed and one long note in column 0
+    fn overlapped_peak(long_note: Note) -> f64 {
+        let notes = vec![Note::simple(3, 0.0), Note::long_note(0, 100.0, 1000.0), long_note];
+        calculate_lazer(&map(4, notes), 1.0).unwrap().strain_peaks[0]
+    }
+
+    #[test]
+    fn strains_follow_the_decay_formula() {
+        let notes = vec![Note::simple(0, 100.0), Note::simple(0, 300.0), Note::simple(1, 500.0)];
+        let result = calculate_lazer(&map(4, notes), 1.0).unwrap();
+
+        // The first note only serves as the previous object, so sections start at 400
+        let overall = 0.3f64.powf(0.2) + 1.0;
+        let first = 2.0 + overall;
+        let second = 2.0 + overall * 0.3f64.powf(0.2) + 1.0;
+        assert_eq!(result.strain_peaks.len(), 2);
+        assert!((result.strain_peaks[0] - first).abs() < 1e-12);
+        assert!((result.strain_peaks[1] - second).abs() < 1e-12);
+        assert!((result.rating - (first.max(second) + 0.9 * first.min(second)) * DIFFICULTY_MULTIPLIER).abs() < 1e-12);
+    }
+
+    #[test]
+    fn chords_keep_the_hardest_individual_strain() {
+        let notes = vec![Note::simple(3, 0.0), Note::simple(0, 100.0), Note::simple(0, 200.0), Note::simple(1, 200.0)];
+        let result = calculate_lazer(&map(4, notes), 1.0).unwrap();
+
+        // Column 0 is a jack, column 1 is fresh; the chord note keeps column 0's strain
+        let jack = 2.0 * 0.125f64.powf(0.1) + 2.0;
+        let overall = (0.3f64.powf(0.1) + 1.0) * 0.3f64.powf(0.1) + 1.0 + 1.0;
+        assert!((result.strain_peaks[0] - (jack + overall)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn overlapping_releases_get_the_hold_addition() {
+        let overall_at_long_note = 0.3f64.powf(0.1) + 1.0;
+        let peak = |hold_addition: f64| 2.0 + overall_at_long_note * 0.3f64.powf(0.2) + 1.0 + hold_addition;
+
+        // Released 200ms after column 0: the full addition
+        let far = 1.0 / (1.0 + (0.27 * (RELEASE_THRESHOLD - 200.0)).exp());
+        assert!((overlapped_peak(Note::long_note(1, 300.0, 1200.0)) - peak(far)).abs() < 1e-12);
+
+        // Released 10ms after column 0: nearly none
+        let close = 1.0 / (1.0 + (0.27 * (RELEASE_THRESHOLD - 10.0)).exp());
+        assert!(close < 0.01);
+        assert!((overlapped_peak(Note::long_note(1, 300.0, 1010.0)) - peak(close)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn notes_released_inside_a_hold_get_the_hold_factor() {
+        let overall_at_long_note = 0.3f64.powf(0.1) + 1.0;
+        let expected = 2.0 * 1.25 + overall_at_long_note * 0.3f64.powf(0.2) + 1.25;
+        assert!((overlapped_peak(Note::simple(1, 300.0)) - expected).abs() < 1e-12);
+    }
+
+    #[test]
+    fn clock_rate_matches_a_map_preprocessed_with_the_mod() {
+        for (mod_name, clock_rate) in [("DT", 1.5), ("HT", 0.75)] {
+            let rated = calculate_lazer(&preprocess_file("assets/test.osu", "None").unwrap(), clock_rate).unwrap();
+            let preprocessed = calculate_lazer(&preprocess_file("assets/test.osu", mod_name).unwrap(), 1.0).unwrap();
+            assert!((rated.rating - preprocessed.rating).abs() < 1e-9, "{}: {} != {}", mod_name, rated.rating, preprocessed.rating);
+        }
+    }
+
+    #[test]
+    fn maps_without_long_notes_rate_like_stable_up_to_section_alignment() {
+        use crate::algorithm::strain::stable::calculate_stable;
+
+        // A 4K stream starting on a section boundary: both clients see the same strains
+        // and sections, lazer drops the section of the first note and adds the final open one
+        let notes: Vec<Note> = (0..64).map(|i| Note::simple(i % 4, 400.0 + 100.0 * i as f64)).collect();
+        let lazer = calculate_lazer(&map(4, notes.clone()), 1.0).unwrap();
+        let stable = calculate_stable(&map(4, notes), 1.0).unwrap();
+        assert_eq!(lazer.strain_peaks.len(), stable.strain_peaks.len());
+        for (a, b) in lazer.strain_peaks.iter().zip(&stable.strain_peaks[1..]) {
+            assert!((a - b).abs() < 1e-9, "{} != {}", a, b);
+        }
+    }
+}
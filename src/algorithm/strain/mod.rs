@@ -0,0 +1,21 @@
+pub mod lazer;
+pub mod stable;
+
+/// Decays a strain value over `delta_time` milliseconds
+#[inline]
+pub fn apply_decay(value: f64, delta_time: f64, decay_base: f64) -> f64 {
+    value * decay_base.powf(delta_time / 1000.0)
+}
+
+/// Sums section peaks from hardest to easiest, each weighted `decay_weight` times less than the previous one
+pub fn weighted_peak_sum(peaks: &[f64], decay_weight: f64) -> f64 {
+    let mut sorted = peaks.to_vec();
+    sorted.sort_unstable_by(|a, b| b.total_cmp(a));
+    let mut difficulty = 0.0;
+    let mut weight = 1.0;
+    for strain in sorted {
+        difficulty += strain * weight;
+        weight *= decay_weight;
+    }
+    difficulty
+}
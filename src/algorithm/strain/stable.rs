@@ -0,0 +1,189 @@
+use crate::algorithm::strain::{apply_decay, weighted_peak_sum};
+use crate::types::{MapData, StarRatingResult, StrainStarRating};
+
+const STAR_SCALING_FACTOR: f64 = 0.018;
+const INDIVIDUAL_DECAY_BASE: f64 = 0.125;
+const OVERALL_DECAY_BASE: f64 = 0.30;
+const STRAIN_STEP: f64 = 400.0;
+const DECAY_WEIGHT: f64 = 0.9;
+
+/// Strain state of one note, as in osu!stable's ManiaHitObjectDifficulty
+#[derive(Debug, Clone)]
+struct StableStrain {
+    start_time: f64,
+    end_time: f64,
+    column: usize,
+    held_until: Vec<f64>,
+    individual_strains: Vec<f64>,
+    overall_strain: f64,
+}
+
+impl StableStrain {
+    fn new(start_time: f64, end_time: f64, column: usize, k: usize) -> Self {
+        Self {
+            start_time,
+            end_time,
+            column,
+            held_until: vec![0.0; k],
+            individual_strains: vec![0.0; k],
+            overall_strain: 1.0,
+        }
+    }
+
+    fn individual_strain(&self) -> f64 {
+        self.individual_strains[self.column]
+    }
+
+    /// Carries strains over from the previous note and adds this note's contribution
+    fn calculate_strains(&mut self, previous: &StableStrain) {
+        let time_elapsed = self.start_time - previous.start_time;
+
+        let mut hold_factor = 1.0;
+        let mut hold_addition = 0.0;
+        for i in 0..self.held_until.len() {
+            self.held_until[i] = previous.held_until[i];
+            if self.start_time < self.held_until[i] && self.end_time > self.held_until[i] {
+                hold_addition = 1.0;
+            }
+            if self.end_time == self.held_until[i] {
+                hold_addition = 0.0;
+            }
+            if self.held_until[i] > self.end_time {
+                hold_factor = 1.25;
+            }
+        }
+        self.held_until[self.column] = self.end_time;
+
+        for (strain, &prev) in self.individual_strains.iter_mut().zip(previous.individual_strains.iter()) {
+            *strain = apply_decay(prev, time_elapsed, INDIVIDUAL_DECAY_BASE);
+        }
+        self.individual_strains[self.column] += 2.0 * hold_factor;
+
+        self.overall_strain = apply_decay(previous.overall_strain, time_elapsed, OVERALL_DECAY_BASE)
+            + (1.0 + hold_addition) * hold_factor;
+    }
+}
+
+/// Computes the osu!stable mania star rating
+///
+/// Individual (per column) and overall strains decay exponentially between notes; the
+/// highest strain of each 400ms section is kept and sections are summed with a 0.9
+/// weighting from hardest to easiest.
+///
+/// # Arguments
+/// * `map_data` - Parsed map data
+/// * `clock_rate` - Playback rate (1.5 for DT, 0.75 for HT). Use 1.0 when the map was
+///   preprocessed with the mod already applied.
+///
+/// # Returns
+/// Stable star rating and the strain peak of every section
+pub fn calculate_stable(map_data: &MapData, clock_rate: f64) -> StarRatingResult<StrainStarRating> {
+    let k = map_data.column_count;
+    let mut strains: Vec<StableStrain> = Vec::with_capacity(map_data.notes.len());
+    for note in map_data.notes.iter().filter(|note| note.column < k) {
+        let start_time = note.hit_time / clock_rate;
+        let end_time = if note.is_long_note() { note.tail_time / clock_rate } else { start_time };
+        let mut strain = StableStrain::new(start_time, end_time, note.column, k);
+        if let Some(previous) = strains.last() {
+            strain.calculate_strains(previous);
+        }
+        strains.push(strain);
+    }
+
+    // Highest strain of every section
+    let mut strain_peaks = Vec::new();
+    let mut current_section_end = STRAIN_STEP;
+    let mut maximum_strain: f64 = 0.0;
+    let mut previous: Option<&StableStrain> = None;
+    for strain in &strains {
+        while strain.start_time > current_section_end {
+            strain_peaks.push(maximum_strain);
+            maximum_strain = match previous {
+                Some(prev) => {
+                    let elapsed = current_section_end - prev.start_time;
+                    apply_decay(prev.individual_strain(), elapsed, INDIVIDUAL_DECAY_BASE)
+                        + apply_decay(prev.overall_strain, elapsed, OVERALL_DECAY_BASE)
+                }
+                None => 0.0,
+            };
+            current_section_end += STRAIN_STEP;
+        }
+        maximum_strain = maximum_strain.max(strain.individual_strain() + strain.overall_strain);
+        previous = Some(strain);
+    }
+
+    let rating = weighted_peak_sum(&strain_peaks, DECAY_WEIGHT) * STAR_SCALING_FACTOR;
+    Ok(StrainStarRating { rating, strain_peaks })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithm::process::preprocess::preprocess_file;
+    use crate::types::Note;
+
+    fn map(column_count: usize, notes: Vec<Note>) -> MapData {
+        let mut map_data = MapData::new();
+        map_data.column_count = column_count;
+        map_data.notes = notes;
+        map_data
+    }
+
+    #[test]
+    fn strains_follow_the_decay_formula() {
+        // A jack in column 0, then a note in column 1 that closes the first section
+        let notes = vec![Note::simple(0, 100.0), Note::simple(0, 300.0), Note::simple(1, 500.0)];
+        let result = calculate_stable(&map(4, notes), 1.0).unwrap();
+
+        let individual = 2.0;
+        let overall = 0.3f64.powf(0.2) + 1.0;
+        // The section still open at the last note is never counted, as in stable
+        assert_eq!(result.strain_peaks.len(), 1);
+        assert!((result.strain_peaks[0] - (individual + overall)).abs() < 1e-12);
+        assert!((result.rating - (individual + overall) * STAR_SCALING_FACTOR).abs() < 1e-12);
+    }
+
+    #[test]
+    fn notes_under_a_held_long_note_get_the_hold_factor() {
+        // The first note only seeds the strains, so its hold is never registered
+        let notes = vec![Note::simple(3, 0.0), Note::long_note(0, 100.0, 1000.0), Note::simple(1, 300.0), Note::simple(2, 500.0)];
+        let result = calculate_stable(&map(4, notes), 1.0).unwrap();
+
+        let overall_at_long_note = 0.3f64.powf(0.1) + 1.0;
+        let individual = 2.0 * 1.25;
+        let overall = overall_at_long_note * 0.3f64.powf(0.2) + 1.25;
+        assert!((result.strain_peaks[0] - (individual + overall)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn empty_sections_start_from_the_decayed_strain() {
+        let notes = vec![Note::simple(0, 100.0), Note::simple(0, 300.0), Note::simple(1, 1300.0), Note::simple(1, 1700.0)];
+        let result = calculate_stable(&map(4, notes), 1.0).unwrap();
+
+        let (individual, overall) = (2.0, 0.3f64.powf(0.2) + 1.0);
+        let decayed = |end: f64| apply_decay(individual, end - 300.0, INDIVIDUAL_DECAY_BASE) + apply_decay(overall, end - 300.0, OVERALL_DECAY_BASE);
+        assert_eq!(result.strain_peaks.len(), 4);
+        assert!((result.strain_peaks[1] - decayed(400.0)).abs() < 1e-12);
+        assert!((result.strain_peaks[2] - decayed(800.0)).abs() < 1e-12);
+        assert!(result.strain_peaks[3] > decayed(1200.0));
+    }
+
+    #[test]
+    fn clock_rate_matches_a_map_preprocessed_with_the_mod() {
+        for (mod_name, clock_rate) in [("DT", 1.5), ("HT", 0.75)] {
+            let rated = calculate_stable(&preprocess_file("assets/test.osu", "None").unwrap(), clock_rate).unwrap();
+            let preprocessed = calculate_stable(&preprocess_file("assets/test.osu", mod_name).unwrap(), 1.0).unwrap();
+            assert!((rated.rating - preprocessed.rating).abs() < 1e-9, "{}: {} != {}", mod_name, rated.rating, preprocessed.rating);
+        }
+    }
+
+    #[test]
+    fn notes_outside_the_columns_are_ignored() {
+        let inside = vec![Note::simple(0, 100.0), Note::simple(1, 300.0), Note::simple(0, 900.0)];
+        let mut outside = inside.clone();
+        outside.insert(1, Note::simple(7, 200.0));
+        let expected = calculate_stable(&map(4, inside), 1.0).unwrap();
+        let actual = calculate_stable(&map(4, outside), 1.0).unwrap();
+        assert_eq!(expected.rating.to_bits(), actual.rating.to_bits());
+    }
+}
@@ -1,11 +1,34 @@
+use crate::algorithm::simd::weighted_steps;
+
 
 #[inline]
 pub fn cumulative_sum(x: &[f64], f: &[f64]) -> Vec<f64> {
     let mut f_cumsum = vec![0.0; x.len()];
+    cumulative_sum_into(x, f, &mut f_cumsum);
+    f_cumsum
+}
+
+/// Writes the cumulative sum into `f_cumsum` (same length as `x`, first value left as is)
+///
+/// The step products are computed by a vectorized kernel, then accumulated in order,
+/// which gives the same values as `cumulative_sum_into_scalar`.
+#[inline]
+pub fn cumulative_sum_into(x: &[f64], f: &[f64], f_cumsum: &mut [f64]) {
+    if x.len() < 2 {
+        return;
+    }
+    weighted_steps(x, f, &mut f_cumsum[1..]);
+    for i in 1..x.len() {
+        f_cumsum[i] += f_cumsum[i - 1];
+    }
+}
+
+/// Scalar version of `cumulative_sum_into`
+#[cfg(test)]
+pub(crate) fn cumulative_sum_into_scalar(x: &[f64], f: &[f64], f_cumsum: &mut [f64]) {
     for i in 1..x.len() {
         f_cumsum[i] = f_cumsum[i - 1] + f[i - 1] * (x[i] - x[i - 1]);
     }
-    f_cumsum
 }
 
 #[inline]
@@ -22,3 +45,81 @@ pub fn query_cumsum(q: f64, x: &[f64], f_cumsum: &[f64], f: &[f64]) -> f64 {
     f_cumsum[idx] + f[idx] * (q - x[idx])
 }
 
+
+/// Evaluates `query_cumsum` for nondecreasing queries, walking `x` once instead of bisecting
+///
+/// Queries must not decrease between calls; each one returns the same value as `query_cumsum`.
+#[derive(Debug, Clone)]
+pub struct CumsumCursor<'a> {
+    x: &'a [f64],
+    f_cumsum: &'a [f64],
+    f: &'a [f64],
+    idx: usize,
+}
+
+impl<'a> CumsumCursor<'a> {
+    /// Creates a cursor at the start of `x`
+    pub fn new(x: &'a [f64], f_cumsum: &'a [f64], f: &'a [f64]) -> Self {
+        Self { x, f_cumsum, f, idx: 0 }
+    }
+
+    /// Cumulative sum at `q`
+    #[inline]
+    pub fn query(&mut self, q: f64) -> f64 {
+        let x = self.x;
+        if q <= x[0] {
+            return 0.0;
+        }
+        if q >= *x.last().expect("Vecteur non vide attendu") {
+            return *self.f_cumsum.last().expect("Vecteur non vide attendu");
+        }
+        // last index with x[idx] <= q, as bisect_right - 1
+        while x[self.idx + 1] <= q {
+            self.idx += 1;
+        }
+        self.f_cumsum[self.idx] + self.f[self.idx] * (q - x[self.idx])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{assert_bits_eq, TestRng};
+
+    #[test]
+    fn cumulative_sum_matches_scalar() {
+        let mut rng = TestRng::new(11);
+        for len in [0, 1, 2, 5, 64, 1001] {
+            let x = rng.sorted_points(len);
+            let f = rng.values(len, 40.0);
+            let mut fast = vec![0.0; len];
+            let mut scalar = vec![0.0; len];
+            cumulative_sum_into(&x, &f, &mut fast);
+            cumulative_sum_into_scalar(&x, &f, &mut scalar);
+            assert_bits_eq("cumulative_sum", &fast, &scalar);
+        }
+    }
+
+    #[test]
+    fn cursor_matches_query_cumsum() {
+        let mut rng = TestRng::new(12);
+        let x = rng.sorted_points(500);
+        let f = rng.values(x.len(), 40.0);
+        let f_cumsum = cumulative_sum(&x, &f);
+        let (first, last) = (x[0], x[x.len() - 1]);
+
+        // random queries, sorted, reaching past both ends
+        let mut queries: Vec<f64> = (0..2000).map(|_| first - 100.0 + rng.next_f64() * (last - first + 200.0)).collect();
+        queries.sort_by(f64::total_cmp);
+        // every sample point, duplicates included, then the range ends
+        queries.extend_from_slice(&x);
+        queries.sort_by(f64::total_cmp);
+        queries.extend([last, last + 1.0]);
+        queries.insert(0, first - 1.0);
+
+        let mut cursor = CumsumCursor::new(&x, &f_cumsum, &f);
+        let fast: Vec<f64> = queries.iter().map(|&q| cursor.query(q)).collect();
+        let reference: Vec<f64> = queries.iter().map(|&q| query_cumsum(q, &x, &f_cumsum, &f)).collect();
+        assert_bits_eq("CumsumCursor", &fast, &reference);
+    }
+}
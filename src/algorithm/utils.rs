@@ -1,10 +1,10 @@
 use crate::types::Note;
 
 pub fn find_next_note_in_column(
-    note: (usize, i64, i64),
-    times: &[i64],
+    note: (usize, f64, f64),
+    times: &[f64],
     notes_by_column: &[Vec<Note>]
-) -> (usize, i64, i64) {
+) -> (usize, f64, f64) {
     let (k, h, _t) = note;
     // bisect_left
     let idx = times.partition_point(|&val| val < h);
@@ -12,6 +12,6 @@ pub fn find_next_note_in_column(
         let next_note = &notes_by_column[k][idx + 1];
         (next_note.column, next_note.hit_time, next_note.tail_time)
     } else {
-        (0, 1_000_000_000, 1_000_000_000)
+        (0, 1_000_000_000.0, 1_000_000_000.0)
     }
 }
\ No newline at end of file
@@ -1,8 +1,79 @@
-use crate::types::{Note, MapData, StarRatingResult};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use crate::types::{
+    BeatmapMetadata, Location, Note, MapData, OsuSection, ParseError, ParseMode, ParseOptions, ParseWarning, StarRatingError,
+    StarRatingResult,
+};
 
-use super::hit_objects::parse_hit_object_line;
-use super::metadata::{read_column_count, read_overall_difficulty, read_metadata};
-use super::reader::{read_file_lines};
+use super::fields::FieldReader;
+use super::hit_objects::{is_hold, parse_hit_object_line};
+use super::metadata::{clamped_column_count, column_count_from_circle_size, read_metadata};
+use super::reader::{read_line_lossy, DecodingReader};
+
+/// Section of the .osu file the parser is in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Section {
+    General,
+    Metadata,
+    Difficulty,
+    HitObjects,
+    Other,
+}
+
+impl Section {
+    fn from_header(header: &str) -> Self {
+        match header {
+            "[General]" => Section::General,
+            "[Metadata]" => Section::Metadata,
+            "[Difficulty]" => Section::Difficulty,
+            "[HitObjects]" => Section::HitObjects,
+            _ => Section::Other,
+        }
+    }
+}
+
+/// Position of the parser in the content, and the required sections met so far
+#[derive(Debug)]
+struct ParseState {
+    section: Section,
+    line_number: usize,
+    has_difficulty: bool,
+    has_hit_objects: bool,
+    /// Line of the [Difficulty] header, where a missing value is reported
+    difficulty_line: usize,
+    has_overall_difficulty: bool,
+    /// [General] Mode was read, or [General] ended without it
+    has_mode: bool,
+}
+
+impl ParseState {
+    fn new() -> Self {
+        Self {
+            section: Section::Other,
+            line_number: 0,
+            has_difficulty: false,
+            has_hit_objects: false,
+            difficulty_line: 0,
+            has_overall_difficulty: false,
+            has_mode: false,
+        }
+    }
+
+    /// Fails with `MissingSection` if [Difficulty] or [HitObjects] never appeared,
+    /// and if no key count was read
+    fn finish(&self, column_count: i32) -> StarRatingResult<()> {
+        if !self.has_difficulty {
+            return Err(ParseError::MissingSection("[Difficulty]".to_string()).into());
+        }
+        if !self.has_hit_objects {
+            return Err(ParseError::MissingSection("[HitObjects]".to_string()).into());
+        }
+        if column_count < 1 {
+            return Err(ParseError::InsufficientData("[Difficulty] has no usable CircleSize".to_string()).into());
+        }
+        Ok(())
+    }
+}
 
 /// Parser that processes .osu content into intermediate buffers, then builds MapData
 pub struct Parser {
@@ -10,9 +81,13 @@ pub struct Parser {
     od: f64,
     column_count: i32,
     columns: Vec<i32>,
-    note_starts: Vec<i32>,
-    note_ends: Vec<i32>,
+    note_starts: Vec<f64>,
+    note_ends: Vec<f64>,
     note_types: Vec<i32>,
+    metadata: BeatmapMetadata,
+    sections: Vec<OsuSection>,
+    options: ParseOptions,
+    warnings: Vec<ParseWarning>,
 }
 
 impl Parser {
@@ -25,50 +100,173 @@ impl Parser {
             note_starts: Vec::new(),
             note_ends: Vec::new(),
             note_types: Vec::new(),
+            metadata: BeatmapMetadata::default(),
+            sections: Vec::new(),
+            options: ParseOptions::default(),
+            warnings: Vec::new(),
         }
     }
 
+    /// Sets the parsing options (lenient by default)
+    pub fn with_options(mut self, options: ParseOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Malformed values replaced so far (lenient mode)
+    pub fn warnings(&self) -> &[ParseWarning] {
+        &self.warnings
+    }
+
+    /// [General] Mode and [Metadata] fields read so far
+    pub fn metadata(&self) -> &BeatmapMetadata {
+        &self.metadata
+    }
+
     /// Read file from disk and process
     pub fn process(&mut self) -> StarRatingResult<()> {
-        let lines = read_file_lines(&self.file_path)?;
-        self.process_lines(&lines)
+        let file = File::open(&self.file_path).map_err(|e| StarRatingError::io(&self.file_path, e))?;
+        self.process_reader(BufReader::new(file))
     }
 
     /// Parse raw .osu content provided as &str
     pub fn process_content(&mut self, content: &str) -> StarRatingResult<()> {
-        let lines: Vec<String> = content.lines().map(|s| s.to_string()).collect();
-        self.process_lines(&lines)
+        let mut state = ParseState::new();
+        let content = content.strip_prefix('\u{FEFF}').unwrap_or(content);
+        for line in content.lines() {
+            self.process_line(line, &mut state)?;
+            if self.skips_rest(&state) {
+                return Ok(());
+            }
+        }
+        self.finish(&state)
     }
 
-    /// Common factor: apply parsing on a list of lines
-    fn process_lines(&mut self, lines: &[String]) -> StarRatingResult<()> {
-        let mut hit_objects_started = false;
-
-        for line in lines {
-            // Metadata
-            read_metadata(line);
+    /// Parse .osu content from any buffered reader (file, stdin, in-memory bytes)
+    ///
+    /// # Arguments
+    /// * `reader` - Source of the .osu content, e.g. `io::stdin().lock()` or `&bytes[..]`
+    ///
+    /// # Returns
+    /// Parser holding the parsed buffers
+    pub fn from_reader<R: BufRead>(reader: R) -> StarRatingResult<Self> {
+        let mut parser = Self::new("");
+        parser.process_reader(reader)?;
+        Ok(parser)
+    }
 
-            // Column count
-            let temp_cc = read_column_count(line);
-            if temp_cc != -1 {
-                self.column_count = temp_cc;
+    /// Parse .osu content from a buffered reader, one line at a time in a reused buffer
+    ///
+    /// UTF-8 (with or without BOM) and UTF-16 LE/BE are accepted; invalid UTF-8 bytes
+    /// are replaced by U+FFFD.
+    pub fn process_reader<R: BufRead>(&mut self, reader: R) -> StarRatingResult<()> {
+        let mut state = ParseState::new();
+        let file_path = self.file_path.clone();
+        let read_error = |e: io::Error, line_number: usize| match file_path.as_str() {
+            "" => StarRatingError::io(format_args!("line {}", line_number), e),
+            path => StarRatingError::io(format_args!("{}, line {}", path, line_number), e),
+        };
+        let mut reader = DecodingReader::new(reader).map_err(|e| read_error(e, 1))?;
+        let mut buffer = Vec::new();
+        while let Some(line) = read_line_lossy(&mut reader, &mut buffer).map_err(|e| read_error(e, state.line_number + 1))? {
+            self.process_line(&line, &mut state)?;
+            if self.skips_rest(&state) {
+                return Ok(());
             }
+        }
+        self.finish(&state)
+    }
 
-            // Overall difficulty
-            let temp_od = read_overall_difficulty(line);
-            if temp_od != -1.0 {
-                self.od = temp_od;
+    /// True once a `mania_only` parse knows the map is of another game mode
+    fn skips_rest(&self, state: &ParseState) -> bool {
+        self.options.mania_only && state.has_mode && !self.metadata.is_mania()
+    }
+
+    /// Checks the sections and values required once the content is read
+    ///
+    /// A missing OverallDifficulty is an error in strict mode; in lenient mode it is
+    /// left at -1, as a malformed one, and a `ParseWarning` is recorded.
+    fn finish(&mut self, state: &ParseState) -> StarRatingResult<()> {
+        state.finish(self.column_count)?;
+        if state.has_overall_difficulty {
+            return Ok(());
+        }
+        let location = Location::new(state.difficulty_line, 1);
+        match self.options.mode {
+            ParseMode::Strict => Err(ParseError::InsufficientData("[Difficulty] has no OverallDifficulty".to_string())
+                .at(location)
+                .into()),
+            ParseMode::Lenient => {
+                self.warnings.push(ParseWarning {
+                    location,
+                    field: "OverallDifficulty".to_string(),
+                    value: String::new(),
+                    fallback: self.od.to_string(),
+                });
+                Ok(())
             }
+        }
+    }
 
-            // Start of [HitObjects]
-            if line.contains("[HitObjects]") {
-                hit_objects_started = true;
-                continue;
+    /// Apply the next line of the content
+    fn process_line(&mut self, line: &str, state: &mut ParseState) -> StarRatingResult<()> {
+        state.line_number += 1;
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') && trimmed.ends_with(']') {
+            state.has_mode |= state.section == Section::General;
+            state.section = Section::from_header(trimmed);
+            if state.section == Section::Difficulty && !state.has_difficulty {
+                state.has_difficulty = true;
+                state.difficulty_line = state.line_number;
+            }
+            state.has_hit_objects |= state.section == Section::HitObjects;
+            if self.options.keep_sections {
+                self.sections.push(OsuSection { header: trimmed.to_string(), lines: Vec::new() });
+            }
+            return Ok(());
+        }
+        if self.options.keep_sections && state.section != Section::HitObjects {
+            if self.sections.is_empty() {
+                self.sections.push(OsuSection::default());
+            }
+            if let Some(section) = self.sections.last_mut() {
+                section.lines.push(line.to_string());
             }
+        }
 
-            if hit_objects_started && !line.trim().is_empty() {
+        let mut fields = FieldReader::new(line, state.line_number, &self.options, &mut self.warnings);
+        match state.section {
+            Section::General | Section::Metadata => {
+                read_metadata(line, &mut self.metadata);
+                state.has_mode |= state.section == Section::General
+                    && line.split_once(':').is_some_and(|(key, _)| key.trim() == "Mode");
+            }
+            Section::Difficulty => {
+                let Some((key, value)) = line.split_once(':') else { return Ok(()) };
+                match key.trim() {
+                    // An unsupported key count is an error in strict mode. Lenient mode
+                    // warns and clamps it into 1..=MAX_KEY_COUNT; a CircleSize that is not
+                    // a number leaves the map without a key count unless another follows
+                    "CircleSize" => {
+                        let circle_size = value.trim().parse::<f64>().unwrap_or(f64::NAN);
+                        self.column_count = match column_count_from_circle_size(circle_size) {
+                            Some(column_count) => column_count,
+                            None if circle_size.is_finite() => {
+                                fields.invalid("CircleSize", value, clamped_column_count(circle_size))?
+                            }
+                            None => fields.invalid("CircleSize", value, self.column_count)?,
+                        };
+                    }
+                    "OverallDifficulty" => {
+                        state.has_overall_difficulty = true;
+                        self.od = fields.number("OverallDifficulty", value, -1.0)?;
+                    }
+                    _ => {}
+                }
+            }
+            Section::HitObjects if !trimmed.is_empty() => {
                 parse_hit_object_line(
-                    line,
+                    &mut fields,
                     self.column_count,
                     &mut self.columns,
                     &mut self.note_starts,
@@ -76,12 +274,13 @@ impl Parser {
                     &mut self.note_types,
                 )?;
             }
+            _ => {}
         }
 
         Ok(())
     }
 
-    pub fn get_parsed_data(&self) -> (i32, Vec<i32>, Vec<i32>, Vec<i32>, Vec<i32>, f64) {
+    pub fn get_parsed_data(&self) -> (i32, Vec<i32>, Vec<f64>, Vec<f64>, Vec<i32>, f64) {
         (
             self.column_count,
             self.columns.clone(),
@@ -93,19 +292,32 @@ impl Parser {
     }
 
     /// Build MapData from parsed buffers
+    ///
+    /// The sections kept with `ParseOptions::keep_sections` are cloned into the map;
+    /// `into_map_data` moves them instead.
     pub fn get_map_data(&self) -> StarRatingResult<MapData> {
+        self.build_map_data(self.sections.clone())
+    }
+
+    /// Build MapData from parsed buffers, moving the kept sections into the map
+    pub fn into_map_data(mut self) -> StarRatingResult<MapData> {
+        let sections = std::mem::take(&mut self.sections);
+        self.build_map_data(sections)
+    }
+
+    fn build_map_data(&self, source_sections: Vec<OsuSection>) -> StarRatingResult<MapData> {
         let mut notes = Vec::with_capacity(self.columns.len());
 
         for i in 0..self.columns.len() {
             let column = self.columns[i] as usize;
-            let hit_time = self.note_starts[i] as i64;
-            let tail_time = if self.note_types[i] == 128 { 
-                self.note_ends[i] as i64 
+            let hit_time = self.note_starts[i];
+            let tail_time = if is_hold(self.note_types[i]) { 
+                self.note_ends[i]
             } else { 
-                -1 
+                -1.0
             };
 
-            let note = if tail_time >= 0 {
+            let note = if tail_time >= 0.0 {
                 Note::long_note(column, hit_time, tail_time)
             } else {
                 Note::simple(column, hit_time)
@@ -114,7 +326,7 @@ impl Parser {
             notes.push(note);
         }
 
-        notes.sort_by(|a, b| match a.hit_time.cmp(&b.hit_time) {
+        notes.sort_by(|a, b| match a.hit_time.total_cmp(&b.hit_time) {
             std::cmp::Ordering::Equal => a.column.cmp(&b.column),
             other => other,
         });
@@ -136,7 +348,7 @@ impl Parser {
             .collect();
 
         let mut tail_sequence = long_notes.clone();
-        tail_sequence.sort_by(|a, b| a.tail_time.cmp(&b.tail_time));
+        tail_sequence.sort_by(|a, b| a.tail_time.total_cmp(&b.tail_time));
 
         let mut long_notes_by_column: Vec<Vec<Note>> = {
             let mut v = Vec::with_capacity(self.column_count as usize);
@@ -151,8 +363,8 @@ impl Parser {
 
         let total_duration = notes.iter()
             .map(|note| note.hit_time.max(note.tail_time))
-            .max()
-            .unwrap_or(0) + 1;
+            .reduce(f64::max)
+            .unwrap_or(0.0) + 1.0;
 
         Ok(MapData {
             hit_leniency: 0.0,
@@ -164,8 +376,156 @@ impl Parser {
             tail_sequence,
             long_notes_by_column,
             overall_difficulty: self.od,
+            warnings: self.warnings.clone(),
+            time_precision: self.options.time_precision,
+            clock_rate: 1.0,
+            source_sections,
         })
     }
 }
 
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WITHOUT_OD: &str = "osu file format v14\n\n[General]\nMode: 3\n\n[Difficulty]\nHPDrainRate:5\nCircleSize:4\n\n[HitObjects]\n64,192,1000,1,0,0:0:0:0:\n192,192,1500,1,0,0:0:0:0:\n";
+
+    fn with_circle_size(circle_size: &str) -> String {
+        WITHOUT_OD.replace("CircleSize:4", &format!("CircleSize:{}\nOverallDifficulty:8", circle_size))
+    }
+
+    #[test]
+    fn unsupported_key_counts_are_errors_in_strict_mode() {
+        for circle_size in ["0", "0.4", "-4", "18", "100", "NaN", "four"] {
+            let mut strict = Parser::new("").with_options(ParseOptions::strict());
+            let error = strict.process_content(&with_circle_size(circle_size)).unwrap_err();
+            assert!(error.to_string().contains("CircleSize"), "{}: {}", circle_size, error);
+        }
+    }
+
+    #[test]
+    fn unsupported_key_counts_are_clamped_in_lenient_mode() {
+        for (circle_size, column_count) in [("0", 1), ("0.4", 1), ("-4", 1), ("18", 10), ("100", 10)] {
+            let mut lenient = Parser::new("");
+            lenient.process_content(&with_circle_size(circle_size)).unwrap();
+            let [warning] = lenient.warnings() else { panic!("{}: expected one warning", circle_size) };
+            assert_eq!((warning.field.as_str(), warning.value.as_str()), ("CircleSize", circle_size));
+            assert_eq!(warning.fallback, column_count.to_string());
+            assert_eq!(lenient.get_map_data().unwrap().column_count, column_count);
+        }
+    }
+
+    #[test]
+    fn unreadable_key_counts_leave_no_key_count_in_lenient_mode() {
+        for circle_size in ["NaN", "four", ""] {
+            let mut lenient = Parser::new("");
+            let error = lenient.process_content(&with_circle_size(circle_size)).unwrap_err();
+            assert!(matches!(error, StarRatingError::ParseError(ParseError::InsufficientData(_))), "{}: {}", circle_size, error);
+            let [warning] = lenient.warnings() else { panic!("{}: expected one warning", circle_size) };
+            assert_eq!(warning.field, "CircleSize");
+        }
+    }
+
+    #[test]
+    fn supported_key_counts_are_read() {
+        for (circle_size, column_count) in [("1", 1), ("4", 4), ("7.4", 7), ("10", 10)] {
+            let mut parser = Parser::new("").with_options(ParseOptions::strict());
+            parser.process_content(&with_circle_size(circle_size)).unwrap();
+            assert_eq!(parser.get_map_data().unwrap().column_count, column_count);
+        }
+    }
+
+    #[test]
+    fn missing_overall_difficulty_is_an_error_in_strict_mode() {
+        let mut parser = Parser::new("").with_options(ParseOptions::strict());
+        let error = parser.process_content(WITHOUT_OD).unwrap_err();
+        assert!(error.to_string().contains("OverallDifficulty"), "{}", error);
+    }
+
+    #[test]
+    fn missing_overall_difficulty_is_a_warning_in_lenient_mode() {
+        let mut parser = Parser::new("");
+        parser.process_content(WITHOUT_OD).unwrap();
+        let [warning] = parser.warnings() else { panic!("expected one warning, got {:?}", parser.warnings()) };
+        assert_eq!(warning.field, "OverallDifficulty");
+        assert_eq!(warning.location, Location::new(6, 1));
+        assert_eq!(parser.get_map_data().unwrap().overall_difficulty, -1.0);
+    }
+
+    #[test]
+    fn map_data_can_be_built_more_than_once() {
+        let mut parser = Parser::new("");
+        parser.process_content(&with_circle_size("4")).unwrap();
+        let first = parser.get_map_data().unwrap();
+        assert!(first.source_sections.iter().any(|section| section.header == "[Difficulty]"));
+        assert_eq!(parser.get_map_data().unwrap().source_sections, first.source_sections);
+        assert_eq!(parser.into_map_data().unwrap().source_sections, first.source_sections);
+    }
+
+    #[test]
+    fn missing_sections_are_named() {
+        for (section, content) in [
+            ("[Difficulty]", WITHOUT_OD.replace("[Difficulty]", "[Unknown]")),
+            ("[HitObjects]", WITHOUT_OD.replace("[HitObjects]", "[Unknown]")),
+        ] {
+            let error = Parser::new("").process_content(&content).unwrap_err();
+            match error {
+                StarRatingError::ParseError(ParseError::MissingSection(missing)) => assert_eq!(missing, section),
+                other => panic!("expected MissingSection({}), got {:?}", section, other),
+            }
+        }
+    }
+
+    #[test]
+    fn errors_carry_their_line() {
+        let content = with_circle_size("4").replace("192,192,1500", "192,192,abc");
+        let mut parser = Parser::new("").with_options(ParseOptions::strict());
+        let StarRatingError::ParseError(error) = parser.process_content(&content).unwrap_err() else {
+            panic!("expected a parse error");
+        };
+        assert_eq!(error.location(), Some(Location::new(13, 9)));
+        assert!(matches!(error.kind(), ParseError::InvalidValue(field, value) if field == "time" && value == "abc"));
+    }
+
+    /// Reader failing once its content is used up
+    struct FailingReader(&'static [u8]);
+
+    impl io::Read for FailingReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.0.is_empty() {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "corrupt stream"));
+            }
+            let count = buf.len().min(self.0.len());
+            buf[..count].copy_from_slice(&self.0[..count]);
+            self.0 = &self.0[count..];
+            Ok(count)
+        }
+    }
+
+    #[test]
+    fn read_errors_stay_io_errors_with_their_line() {
+        let reader = BufReader::new(FailingReader(b"osu file format v14\n\n[General]\n"));
+        let error = Parser::new("map.osu").process_reader(reader).unwrap_err();
+        match error {
+            StarRatingError::FileError(e) => {
+                assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+                assert_eq!(e.to_string(), "map.osu, line 4: corrupt stream");
+            }
+            other => panic!("expected a FileError, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn missing_files_stay_io_errors_with_their_path() {
+        let error = Parser::new("assets/missing.osu").process().unwrap_err();
+        match error {
+            StarRatingError::FileError(e) => {
+                assert_eq!(e.kind(), io::ErrorKind::NotFound);
+                assert!(e.to_string().starts_with("assets/missing.osu: "), "{}", e);
+            }
+            other => panic!("expected a FileError, got {:?}", other),
+        }
+    }
+}
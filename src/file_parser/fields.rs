@@ -0,0 +1,87 @@
+use crate::types::{Location, ParseError, ParseMode, ParseOptions, ParseWarning, StarRatingResult, TimePrecision};
+
+/// Reads the values of one line, applying the parse mode to malformed ones
+///
+/// In strict mode a malformed value is an `InvalidValue` error; in lenient mode it is
+/// replaced by the given fallback and a `ParseWarning` is recorded.
+pub struct FieldReader<'a> {
+    line: &'a str,
+    line_number: usize,
+    mode: ParseMode,
+    time_precision: TimePrecision,
+    warnings: &'a mut Vec<ParseWarning>,
+}
+
+impl<'a> FieldReader<'a> {
+    pub fn new(line: &'a str, line_number: usize, options: &ParseOptions, warnings: &'a mut Vec<ParseWarning>) -> Self {
+        Self { line, line_number, mode: options.mode, time_precision: options.time_precision, warnings }
+    }
+
+    /// The line being read
+    pub fn line(&self) -> &'a str {
+        self.line
+    }
+
+    /// Location of `value`, which must be a slice of the line (start of the line otherwise)
+    pub fn location_of(&self, value: &str) -> Location {
+        let start = self.line.as_ptr() as usize;
+        let offset = (value.as_ptr() as usize).wrapping_sub(start);
+        let column = if offset <= self.line.len() && self.line.is_char_boundary(offset) {
+            self.line[..offset].chars().count() + 1
+        } else {
+            1
+        };
+        Location::new(self.line_number, column)
+    }
+
+    /// Location right after the end of the line
+    pub fn end_location(&self) -> Location {
+        Location::new(self.line_number, self.line.chars().count() + 1)
+    }
+
+    /// Reads a finite number
+    ///
+    /// # Arguments
+    /// * `field` - Name of the field, reported in errors and warnings
+    /// * `value` - Slice of the line holding the value
+    /// * `fallback` - Value used in lenient mode when `value` is malformed
+    ///
+    /// # Returns
+    /// The parsed value, or `fallback` in lenient mode
+    pub fn number(&mut self, field: &str, value: &str, fallback: f64) -> StarRatingResult<f64> {
+        match value.trim().parse::<f64>() {
+            Ok(number) if number.is_finite() => Ok(number),
+            _ => self.invalid(field, value, fallback),
+        }
+    }
+
+    /// Reads a number truncated to an integer (e.g. positions)
+    pub fn int(&mut self, field: &str, value: &str, fallback: i32) -> StarRatingResult<i32> {
+        Ok(self.number(field, value, fallback as f64)? as i32)
+    }
+
+    /// Reads a time in milliseconds, at the precision of the options
+    pub fn time(&mut self, field: &str, value: &str, fallback: f64) -> StarRatingResult<f64> {
+        Ok(self.time_precision.parsed(self.number(field, value, fallback)?))
+    }
+
+    /// Handles a malformed value according to the mode
+    ///
+    /// # Returns
+    /// An `InvalidValue` error in strict mode, `fallback` in lenient mode
+    pub fn invalid<T: ToString>(&mut self, field: &str, value: &str, fallback: T) -> StarRatingResult<T> {
+        let location = self.location_of(value);
+        match self.mode {
+            ParseMode::Strict => Err(ParseError::InvalidValue(field.to_string(), value.to_string()).at(location).into()),
+            ParseMode::Lenient => {
+                self.warnings.push(ParseWarning {
+                    location,
+                    field: field.to_string(),
+                    value: value.to_string(),
+                    fallback: fallback.to_string(),
+                });
+                Ok(fallback)
+            }
+        }
+    }
+}
@@ -1,41 +1,87 @@
 use crate::types::{StarRatingResult, ParseError};
+use super::fields::FieldReader;
 
-fn string_to_int(s: &str) -> i32 {
-    s.parse::<f64>().unwrap_or(0.0) as i32
+/// Hit circle bit of the `type` field
+pub const TYPE_CIRCLE: i32 = 1;
+/// Slider bit of the `type` field
+pub const TYPE_SLIDER: i32 = 1 << 1;
+/// New combo bit of the `type` field
+pub const TYPE_NEW_COMBO: i32 = 1 << 2;
+/// Spinner bit of the `type` field
+pub const TYPE_SPINNER: i32 = 1 << 3;
+/// Combo colour skip bits of the `type` field
+pub const TYPE_COMBO_SKIP: i32 = 0b111 << 4;
+/// osu!mania hold note bit of the `type` field
+pub const TYPE_HOLD: i32 = 1 << 7;
+
+/// Whether a `type` field describes a hold note, whatever the combo bits
+pub fn is_hold(note_type: i32) -> bool {
+    note_type & TYPE_HOLD != 0
+}
+
+/// Column of a hit object, as osu!mania maps it: floor(x * k / 512) clamped to [0, k-1]
+///
+/// # Arguments
+/// * `x` - Horizontal position of the object
+/// * `column_count` - Number of columns (k)
+///
+/// # Returns
+/// Column index
+pub fn column_from_x(x: i32, column_count: i32) -> i32 {
+    let column_count = column_count.max(1);
+    let column = (x as i64 * column_count as i64).div_euclid(512);
+    column.clamp(0, column_count as i64 - 1) as i32
 }
 
 /// Parse one [HitObjects] line and push into buffers
+///
+/// Malformed numbers are handled by `fields` according to the parse mode. Lines use
+/// the `x,y,time,type,hitSound[,extras]` layout of format versions 3 to 14: the
+/// extras field may be missing on plain notes, but holds need it for their end time.
 pub fn parse_hit_object_line(
-    object_line: &str,
+    fields: &mut FieldReader,
     column_count: i32,
     columns: &mut Vec<i32>,
-    note_starts: &mut Vec<i32>,
-    note_ends: &mut Vec<i32>,
+    note_starts: &mut Vec<f64>,
+    note_ends: &mut Vec<f64>,
     note_types: &mut Vec<i32>,
 ) -> StarRatingResult<()> {
-    let params: Vec<&str> = object_line.split(',').collect();
-    if params.len() < 6 {
+    // The fields are borrowed from the line
+    let object_line = fields.line();
+    let mut params = object_line.split(',');
+    let (Some(x), Some(_y), Some(time), Some(kind), Some(_hit_sound)) = (
+        params.next(), params.next(), params.next(), params.next(), params.next()
+    ) else {
+        // Points right after the last field present
         return Err(ParseError::InsufficientData(
             format!("Invalid hit object line: {}", object_line)
-        ).into());
+        ).at(fields.end_location()).into());
+    };
+    let extras = params.next();
+
+    let note_type = fields.int("type", kind, 0)?;
+    if is_hold(note_type) && extras.is_none() {
+        return Err(ParseError::InsufficientData(
+            format!("Hold note without end time: {}", object_line)
+        ).at(fields.end_location()).into());
     }
 
-    let x_pos = string_to_int(params[0]);
-    let column_width = 512 / column_count;
-    let column = x_pos / column_width;
-    columns.push(column);
+    let x_pos = fields.int("x", x, 0)?;
+    columns.push(column_from_x(x_pos, column_count));
 
-    let note_start = string_to_int(params[2]);
+    let note_start = fields.time("time", time, 0.0)?;
     note_starts.push(note_start);
 
-    let note_type = string_to_int(params[3]);
     note_types.push(note_type);
 
-    let last_param_chunk: Vec<&str> = params[5].split(':').collect();
-    let note_end = string_to_int(last_param_chunk[0]);
+    // Only hold notes use their end time
+    let end_time = extras.and_then(|extras| extras.split(':').next()).unwrap_or("");
+    let note_end = if is_hold(note_type) {
+        fields.time("endTime", end_time, 0.0)?
+    } else {
+        end_time.parse::<f64>().unwrap_or(0.0)
+    };
     note_ends.push(note_end);
 
     Ok(())
 }
-
-
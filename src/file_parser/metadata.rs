@@ -1,33 +1,34 @@
-fn string_to_int(s: &str) -> i32 {
-    s.parse::<f64>().unwrap_or(0.0) as i32
-}
+use crate::types::{BeatmapMetadata, MAX_KEY_COUNT};
 
-/// Currently a no-op placeholder to document metadata handling responsibility
-pub fn read_metadata(line: &str) {
-    if line.contains("[Metadata]") {
-        // Not used currently
+/// Reads a "Key: Value" line of [General] or [Metadata] into `metadata`
+///
+/// Unknown keys are ignored; a malformed Mode leaves the previous value.
+pub fn read_metadata(line: &str, metadata: &mut BeatmapMetadata) {
+    let Some((key, value)) = line.split_once(':') else { return };
+    let value = value.trim();
+    match key.trim() {
+        "Mode" => metadata.mode = value.parse().unwrap_or(metadata.mode),
+        "Title" => metadata.title = value.to_string(),
+        "Artist" => metadata.artist = value.to_string(),
+        "Creator" => metadata.creator = value.to_string(),
+        "Version" => metadata.version = value.to_string(),
+        _ => {}
     }
 }
 
-pub fn read_overall_difficulty(line: &str) -> f64 {
-    if line.contains("OverallDifficulty:") {
-        let temp = line.trim();
-        if let Some(pos) = temp.find(':') {
-            let od_str = &temp[pos + 1..];
-            return od_str.parse::<f64>().unwrap_or(-1.0);
-        }
-    }
-    -1.0
+/// Key count of an osu!mania map: CircleSize rounded to the nearest integer
+///
+/// # Returns
+/// The key count, or `None` when it is outside 1..=`MAX_KEY_COUNT`
+pub fn column_count_from_circle_size(circle_size: f64) -> Option<i32> {
+    let column_count = circle_size.round();
+    (1.0..=MAX_KEY_COUNT as f64).contains(&column_count).then_some(column_count as i32)
 }
 
-pub fn read_column_count(line: &str) -> i32 {
-    if line.contains("CircleSize:") {
-        let temp = line.trim();
-        let column_count = temp.chars().last().unwrap_or('0');
-        let column_count_str = if column_count == '0' { "10" } else { &column_count.to_string() };
-        return string_to_int(column_count_str);
-    }
-    -1
+/// Supported key count nearest to a finite CircleSize, used in lenient mode
+///
+/// # Returns
+/// CircleSize rounded and clamped to 1..=`MAX_KEY_COUNT`
+pub fn clamped_column_count(circle_size: f64) -> i32 {
+    circle_size.round().clamp(1.0, MAX_KEY_COUNT as f64) as i32
 }
-
-
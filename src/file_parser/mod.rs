@@ -2,6 +2,11 @@ pub mod reader;
 pub mod metadata;
 pub mod hit_objects;
 pub mod builder;
+pub mod fields;
+pub mod writer;
+pub mod stepmania;
 
 // Preserve public API
 pub use builder::Parser;
+pub use writer::{to_osu_string, write_osu, write_osu_file};
+pub use stepmania::{parse_stepmania, parse_stepmania_file};
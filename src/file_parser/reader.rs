@@ -1,15 +1,256 @@
 use std::fs::File;
-use std::io::{BufRead, BufReader};
-use crate::types::{StarRatingResult, ParseError};
+use std::io::{self, BufRead, BufReader, Read};
+use crate::types::{StarRatingError, StarRatingResult};
+
+/// Text encoding of a .osu file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextEncoding {
+    /// UTF-8, with or without BOM (invalid bytes are replaced when decoding lines)
+    Utf8,
+    /// UTF-16 little endian
+    Utf16Le,
+    /// UTF-16 big endian
+    Utf16Be,
+}
+
+/// Detects the encoding from the first bytes of a file
+///
+/// A BOM decides; without one, a zero byte in the first code unit next to an ASCII
+/// character ("o\0s\0u\0...") reveals BOM-less UTF-16.
+///
+/// # Arguments
+/// * `prefix` - First bytes of the content
+///
+/// # Returns
+/// Tuple (encoding, BOM length in bytes)
+pub fn detect_encoding(prefix: &[u8]) -> (TextEncoding, usize) {
+    match prefix {
+        [0xEF, 0xBB, 0xBF, ..] => (TextEncoding::Utf8, 3),
+        [0xFF, 0xFE, ..] => (TextEncoding::Utf16Le, 2),
+        [0xFE, 0xFF, ..] => (TextEncoding::Utf16Be, 2),
+        [low, 0, ..] if *low != 0 && low.is_ascii() => (TextEncoding::Utf16Le, 0),
+        [0, low, ..] if *low != 0 && low.is_ascii() => (TextEncoding::Utf16Be, 0),
+        _ => (TextEncoding::Utf8, 0),
+    }
+}
+
+/// Buffered reader yielding UTF-8 whatever the encoding of the underlying content
+///
+/// The BOM is skipped; UTF-8 content is passed through untouched, UTF-16 content is
+/// transcoded chunk by chunk (unpaired surrogates become U+FFFD).
+pub struct DecodingReader<R> {
+    inner: R,
+    encoding: TextEncoding,
+    /// Raw UTF-16 bytes not decoded yet (odd byte, high surrogate at a chunk end)
+    pending: Vec<u8>,
+    /// Transcoded UTF-8 bytes (UTF-8 content: sniffed bytes not read yet) and read
+    /// position in them
+    decoded: Vec<u8>,
+    position: usize,
+}
+
+impl<R: BufRead> DecodingReader<R> {
+    /// Sniffs the encoding of `inner` and skips its BOM
+    ///
+    /// A reader handing out fewer than 3 bytes at a time is read until the longest
+    /// BOM fits; the bytes read past the BOM are replayed before the rest.
+    pub fn new(mut inner: R) -> io::Result<Self> {
+        let mut prefix = Vec::new();
+        loop {
+            let available = inner.fill_buf()?;
+            if (prefix.is_empty() && available.len() >= 3) || available.is_empty() || prefix.len() >= 3 {
+                break;
+            }
+            let taken = available.len().min(3 - prefix.len());
+            prefix.extend_from_slice(&available[..taken]);
+            inner.consume(taken);
+        }
+
+        if prefix.is_empty() {
+            let (encoding, bom_len) = detect_encoding(inner.fill_buf()?);
+            inner.consume(bom_len);
+            return Ok(Self { inner, encoding, pending: Vec::new(), decoded: Vec::new(), position: 0 });
+        }
+        let (encoding, bom_len) = detect_encoding(&prefix);
+        prefix.drain(..bom_len);
+        let (pending, decoded) = match encoding {
+            TextEncoding::Utf8 => (Vec::new(), prefix),
+            TextEncoding::Utf16Le | TextEncoding::Utf16Be => (prefix, Vec::new()),
+        };
+        Ok(Self { inner, encoding, pending, decoded, position: 0 })
+    }
+
+    /// Encoding of the content
+    pub fn encoding(&self) -> TextEncoding {
+        self.encoding
+    }
+
+    /// Decodes the next chunk of UTF-16 into `decoded`; leaves it empty at the end
+    fn decode_next_chunk(&mut self) -> io::Result<()> {
+        self.decoded.clear();
+        self.position = 0;
+        while self.decoded.is_empty() {
+            let chunk = self.inner.fill_buf()?;
+            let at_end = chunk.is_empty();
+            self.pending.extend_from_slice(chunk);
+            let chunk_len = chunk.len();
+            self.inner.consume(chunk_len);
+
+            let mut units: Vec<u16> = self.pending
+                .chunks_exact(2)
+                .map(|pair| match self.encoding {
+                    TextEncoding::Utf16Be => u16::from_be_bytes([pair[0], pair[1]]),
+                    _ => u16::from_le_bytes([pair[0], pair[1]]),
+                })
+                .collect();
+            // A high surrogate may be completed by the next chunk
+            let keep_surrogate = !at_end && units.last().is_some_and(|unit| (0xD800..0xDC00).contains(unit));
+            if keep_surrogate {
+                units.pop();
+            }
+            let decoded_bytes = units.len() * 2;
+            let mut utf8 = [0u8; 4];
+            for c in char::decode_utf16(units).map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER)) {
+                self.decoded.extend_from_slice(c.encode_utf8(&mut utf8).as_bytes());
+            }
+            self.pending.drain(..decoded_bytes);
+
+            if at_end {
+                // A dangling odd byte cannot be decoded
+                if !self.pending.is_empty() {
+                    self.pending.clear();
+                    self.decoded.extend_from_slice(char::REPLACEMENT_CHARACTER.encode_utf8(&mut utf8).as_bytes());
+                }
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<R: BufRead> Read for DecodingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<R: BufRead> BufRead for DecodingReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.encoding == TextEncoding::Utf8 && self.position >= self.decoded.len() {
+            return self.inner.fill_buf();
+        }
+        if self.position >= self.decoded.len() {
+            self.decode_next_chunk()?;
+        }
+        Ok(&self.decoded[self.position..])
+    }
+
+    fn consume(&mut self, amount: usize) {
+        if self.encoding == TextEncoding::Utf8 && self.position >= self.decoded.len() {
+            self.inner.consume(amount);
+        } else {
+            self.position = (self.position + amount).min(self.decoded.len());
+        }
+    }
+}
+
+/// Reads the next line of `reader` into `buffer`, without its line ending
+///
+/// Invalid UTF-8 sequences are replaced by U+FFFD instead of failing the read.
+///
+/// # Returns
+/// The line, or None at the end of the content
+pub fn read_line_lossy<'a, R: BufRead>(reader: &mut R, buffer: &'a mut Vec<u8>) -> io::Result<Option<std::borrow::Cow<'a, str>>> {
+    buffer.clear();
+    if reader.read_until(b'\n', buffer)? == 0 {
+        return Ok(None);
+    }
+    let mut line: &[u8] = buffer;
+    if let Some(stripped) = line.strip_suffix(b"\n") {
+        line = stripped;
+    }
+    if let Some(stripped) = line.strip_suffix(b"\r") {
+        line = stripped;
+    }
+    Ok(Some(String::from_utf8_lossy(line)))
+}
 
 pub fn read_file_lines(path: &str) -> StarRatingResult<Vec<String>> {
-    let file = File::open(path)
-        .map_err(|e| ParseError::FileNotFound(format!("{}: {}", path, e)))?;
-    let reader = BufReader::new(file);
-    let lines: Vec<String> = reader.lines()
-        .collect::<Result<Vec<_>, _>>()
-        .map_err(|e| ParseError::InvalidLine(format!("Read error: {}", e)))?;
+    let file = File::open(path).map_err(|e| StarRatingError::io(path, e))?;
+    let mut reader = DecodingReader::new(BufReader::new(file)).map_err(|e| StarRatingError::io(path, e))?;
+    let mut lines = Vec::new();
+    let mut buffer = Vec::new();
+    while let Some(line) = read_line_lossy(&mut reader, &mut buffer)
+        .map_err(|e| StarRatingError::io(format_args!("{}, line {}", path, lines.len() + 1), e))?
+    {
+        lines.push(line.into_owned());
+    }
     Ok(lines)
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
 
+    const TEXT: &str = "osu file format v14\r\n[Metadata]\nTitle:Ünïcödé 🎵\n";
+
+    fn utf16(text: &str, bom: bool, to_bytes: fn(u16) -> [u8; 2]) -> Vec<u8> {
+        let bom = if bom { Some(0xFEFF) } else { None };
+        bom.into_iter().chain(text.encode_utf16()).flat_map(to_bytes).collect()
+    }
+
+    /// Encoding and lines of `bytes`, read through a buffer of `capacity` bytes
+    fn decode(bytes: &[u8], capacity: usize) -> (TextEncoding, Vec<String>) {
+        let mut reader = DecodingReader::new(BufReader::with_capacity(capacity, bytes)).unwrap();
+        let mut lines = Vec::new();
+        let mut buffer = Vec::new();
+        while let Some(line) = read_line_lossy(&mut reader, &mut buffer).unwrap() {
+            lines.push(line.into_owned());
+        }
+        (reader.encoding(), lines)
+    }
+
+    #[test]
+    fn encodings_are_detected_and_decoded() {
+        let expected: Vec<String> = TEXT.lines().map(str::to_string).collect();
+        let bom_utf8 = [&[0xEF, 0xBB, 0xBF][..], TEXT.as_bytes()].concat();
+        for (name, bytes, encoding) in [
+            ("UTF-8", TEXT.as_bytes().to_vec(), TextEncoding::Utf8),
+            ("UTF-8 BOM", bom_utf8, TextEncoding::Utf8),
+            ("UTF-16 LE BOM", utf16(TEXT, true, u16::to_le_bytes), TextEncoding::Utf16Le),
+            ("UTF-16 BE BOM", utf16(TEXT, true, u16::to_be_bytes), TextEncoding::Utf16Be),
+            ("UTF-16 LE", utf16(TEXT, false, u16::to_le_bytes), TextEncoding::Utf16Le),
+            ("UTF-16 BE", utf16(TEXT, false, u16::to_be_bytes), TextEncoding::Utf16Be),
+        ] {
+            // 1 byte at a time: the BOM and surrogate pairs arrive split across reads
+            for capacity in [1, 2, 3, 8192] {
+                assert_eq!(decode(&bytes, capacity), (encoding, expected.clone()), "{} by {}", name, capacity);
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_utf8_is_replaced() {
+        let bytes = b"Title:caf\xE9\nArtist:\xFF\xFEok\n";
+        for capacity in [1, 8192] {
+            let (encoding, lines) = decode(bytes, capacity);
+            // \xFF\xFE past the start is not a BOM
+            assert_eq!(encoding, TextEncoding::Utf8);
+            assert_eq!(lines, ["Title:caf\u{FFFD}", "Artist:\u{FFFD}\u{FFFD}ok"]);
+        }
+    }
+
+    #[test]
+    fn short_content_is_read_whole() {
+        for capacity in [1, 8192] {
+            assert_eq!(decode(b"", capacity), (TextEncoding::Utf8, vec![]));
+            assert_eq!(decode(b"a", capacity), (TextEncoding::Utf8, vec!["a".to_string()]));
+            assert_eq!(decode(&[0xEF, 0xBB, 0xBF], capacity), (TextEncoding::Utf8, vec![]));
+            assert_eq!(decode(&[0xFF, 0xFE, b'a'], capacity), (TextEncoding::Utf16Le, vec!["\u{FFFD}".to_string()]));
+        }
+    }
+}
@@ -0,0 +1,450 @@
+use std::fs::File;
+use std::io::{BufReader, Read};
+use crate::algorithm::process::normalize::{rebuild_groupings, recompute_hit_leniency, recompute_total_duration};
+use crate::types::{
+    Location, MapData, Note, ParseError, StarRatingError, StarRatingResult, StepmaniaChart, StepmaniaSimfile,
+    STEPMANIA_OVERALL_DIFFICULTY,
+};
+use super::reader::DecodingReader;
+
+/// Rows per beat of the StepMania timing grid, used to compare beats exactly
+const ROWS_PER_BEAT: f64 = 48.0;
+
+/// `#TAG:value;` entry of a simfile, with the line of its `#`
+struct Tag {
+    name: String,
+    value: String,
+    line: usize,
+}
+
+/// Offset, BPM changes, stops and delays of a song or of one .ssc chart
+#[derive(Debug, Clone, Default)]
+struct TimingData {
+    /// Seconds; beat 0 is heard at -offset
+    offset: f64,
+    /// (beat, BPM), sorted by beat
+    bpms: Vec<(f64, f64)>,
+    /// (row, seconds) pauses after the row is hit
+    stops: Vec<(i64, f64)>,
+    /// (row, seconds) pauses before the row is hit
+    delays: Vec<(i64, f64)>,
+}
+
+impl TimingData {
+    /// Time in milliseconds of a beat
+    fn time_at(&self, beat: f64) -> f64 {
+        let row = beat_row(beat);
+        let mut seconds = 0.0 - self.offset;
+        for (i, &(start, bpm)) in self.bpms.iter().enumerate() {
+            // the first BPM also covers the beats before its own start
+            let start = if i == 0 { 0.0 } else { start };
+            if beat <= start {
+                break;
+            }
+            let end = self.bpms.get(i + 1).map_or(beat, |&(next, _)| next.min(beat));
+            seconds += (end - start) * 60.0 / bpm;
+        }
+        seconds += self.stops.iter().filter(|&&(stop, _)| stop < row).map(|&(_, s)| s).sum::<f64>();
+        seconds += self.delays.iter().filter(|&&(delay, _)| delay <= row).map(|&(_, s)| s).sum::<f64>();
+        seconds * 1000.0
+    }
+}
+
+/// Chart header and note data, before timing is applied
+#[derive(Default)]
+struct ChartSource {
+    steps_type: String,
+    description: String,
+    difficulty: String,
+    meter: String,
+    notes: String,
+    timing: Option<TimingData>,
+}
+
+/// Parses a .sm or .ssc file from disk
+///
+/// # Arguments
+/// * `file_path` - Path to the simfile
+///
+/// # Returns
+/// The dance-single and dance-double charts of the file
+pub fn parse_stepmania_file(file_path: &str) -> StarRatingResult<StepmaniaSimfile> {
+    let file = File::open(file_path).map_err(|e| StarRatingError::io(file_path, e))?;
+    let mut bytes = Vec::new();
+    DecodingReader::new(BufReader::new(file))
+        .and_then(|mut reader| reader.read_to_end(&mut bytes))
+        .map_err(|e| StarRatingError::io(file_path, e))?;
+    parse_stepmania(&String::from_utf8_lossy(&bytes))
+}
+
+/// Parses .sm or .ssc content
+///
+/// Beats are timed from #OFFSET, #BPMS, #STOPS (or #FREEZES) and #DELAYS, with the
+/// .ssc per-chart timing taking precedence over the song timing. Taps and lifts
+/// become notes, holds and rolls become long notes; mines and fakes are returned
+/// apart and do not reach the `MapData`. Charts without any note are listed in
+/// `skipped` rather than returned.
+///
+/// # Arguments
+/// * `content` - Simfile content
+///
+/// # Returns
+/// The dance-single (4K) and dance-double (8K) charts of the simfile
+pub fn parse_stepmania(content: &str) -> StarRatingResult<StepmaniaSimfile> {
+    let content = content.strip_prefix('\u{FEFF}').unwrap_or(content);
+    let mut simfile = StepmaniaSimfile::default();
+    let mut song_timing = TimingData::default();
+    let mut sources: Vec<ChartSource> = Vec::new();
+    // .ssc charts open with #NOTEDATA; everything after it belongs to the chart
+    let mut in_ssc_chart = false;
+
+    for tag in read_tags(content) {
+        match tag.name.as_str() {
+            "NOTEDATA" => {
+                in_ssc_chart = true;
+                sources.push(ChartSource::default());
+                continue;
+            }
+            "NOTES" if !in_ssc_chart => {
+                sources.push(sm_chart_source(&tag));
+                continue;
+            }
+            _ => {}
+        }
+
+        let timing = match sources.last_mut().filter(|_| in_ssc_chart) {
+            Some(chart) => {
+                match tag.name.as_str() {
+                    "STEPSTYPE" => chart.steps_type = tag.value.trim().to_string(),
+                    "DESCRIPTION" | "CREDIT" if chart.description.is_empty() => {
+                        chart.description = tag.value.trim().to_string();
+                    }
+                    "DIFFICULTY" => chart.difficulty = tag.value.trim().to_string(),
+                    "METER" => chart.meter = tag.value.trim().to_string(),
+                    "NOTES" | "NOTES2" => {
+                        chart.notes = tag.value;
+                        continue;
+                    }
+                    _ => {}
+                }
+                chart.timing.get_or_insert_with(|| song_timing.clone())
+            }
+            None => &mut song_timing,
+        };
+
+        match tag.name.as_str() {
+            "TITLE" => simfile.title = tag.value.trim().to_string(),
+            "ARTIST" => simfile.artist = tag.value.trim().to_string(),
+            "OFFSET" => timing.offset = parse_number(&tag, tag.value.trim())?,
+            "BPMS" => {
+                timing.bpms = parse_pairs(&tag)?;
+                if let Some(&(_, bpm)) = timing.bpms.iter().find(|&&(_, bpm)| bpm <= 0.0) {
+                    return Err(ParseError::InvalidValue("BPMS".to_string(), bpm.to_string())
+                        .at(Location::new(tag.line, 1))
+                        .into());
+                }
+                timing.bpms.sort_by(|a, b| a.0.total_cmp(&b.0));
+            }
+            "STOPS" | "FREEZES" => timing.stops = to_rows(parse_pairs(&tag)?),
+            "DELAYS" => timing.delays = to_rows(parse_pairs(&tag)?),
+            _ => {}
+        }
+    }
+
+    if sources.is_empty() {
+        return Err(ParseError::MissingSection("#NOTES".to_string()).into());
+    }
+    for source in sources {
+        let column_count = match source.steps_type.as_str() {
+            "dance-single" => 4,
+            "dance-double" => 8,
+            _ => continue,
+        };
+        let timing = source.timing.as_ref().unwrap_or(&song_timing);
+        if timing.bpms.is_empty() {
+            return Err(ParseError::MissingSection("#BPMS".to_string()).into());
+        }
+        let chart = build_chart(&source, timing, column_count);
+        // nothing to rate: the rating needs at least one judged note
+        if chart.map_data.notes.is_empty() {
+            simfile.skipped.push(format!("{} {}", chart.steps_type, chart.difficulty));
+            continue;
+        }
+        simfile.charts.push(chart);
+    }
+    Ok(simfile)
+}
+
+/// Splits the content into `#TAG:value;` entries, dropping `//` comments
+///
+/// A tag missing its `;` ends at the next line starting with `#`, as StepMania does.
+fn read_tags(content: &str) -> Vec<Tag> {
+    let mut tags = Vec::new();
+    let mut current: Option<Tag> = None;
+    for (index, raw_line) in content.lines().enumerate() {
+        let line = raw_line.split_once("//").map_or(raw_line, |(code, _)| code);
+        if line.trim_start().starts_with('#') {
+            tags.extend(current.take());
+        }
+        let mut rest = line;
+        loop {
+            match current.as_mut() {
+                None => {
+                    let Some((_, after_hash)) = rest.split_once('#') else { break };
+                    let Some((name, value)) = after_hash.split_once(':') else { break };
+                    current = Some(Tag { name: name.trim().to_ascii_uppercase(), value: String::new(), line: index + 1 });
+                    rest = value;
+                }
+                Some(tag) => match rest.split_once(';') {
+                    Some((value, after)) => {
+                        tag.value.push_str(value);
+                        tags.extend(current.take());
+                        rest = after;
+                    }
+                    None => {
+                        tag.value.push_str(rest);
+                        tag.value.push('\n');
+                        break;
+                    }
+                },
+            }
+        }
+    }
+    tags.extend(current);
+    tags
+}
+
+/// .sm #NOTES value: "type:description:difficulty:meter:radar values:note data"
+fn sm_chart_source(tag: &Tag) -> ChartSource {
+    let fields: Vec<&str> = tag.value.splitn(6, ':').map(str::trim).collect();
+    let field = |index: usize| fields.get(index).copied().unwrap_or("").to_string();
+    ChartSource {
+        steps_type: field(0),
+        description: field(1),
+        difficulty: field(2),
+        meter: field(3),
+        notes: field(5),
+        timing: None,
+    }
+}
+
+fn parse_number(tag: &Tag, value: &str) -> StarRatingResult<f64> {
+    match value.trim().parse::<f64>() {
+        Ok(number) if number.is_finite() => Ok(number),
+        _ => Err(ParseError::InvalidValue(tag.name.clone(), value.to_string())
+            .at(Location::new(tag.line, 1))
+            .into()),
+    }
+}
+
+/// "beat=value,beat=value" list of a timing tag
+fn parse_pairs(tag: &Tag) -> StarRatingResult<Vec<(f64, f64)>> {
+    tag.value
+        .split(',')
+        .filter(|pair| !pair.trim().is_empty())
+        .map(|pair| {
+            let (beat, value) = pair.split_once('=').ok_or_else(|| {
+                ParseError::InvalidValue(tag.name.clone(), pair.trim().to_string()).at(Location::new(tag.line, 1))
+            })?;
+            Ok((parse_number(tag, beat)?, parse_number(tag, value)?))
+        })
+        .collect()
+}
+
+fn to_rows(pairs: Vec<(f64, f64)>) -> Vec<(i64, f64)> {
+    pairs.into_iter().map(|(beat, seconds)| (beat_row(beat), seconds)).collect()
+}
+
+fn beat_row(beat: f64) -> i64 {
+    (beat * ROWS_PER_BEAT).round() as i64
+}
+
+/// Times the note rows of a chart and builds its map
+fn build_chart(source: &ChartSource, timing: &TimingData, column_count: usize) -> StepmaniaChart {
+    let mut notes = Vec::new();
+    let mut mines = Vec::new();
+    let mut fakes = Vec::new();
+    let mut open_heads: Vec<Option<f64>> = vec![None; column_count];
+
+    for (measure_index, measure) in source.notes.split(',').enumerate() {
+        let rows: Vec<&str> = measure.lines().map(str::trim).filter(|row| !row.is_empty()).collect();
+        for (row_index, row) in rows.iter().enumerate() {
+            let beat = 4.0 * (measure_index as f64 + row_index as f64 / rows.len() as f64);
+            let time = timing.time_at(beat);
+            for (column, symbol) in row_symbols(row).take(column_count).enumerate() {
+                match symbol {
+                    '1' | 'L' => notes.push(Note::simple(column, time)),
+                    '2' | '4' => {
+                        // an unclosed hold is played as a tap
+                        if let Some(head) = open_heads[column].replace(time) {
+                            notes.push(Note::simple(column, head));
+                        }
+                    }
+                    '3' => {
+                        if let Some(head) = open_heads[column].take() {
+                            notes.push(Note::long_note(column, head, time));
+                        }
+                    }
+                    'M' => mines.push(Note::simple(column, time)),
+                    'F' => fakes.push(Note::simple(column, time)),
+                    _ => {}
+                }
+            }
+        }
+    }
+    for (column, head) in open_heads.into_iter().enumerate() {
+        if let Some(head) = head {
+            notes.push(Note::simple(column, head));
+        }
+    }
+
+    let mut map_data = MapData::new();
+    map_data.column_count = column_count;
+    map_data.overall_difficulty = STEPMANIA_OVERALL_DIFFICULTY;
+    map_data.notes = notes;
+    rebuild_groupings(&mut map_data);
+    recompute_hit_leniency(&mut map_data);
+    recompute_total_duration(&mut map_data);
+
+    StepmaniaChart {
+        steps_type: source.steps_type.clone(),
+        difficulty: source.difficulty.clone(),
+        meter: source.meter.parse().unwrap_or(0),
+        description: source.description.clone(),
+        map_data,
+        mines,
+        fakes,
+    }
+}
+
+/// Note symbols of a row, without keysound `[..]` and attack `{..}` attachments
+fn row_symbols(row: &str) -> impl Iterator<Item = char> + '_ {
+    let mut depth = 0usize;
+    row.chars().filter(move |&c| match c {
+        '[' | '{' => {
+            depth += 1;
+            false
+        }
+        ']' | '}' => {
+            depth = depth.saturating_sub(1);
+            false
+        }
+        _ => depth == 0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// .sm file at 120 BPM (500 ms per beat) from `offset` seconds, one #NOTES per chart
+    fn sm(offset: &str, extra_tags: &str, charts: &[(&str, &str, &str)]) -> String {
+        let mut content = format!("#TITLE:Song;\n#ARTIST:Artist;\n#OFFSET:{};\n#BPMS:0.000=120.000;\n{}\n", offset, extra_tags);
+        for (steps_type, difficulty, notes) in charts {
+            content += &format!("#NOTES:\n     {}:\n     :\n     {}:\n     10:\n     0,0,0,0,0:\n{}\n;\n", steps_type, difficulty, notes);
+        }
+        content
+    }
+
+    #[test]
+    fn charts_without_notes_are_skipped() {
+        let content = sm("0", "", &[
+            ("dance-single", "Edit", "0000\n0000\n0000\n0000\n"),
+            ("dance-single", "Hard", "M000\n0F00\n0000\n0000\n"),
+            ("dance-single", "Easy", "1000\n0000\n0000\n0000\n"),
+        ]);
+        let simfile = parse_stepmania(&content).unwrap();
+        assert_eq!(simfile.skipped, ["dance-single Edit", "dance-single Hard"]);
+        let [chart] = &simfile.charts[..] else { panic!("expected one chart") };
+        assert_eq!(chart.difficulty, "Easy");
+    }
+
+    /// Notes of the only chart of the content, as (column, hit, tail) sorted by time then column
+    fn single_chart_notes(content: &str) -> Vec<(usize, f64, f64)> {
+        let simfile = parse_stepmania(content).unwrap();
+        let [chart] = &simfile.charts[..] else { panic!("expected one chart") };
+        let mut notes: Vec<_> = chart.map_data.notes.iter().map(|n| (n.column, n.hit_time, n.tail_time)).collect();
+        notes.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+        notes
+    }
+
+    fn hit_times(content: &str) -> Vec<f64> {
+        single_chart_notes(content).into_iter().map(|(_, hit, _)| hit).collect()
+    }
+
+    #[test]
+    fn bpm_changes_apply_from_their_beat() {
+        let content = sm("0", "#BPMS:0.000=120.000,4.000=240.000;", &[(
+            "dance-single",
+            "Hard",
+            "1000\n1000\n1000\n1000\n,\n1000\n1000\n1000\n1000\n",
+        )]);
+        assert_eq!(hit_times(&content), [0.0, 500.0, 1000.0, 1500.0, 2000.0, 2250.0, 2500.0, 2750.0]);
+    }
+
+    #[test]
+    fn stops_follow_their_row_and_delays_precede_it() {
+        let notes = "1000\n1000\n1000\n0000\n";
+        let stopped = sm("0", "#STOPS:1.000=0.500;", &[("dance-single", "Hard", notes)]);
+        assert_eq!(hit_times(&stopped), [0.0, 500.0, 1500.0]);
+        let delayed = sm("0", "#DELAYS:1.000=0.500;", &[("dance-single", "Hard", notes)]);
+        assert_eq!(hit_times(&delayed), [0.0, 1000.0, 1500.0]);
+        // #FREEZES is the older name of #STOPS
+        let frozen = sm("0", "#FREEZES:1.000=0.500;", &[("dance-single", "Hard", notes)]);
+        assert_eq!(hit_times(&frozen), [0.0, 500.0, 1500.0]);
+    }
+
+    #[test]
+    fn negative_offset_delays_beat_zero() {
+        let notes = "1000\n0000\n0000\n0000\n";
+        assert_eq!(hit_times(&sm("-0.100", "", &[("dance-single", "Hard", notes)])), [100.0]);
+        assert_eq!(hit_times(&sm("0.100", "", &[("dance-single", "Hard", notes)])), [-100.0]);
+    }
+
+    #[test]
+    fn holds_and_rolls_end_at_their_tail() {
+        let content = sm("0", "", &[("dance-single", "Hard", "2000\n0400\n3000\n0300\n")]);
+        assert_eq!(single_chart_notes(&content), [(0, 0.0, 1000.0), (1, 500.0, 1500.0)]);
+    }
+
+    #[test]
+    fn unclosed_holds_are_played_as_taps() {
+        // a second head before the tail and a head never closed both leave a tap
+        let content = sm("0", "", &[("dance-single", "Hard", "2000\n2000\n3000\n0200\n")]);
+        assert_eq!(single_chart_notes(&content), [(0, 0.0, -1.0), (0, 500.0, 1000.0), (1, 1500.0, -1.0)]);
+    }
+
+    #[test]
+    fn keysounds_and_attacks_are_stripped() {
+        let content = sm("0", "", &[("dance-single", "Hard", "1[kick.wav]0{x}0{a[b]c}1\n0000\n0000\n0000\n")]);
+        assert_eq!(single_chart_notes(&content), [(0, 0.0, -1.0), (3, 0.0, -1.0)]);
+    }
+
+    #[test]
+    fn ssc_chart_timing_overrides_song_timing() {
+        let content = "#VERSION:0.83;\n#TITLE:Song;\n#OFFSET:0;\n#BPMS:0.000=120.000;\n\
+            #NOTEDATA:;\n#STEPSTYPE:dance-single;\n#DIFFICULTY:Hard;\n#METER:9;\n#BPMS:0.000=240.000;\n\
+            #NOTES:\n0000\n1000\n0000\n0000\n;\n\
+            #NOTEDATA:;\n#STEPSTYPE:dance-single;\n#DIFFICULTY:Easy;\n#METER:3;\n\
+            #NOTES:\n0000\n1000\n0000\n0000\n;\n";
+        let simfile = parse_stepmania(content).unwrap();
+        let hits: Vec<(&str, i32, f64)> = simfile
+            .charts
+            .iter()
+            .map(|chart| (chart.difficulty.as_str(), chart.meter, chart.map_data.notes[0].hit_time))
+            .collect();
+        assert_eq!(hits, [("Hard", 9, 250.0), ("Easy", 3, 500.0)]);
+    }
+
+    #[test]
+    fn dance_double_charts_have_eight_columns() {
+        let content = sm("0", "", &[
+            ("dance-double", "Hard", "10000001\n00000000\n00000000\n00000000\n"),
+            ("pump-single", "Hard", "10000\n00000\n00000\n00000\n"),
+        ]);
+        let simfile = parse_stepmania(&content).unwrap();
+        let [chart] = &simfile.charts[..] else { panic!("expected one chart") };
+        assert_eq!(chart.map_data.column_count, 8);
+        assert_eq!(chart.map_data.notes.iter().map(|n| n.column).max(), Some(7));
+    }
+}
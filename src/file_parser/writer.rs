@@ -0,0 +1,257 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use crate::types::{MapData, Note, OsuSection, StarRatingError, StarRatingResult, TimePrecision};
+use super::hit_objects::{TYPE_CIRCLE, TYPE_HOLD};
+
+/// x coordinate of the centre of a column, the inverse of `column_from_x`
+///
+/// # Arguments
+/// * `column` - Column index (0-based)
+/// * `column_count` - Number of columns
+///
+/// # Returns
+/// floor((512 * column + 256) / column_count)
+pub fn x_from_column(column: usize, column_count: usize) -> i32 {
+    ((512 * column + 256) / column_count.max(1)) as i32
+}
+
+/// Writes a map as a mania .osu file
+///
+/// A parsed map keeps its original sections, with [Difficulty] CircleSize and
+/// OverallDifficulty updated from the map and [HitObjects] regenerated from the notes
+/// (hitsounds are not kept). After a rate change the [TimingPoints], break periods,
+/// bookmarks and preview time are rescaled to match; the audio file itself is left
+/// as is. A map without `source_sections` (built in code, or parsed with
+/// `ParseOptions::keep_sections` turned off) gets minimal [General], [Metadata],
+/// [Difficulty] and [TimingPoints] sections instead, titled "Untitled" by "ssrrr".
+///
+/// Times are written in whole milliseconds, as the format expects: rounded, or
+/// floored with `TimePrecision::Integer` like the note times of such maps.
+///
+/// # Arguments
+/// * `map_data` - Map to write
+/// * `writer` - Destination
+pub fn write_osu<W: Write>(map_data: &MapData, mut writer: W) -> StarRatingResult<()> {
+    if map_data.source_sections.is_empty() {
+        write_minimal_sections(map_data, &mut writer)?;
+        write_hit_objects(map_data, &mut writer)?;
+        return Ok(writer.flush()?);
+    }
+
+    let mut wrote_hit_objects = false;
+    for section in &map_data.source_sections {
+        if !section.header.is_empty() {
+            writeln!(writer, "{}", section.header)?;
+        }
+        if section.header == "[HitObjects]" {
+            write_hit_objects(map_data, &mut writer)?;
+            wrote_hit_objects = true;
+            continue;
+        }
+        for line in &section.lines {
+            writeln!(writer, "{}", rewrite_line(map_data, section, line))?;
+        }
+    }
+    if !wrote_hit_objects {
+        writeln!(writer, "[HitObjects]")?;
+        write_hit_objects(map_data, &mut writer)?;
+    }
+    Ok(writer.flush()?)
+}
+
+/// Writes a map as .osu content in memory
+pub fn to_osu_string(map_data: &MapData) -> StarRatingResult<String> {
+    let mut buffer = Vec::new();
+    write_osu(map_data, &mut buffer)?;
+    Ok(String::from_utf8_lossy(&buffer).into_owned())
+}
+
+/// Writes a map to a .osu file on disk
+pub fn write_osu_file(map_data: &MapData, file_path: &str) -> StarRatingResult<()> {
+    let file = File::create(file_path).map_err(|e| StarRatingError::io(file_path, e))?;
+    write_osu(map_data, BufWriter::new(file))
+}
+
+/// Line of an original section, updated for the current state of the map
+fn rewrite_line(map_data: &MapData, section: &OsuSection, line: &str) -> String {
+    let rate = map_data.clock_rate;
+    let precision = map_data.time_precision;
+    match section.header.as_str() {
+        "[General]" => {
+            let Some((key, value)) = line.split_once(':') else { return line.to_string() };
+            match (key.trim(), value.trim().parse::<f64>()) {
+                ("Mode", _) => "Mode: 3".to_string(),
+                ("PreviewTime", Ok(time)) if time >= 0.0 && rate != 1.0 => {
+                    format!("PreviewTime: {}", format_time(time / rate, precision))
+                }
+                _ => line.to_string(),
+            }
+        }
+        "[Editor]" if rate != 1.0 => match line.split_once(':') {
+            Some((key, value)) if key.trim() == "Bookmarks" => {
+                let Some(times) = value.split(',').map(|time| time.trim().parse::<f64>().ok()).collect::<Option<Vec<_>>>() else {
+                    return line.to_string();
+                };
+                let times: Vec<String> = times.iter().map(|&time| format_time(time / rate, precision)).collect();
+                format!("Bookmarks: {}", times.join(","))
+            }
+            _ => line.to_string(),
+        },
+        "[Difficulty]" => {
+            let Some((key, _)) = line.split_once(':') else { return line.to_string() };
+            match key.trim() {
+                "CircleSize" => format!("CircleSize:{}", map_data.column_count),
+                "OverallDifficulty" if map_data.overall_difficulty >= 0.0 => {
+                    format!("OverallDifficulty:{}", map_data.overall_difficulty)
+                }
+                _ => line.to_string(),
+            }
+        }
+        "[Events]" if rate != 1.0 => rescale_break(line, rate, precision),
+        "[TimingPoints]" if rate != 1.0 => rescale_timing_point(line, rate, precision),
+        _ => line.to_string(),
+    }
+}
+
+/// Break event line ("2,start,end" or "Break,start,end") with its times divided by
+/// `rate`; other events are left as is
+fn rescale_break(line: &str, rate: f64, precision: TimePrecision) -> String {
+    let mut fields: Vec<String> = line.split(',').map(str::to_string).collect();
+    if fields.len() < 3 || !matches!(fields[0].trim(), "2" | "Break") {
+        return line.to_string();
+    }
+    let (Ok(start), Ok(end)) = (fields[1].trim().parse::<f64>(), fields[2].trim().parse::<f64>()) else {
+        return line.to_string();
+    };
+    fields[1] = format_time(start / rate, precision);
+    fields[2] = format_time(end / rate, precision);
+    fields.join(",")
+}
+
+/// Timing point line with its time divided by `rate`, and its beat length too when
+/// it is an uninherited (positive beat length) point
+fn rescale_timing_point(line: &str, rate: f64, precision: TimePrecision) -> String {
+    let mut fields: Vec<String> = line.split(',').map(str::to_string).collect();
+    let (Some(Ok(time)), Some(Ok(beat_length))) = (
+        fields.first().map(|field| field.trim().parse::<f64>()),
+        fields.get(1).map(|field| field.trim().parse::<f64>()),
+    ) else {
+        return line.to_string();
+    };
+    fields[0] = format_time(time / rate, precision);
+    if beat_length > 0.0 {
+        fields[1] = (beat_length / rate).to_string();
+    }
+    fields.join(",")
+}
+
+fn write_minimal_sections<W: Write>(map_data: &MapData, writer: &mut W) -> StarRatingResult<()> {
+    writeln!(writer, "osu file format v14")?;
+    writeln!(writer)?;
+    writeln!(writer, "[General]")?;
+    writeln!(writer, "AudioFilename: audio.mp3")?;
+    writeln!(writer, "Mode: 3")?;
+    writeln!(writer)?;
+    writeln!(writer, "[Metadata]")?;
+    writeln!(writer, "Title:Untitled")?;
+    writeln!(writer, "Artist:Unknown")?;
+    writeln!(writer, "Creator:ssrrr")?;
+    writeln!(writer, "Version:{}K", map_data.column_count)?;
+    writeln!(writer)?;
+    writeln!(writer, "[Difficulty]")?;
+    writeln!(writer, "HPDrainRate:5")?;
+    writeln!(writer, "CircleSize:{}", map_data.column_count)?;
+    if map_data.overall_difficulty >= 0.0 {
+        writeln!(writer, "OverallDifficulty:{}", map_data.overall_difficulty)?;
+    }
+    writeln!(writer, "ApproachRate:5")?;
+    writeln!(writer, "SliderMultiplier:1.4")?;
+    writeln!(writer, "SliderTickRate:1")?;
+    writeln!(writer)?;
+    writeln!(writer, "[TimingPoints]")?;
+    writeln!(writer, "0,500,4,1,0,100,1,0")?;
+    writeln!(writer)?;
+    writeln!(writer, "[HitObjects]")?;
+    Ok(())
+}
+
+fn write_hit_objects<W: Write>(map_data: &MapData, writer: &mut W) -> StarRatingResult<()> {
+    for note in &map_data.notes {
+        writeln!(writer, "{}", hit_object_line(note, map_data.column_count, map_data.time_precision))?;
+    }
+    Ok(())
+}
+
+/// "x,192,time,type,hitSound,[endTime:]hitSample" line of a note
+fn hit_object_line(note: &Note, column_count: usize, precision: TimePrecision) -> String {
+    let x = x_from_column(note.column, column_count);
+    let time = format_time(note.hit_time, precision);
+    if note.is_long_note() {
+        format!("{},192,{},{},0,{}:0:0:0:0:", x, time, TYPE_HOLD, format_time(note.tail_time, precision))
+    } else {
+        format!("{},192,{},{},0,0:0:0:0:", x, time, TYPE_CIRCLE)
+    }
+}
+
+/// Time in whole milliseconds: floored with `TimePrecision::Integer`, rounded otherwise
+fn format_time(time: f64, precision: TimePrecision) -> String {
+    let time = match precision {
+        TimePrecision::Integer => time.floor(),
+        TimePrecision::Fractional => time.round(),
+    };
+    (time as i64).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithm::process::preprocess::{preprocess, preprocess_with_options};
+    use crate::types::{ParseOptions, TimePrecision};
+
+    const RATED: &str = "osu file format v14\n\n[General]\nAudioFilename: audio.mp3\nPreviewTime: 1000\nMode: 3\n\n\
+        [Editor]\nBookmarks: 1000,2500\n\n[Events]\n0,0,\"bg.jpg\",0,0\n2,1300,2200\n\n\
+        [Difficulty]\nCircleSize:4\nOverallDifficulty:8\n\n[TimingPoints]\n1000,500,4,1,0,100,1,0\n2000,-100,4,1,0,100,0,0\n\n\
+        [HitObjects]\n64,192,1000,1,0,0:0:0:0:\n192,192,1250,128,0,2501:0:0:0:0:\n";
+
+    /// Lines of the written map under `header`, up to the next blank line
+    fn section_lines<'a>(written: &'a str, header: &str) -> Vec<&'a str> {
+        written.lines().skip_while(|line| *line != header).skip(1).take_while(|line| !line.is_empty()).collect()
+    }
+
+    #[test]
+    fn rated_times_are_whole_milliseconds() {
+        for (precision, expected) in [
+            (TimePrecision::Fractional, ["667", "1667", "867,1467", "667,333.3333333333333", "1333,-100", "64,192,667", "192,192,833,128,0,1667"]),
+            (TimePrecision::Integer, ["666", "1666", "866,1466", "666,333.3333333333333", "1333,-100", "64,192,666", "192,192,833,128,0,1667"]),
+        ] {
+            let options = ParseOptions::new().with_time_precision(precision);
+            let written = to_osu_string(&preprocess_with_options(RATED, "DT", &options).unwrap()).unwrap();
+            let [preview, bookmarks, break_times, red_line, green_line, note, hold] = expected;
+
+            assert!(written.contains(&format!("PreviewTime: {}\n", preview)), "{}", written);
+            assert_eq!(section_lines(&written, "[Editor]"), [format!("Bookmarks: {},{}", preview, bookmarks)]);
+            assert_eq!(section_lines(&written, "[Events]"), ["0,0,\"bg.jpg\",0,0".to_string(), format!("2,{}", break_times)]);
+            let timing_points = section_lines(&written, "[TimingPoints]");
+            assert!(timing_points[0].starts_with(red_line) && timing_points[1].starts_with(green_line), "{:?}", timing_points);
+            let hit_objects = section_lines(&written, "[HitObjects]");
+            assert!(hit_objects[0].starts_with(note) && hit_objects[1].starts_with(hold), "{:?}", hit_objects);
+        }
+    }
+
+    #[test]
+    fn parsed_maps_keep_their_sections_by_default() {
+        let content = std::fs::read_to_string("assets/test.osu").unwrap();
+        let plain = preprocess_with_options(&content, "None", &ParseOptions::new().with_keep_sections(false)).unwrap();
+        assert!(plain.source_sections.is_empty());
+        assert!(to_osu_string(&plain).unwrap().contains("Creator:ssrrr"));
+
+        let kept = preprocess(&content, "None").unwrap();
+        assert!(kept.source_sections.iter().any(|section| section.header == "[Metadata]"));
+        let written = to_osu_string(&kept).unwrap();
+        assert!(!written.contains("Creator:ssrrr"));
+
+        let reparsed = preprocess(&written, "None").unwrap();
+        assert_eq!(reparsed.notes, kept.notes);
+        assert_eq!(reparsed.column_count, kept.column_count);
+    }
+}
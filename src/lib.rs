@@ -2,6 +2,20 @@ pub mod algorithm;
 pub mod file_parser;
 pub mod params;
 pub mod types;
+#[cfg(test)]
+mod test_support;
 
 // Public re-exports for simplified API
-pub use algorithm::process::preprocess::{preprocess_file, preprocess};
\ No newline at end of file
+pub use algorithm::process::preprocess::{
+    preprocess_file, preprocess, preprocess_reader,
+    preprocess_file_with_options, preprocess_with_options, preprocess_reader_with_options,
+    preprocess_stepmania, preprocess_stepmania_file,
+};
+pub use file_parser::writer::{to_osu_string, write_osu, write_osu_file};
+pub use algorithm::calculator::DifficultyCalculator;
+pub use algorithm::process::calculator::Calculator;
+pub use algorithm::process::batch::{rate_directory, rate_files};
+pub use algorithm::process::osz::{rate_osz, rate_osz_reader};
+pub use algorithm::process::async_api::{
+    calculate_async, preprocess_file_async, preprocess_file_with_options_async, AsyncCalculator,
+};
@@ -1,7 +1,40 @@
+use crate::algorithm::fft::autocorrelation;
+use crate::types::{StarRatingResult, CalculationError, Note};
 
-use crate::types::{StarRatingResult, CalculationError};
+/// Largest histogram (in bins) handled with the FFT autocorrelation; wider samples
+/// are binned more coarsely to fit
+const MAX_HISTOGRAM_SPAN: f64 = (1u64 << 22) as f64;
 
+/// Histogram bins per unit for fractional values: rate-changed millisecond gaps
+/// (multiples of 2/3 or 4/3) and half milliseconds fall exactly on a bin
+const FRACTIONAL_BINS_PER_UNIT: f64 = 12.0;
+
+/// Computes Rao's quadratic entropy of a sample, with a log-compressed distance
+///
+/// The sample is sorted and grouped in O(n log n), then
+/// - without log compression the distance is linear and Q comes from prefix sums;
+/// - otherwise Q is summed pairwise over the u unique values (O(u^2)), or over the
+///   autocorrelation of their histogram (FFT, O(s log s) for s bins), whichever is
+///   cheaper. Integral values get one bin per unit and an exact result; fractional
+///   values are quantized to 1/12 unit, which costs at most 1/12 on each distance
+///   before compression.
+/// - the histogram is capped at 2^22 bins, so a sample spanning more than that many
+///   bins gets wider ones (span / 2^22 units each) and the work stays bounded. Each
+///   distance is then off by at most one bin width, and since `ln(1 + d)` shrinks
+///   differences, Q is off by at most one bin width as well (0.24 for a span of
+///   10^6 units).
+///
+/// # Arguments
+/// * `values` - Sample values
+/// * `log_iterations` - Number of `ln(1 + d)` applications on the absolute difference
+///
+/// # Returns
+/// Q = sum_{i,j} p_i * p_j * d(i, j), 0 for an empty sample
 pub fn rao_quadratic_entropy_log(values: &[f64], log_iterations: usize) -> StarRatingResult<f64> {
+    if values.is_empty() {
+        return Ok(0.0);
+    }
+
     // Determine the unique categories and their counts
     let mut sorted_values = values.to_vec();
     sorted_values.sort_by(|a, b| a.partial_cmp(b).expect("Valeurs finies attendues"));
@@ -9,78 +42,140 @@ pub fn rao_quadratic_entropy_log(values: &[f64], log_iterations: usize) -> StarR
     let mut unique = Vec::new();
     let mut counts = Vec::new();
     
-    if !sorted_values.is_empty() {
-        let mut current_value = sorted_values[0];
-        let mut current_count = 1;
-        
-        for &value in &sorted_values[1..] {
-            if (value - current_value).abs() < 1e-10 {
-                current_count += 1;
-            } else {
-                unique.push(current_value);
-                counts.push(current_count);
-                current_value = value;
-                current_count = 1;
-            }
+    let mut current_value = sorted_values[0];
+    let mut current_count = 1;
+    
+    for &value in &sorted_values[1..] {
+        if (value - current_value).abs() < 1e-10 {
+            current_count += 1;
+        } else {
+            unique.push(current_value);
+            counts.push(current_count);
+            current_value = value;
+            current_count = 1;
         }
-        unique.push(current_value);
-        counts.push(current_count);
     }
+    unique.push(current_value);
+    counts.push(current_count);
     
-    let total_count: usize = counts.iter().sum();
-    let p: Vec<f64> = counts.iter().map(|&count| count as f64 / total_count as f64).collect();
-    
-    let distance_func = |x: f64, y: f64, log_it: usize| -> f64 {
-        let mut acc = (x - y).abs();
-        for _ in 0..log_it {
+    let total_count = values.len() as f64;
+    let p: Vec<f64> = counts.iter().map(|&count| count as f64 / total_count).collect();
+
+    let distance_func = |d: f64| -> f64 {
+        let mut acc = d;
+        for _ in 0..log_iterations {
             acc = (1.0 + acc).ln();
         }
         acc
     };
-    
-    // Compute the distance (dissimilarity) matrix for the unique values
-    let n = unique.len();
-    let mut dist_matrix = vec![vec![0.0; n]; n];
-    for i in 0..n {
-        for j in 0..n {
-            dist_matrix[i][j] = distance_func(unique[i], unique[j], log_iterations);
+
+    // Linear distance: sum_{i<j} p_i p_j (u_j - u_i) with prefix sums of p and p * u
+    if log_iterations == 0 {
+        let mut q = 0.0;
+        let mut prefix_p = 0.0;
+        let mut prefix_pu = 0.0;
+        for (&u, &p_j) in unique.iter().zip(p.iter()) {
+            q += p_j * (u * prefix_p - prefix_pu);
+            prefix_p += p_j;
+            prefix_pu += p_j * u;
         }
+        return Ok(2.0 * q);
     }
-    
-    // Compute Rao's Quadratic Entropy: Q = sum_{i,j} p_i * p_j * d(i, j)
+
+    let n = unique.len();
+    let span = unique[n - 1] - unique[0];
+    let integral = unique.iter().all(|u| u.fract() == 0.0);
+    let mut bins_per_unit = if integral { 1.0 } else { FRACTIONAL_BINS_PER_UNIT };
+    if span * bins_per_unit >= MAX_HISTOGRAM_SPAN {
+        bins_per_unit = (MAX_HISTOGRAM_SPAN - 1.0) / span;
+    }
+    let span_bins = (span * bins_per_unit).round();
+    let pairwise_cost = (n * n / 2) as f64;
+    let size = (2.0 * span_bins + 1.0).max(2.0);
+    let histogram_cost = 8.0 * size * size.log2();
+
+    if pairwise_cost <= histogram_cost {
+        return Ok(pairwise_entropy(&unique, &p, distance_func));
+    }
+    Ok(histogram_entropy(&unique, &counts, bins_per_unit, distance_func))
+}
+
+/// Q summed pairwise over sorted unique values, without a distance matrix
+fn pairwise_entropy(unique: &[f64], p: &[f64], distance_func: impl Fn(f64) -> f64) -> f64 {
     let mut q = 0.0;
-    for i in 0..n {
-        for j in 0..n {
-            q += p[i] * p[j] * dist_matrix[i][j];
+    for i in 0..unique.len() {
+        let mut row = 0.0;
+        for j in i + 1..unique.len() {
+            row += p[j] * distance_func(unique[j] - unique[i]);
         }
+        q += p[i] * row;
     }
-    Ok(q)
+    2.0 * q
 }
 
-pub fn variety(note_seq: &[(i32, i32, i32)], note_seq_by_column: &[Vec<(i32, i32, i32)>]) -> StarRatingResult<f64> {
+/// Q from the histogram autocorrelation: Q = sum_d f(d) * sum_x h(x) h(x + d) / N^2
+fn histogram_entropy(unique: &[f64], counts: &[usize], bins_per_unit: f64, distance_func: impl Fn(f64) -> f64) -> f64 {
+    let origin = unique[0];
+    let span_bins = ((unique[unique.len() - 1] - origin) * bins_per_unit).round();
+    let mut hist = vec![0.0; span_bins as usize + 1];
+    for (&u, &count) in unique.iter().zip(counts.iter()) {
+        hist[((u - origin) * bins_per_unit).round() as usize] += count as f64;
+    }
+    let total_count: usize = counts.iter().sum();
+    let correlation = autocorrelation(&hist);
+    let q: f64 = correlation.iter()
+        .enumerate()
+        .skip(1)
+        .map(|(d, &pairs)| pairs.round() * distance_func(d as f64 / bins_per_unit))
+        .sum();
+    2.0 * q / (total_count as f64 * total_count as f64)
+}
+
+/// Gaps between consecutive values
+fn consecutive_gaps(values: &[f64]) -> Vec<f64> {
+    values.windows(2).map(|pair| pair[1] - pair[0]).collect()
+}
+
+/// Computes the rhythm variety of a map
+///
+/// # Arguments
+/// * `note_seq` - Notes sorted by hit time
+/// * `note_seq_by_column` - Notes of each column sorted by hit time
+///
+/// # Returns
+/// Weighted entropy of head gaps, tail gaps and per-column head gaps
+pub fn variety(note_seq: &[Note], note_seq_by_column: &[Vec<Note>]) -> StarRatingResult<f64> {
     // assume that note_seq already is sorted by head
-    let heads: Vec<i32> = note_seq.iter().map(|n| n.1).collect();
-    let mut tails: Vec<i32> = note_seq.iter().map(|n| n.2).collect(); // -1 for rice is included
-    tails.sort();
-    
-    let head_gaps: Vec<f64> = (0..heads.len()-1).map(|i| (heads[i+1] - heads[i]) as f64).collect();
-    let tail_gaps: Vec<f64> = (0..tails.len()-1).map(|i| (tails[i+1] - tails[i]) as f64).collect();
+    let heads: Vec<f64> = note_seq.iter().map(|n| n.hit_time).collect();
+    let mut tails: Vec<f64> = note_seq.iter().map(|n| n.tail_time).collect(); // -1 for rice is included
+    tails.sort_by(f64::total_cmp);
     
-    let head_variety = rao_quadratic_entropy_log(&head_gaps, 1)?;
-    let tail_variety = rao_quadratic_entropy_log(&tail_gaps, 1)?;
+    let head_variety = rao_quadratic_entropy_log(&consecutive_gaps(&heads), 1)?;
+    let tail_variety = rao_quadratic_entropy_log(&consecutive_gaps(&tails), 1)?;
     
     let mut all_head_gaps = Vec::new();
-    for k in 0..note_seq_by_column.len() {
-        let column_heads: Vec<i32> = note_seq_by_column[k].iter().map(|n| n.1).collect();
-        let column_gaps: Vec<f64> = (0..column_heads.len()-1).map(|i| (column_heads[i+1] - column_heads[i]) as f64).collect();
-        all_head_gaps.extend(column_gaps);
+    for column_notes in note_seq_by_column {
+        let column_heads: Vec<f64> = column_notes.iter().map(|n| n.hit_time).collect();
+        all_head_gaps.extend(consecutive_gaps(&column_heads));
     }
     let col_variety = 2.5 * rao_quadratic_entropy_log(&all_head_gaps, 2)?;
     
     Ok(0.5 * head_variety + 0.11 * tail_variety + 0.45 * col_variety)
 }
 
+/// Computes how spiky the difficulty curve is
+///
+/// # Arguments
+/// * `d_sorted` - Difficulty values sorted ascending
+/// * `w_sorted` - Effective weights in the same order
+///
+/// # Returns
+/// Weighted deviation of D^8 relative to the power-5 weighted mean
 pub fn spikiness(d_sorted: &[f64], w_sorted: &[f64]) -> StarRatingResult<f64> {
+    if d_sorted.is_empty() {
+        return Err(CalculationError::EmptyData("spikiness: difficulty values".to_string()).into());
+    }
+
     let total_weight = w_sorted.iter().sum::<f64>();
     if total_weight.abs() < 1e-10 {
         return Err(CalculationError::DivisionByZero("spikiness: total weight".to_string()).into());
@@ -101,68 +196,155 @@ pub fn spikiness(d_sorted: &[f64], w_sorted: &[f64]) -> StarRatingResult<f64> {
     Ok(weighted_variance.sqrt() / weighted_mean)
 }
 
-pub fn switch(note_seq: &[(i32, i32, i32)], tail_seq: &[(i32, i32, i32)], all_corners: &[f64], ks_arr: &[f64], weights: &[f64]) -> StarRatingResult<f64> {
-    let heads: Vec<i32> = note_seq.iter().map(|n| n.1).collect();
-    let idx_list: Vec<usize> = heads.iter().map(|&head| {
-        match all_corners.iter().position(|&val| val >= head as f64) {
-            Some(pos) => pos,
-            None => all_corners.len() - 1,
-        }
-    }).collect();
+/// Index of the first corner at or after `time` (last corner if none)
+fn corner_index(all_corners: &[f64], time: f64) -> usize {
+    // bisect_left on sorted corners
+    all_corners.partition_point(|&val| val < time).min(all_corners.len() - 1)
+}
+
+/// Gap signature of a time sequence: (signature, reference signature, gap count)
+fn gap_signature(times: &[f64], all_corners: &[f64], ks_arr: &[f64], weights: &[f64]) -> (f64, f64, usize) {
+    let idx_list: Vec<usize> = times.iter().map(|&time| corner_index(all_corners, time)).collect();
     
     let ks_arr_at_note: Vec<f64> = idx_list[..idx_list.len()-1].iter().map(|&idx| ks_arr[idx]).collect();
     let weights_at_note: Vec<f64> = idx_list[..idx_list.len()-1].iter().map(|&idx| weights[idx]).collect();
     
-    let head_gaps: Vec<f64> = (0..heads.len()-1).map(|i| (heads[i+1] - heads[i]) as f64 / 1000.0).collect();
+    let gaps: Vec<f64> = times.windows(2).map(|pair| (pair[1] - pair[0]) / 1000.0).collect();
     
-    let avgs: Vec<f64> = (0..head_gaps.len()).map(|i| {
-        let start = (i as i32 - 50).max(0) as usize;
-        let end = (i + 50).min(head_gaps.len() - 1);
-        head_gaps[start..=end].iter().sum::<f64>() / (end - start + 1) as f64
+    // ±50-gap moving averages from prefix sums
+    let mut prefix = Vec::with_capacity(gaps.len() + 1);
+    prefix.push(0.0);
+    for &gap in &gaps {
+        let last = *prefix.last().expect("Vecteur non vide attendu");
+        prefix.push(last + gap);
+    }
+    let avgs: Vec<f64> = (0..gaps.len()).map(|i| {
+        let start = i.saturating_sub(50);
+        let end = (i + 50).min(gaps.len() - 1);
+        (prefix[end + 1] - prefix[start]) / (end - start + 1) as f64
     }).collect();
     
-    let signature_head: f64 = head_gaps.iter().zip(avgs.iter()).zip(weights_at_note.iter()).zip(ks_arr_at_note.iter())
-        .map(|(((gap, avg), weight), ks)| (gap / avg / head_gaps.len() as f64 * weight).sqrt() * ks.powf(0.25))
+    let signature: f64 = gaps.iter().zip(avgs.iter()).zip(weights_at_note.iter()).zip(ks_arr_at_note.iter())
+        .map(|(((gap, avg), weight), ks)| (gap / avg / gaps.len() as f64 * weight).sqrt() * ks.powf(0.25))
         .sum();
     
-    let ref_signature_head: f64 = head_gaps.iter().zip(avgs.iter()).zip(weights_at_note.iter())
+    let ref_signature: f64 = gaps.iter().zip(avgs.iter()).zip(weights_at_note.iter())
         .map(|((gap, avg), weight)| gap / avg * weight)
         .sum::<f64>().sqrt();
+
+    (signature, ref_signature, gaps.len())
+}
+
+/// Computes the hand-switch rate of a map
+///
+/// # Arguments
+/// * `note_seq` - Notes sorted by hit time
+/// * `tail_seq` - Long notes sorted by tail time
+/// * `all_corners` - All corners
+/// * `ks_arr` - Active key count on all corners
+/// * `weights` - Effective weights on all corners
+///
+/// # Returns
+/// Switch value in [0.5, ~1]
+pub fn switch(note_seq: &[Note], tail_seq: &[Note], all_corners: &[f64], ks_arr: &[f64], weights: &[f64]) -> StarRatingResult<f64> {
+    if note_seq.len() < 2 {
+        return Err(CalculationError::EmptyData("switch: at least two notes are required".to_string()).into());
+    }
+    if all_corners.is_empty() {
+        return Err(CalculationError::EmptyData("switch: corners".to_string()).into());
+    }
+
+    let heads: Vec<f64> = note_seq.iter().map(|n| n.hit_time).collect();
+    let (signature_head, ref_signature_head, head_gap_count) = gap_signature(&heads, all_corners, ks_arr, weights);
     
-    let tails: Vec<i32> = tail_seq.iter().map(|n| n.2).collect();
-    let tail_idx_list: Vec<usize> = tails.iter().map(|&tail| {
-        match all_corners.iter().position(|&val| val >= tail as f64) {
-            Some(pos) => pos,
-            None => all_corners.len() - 1,
-        }
-    }).collect();
-    
-    let tail_ks_arr_at_note: Vec<f64> = tail_idx_list[..tail_idx_list.len()-1].iter().map(|&idx| ks_arr[idx]).collect();
-    let tail_weights_at_note: Vec<f64> = tail_idx_list[..tail_idx_list.len()-1].iter().map(|&idx| weights[idx]).collect();
-    
-    let tail_gaps: Vec<f64> = (0..tails.len()-1).map(|i| (tails[i+1] - tails[i]) as f64 / 1000.0).collect();
-    
+    let tails: Vec<f64> = tail_seq.iter().map(|n| n.tail_time).collect();
     let mut signature_tail = 0.0;
     let mut ref_signature_tail = 0.0;
+    let mut tail_gap_count = 0;
+    
+    if tails.len() >= 2 && tails[tails.len()-1] > tails[0] {
+        (signature_tail, ref_signature_tail, tail_gap_count) = gap_signature(&tails, all_corners, ks_arr, weights);
+    }
     
-    if !tails.is_empty() && tails[tails.len()-1] > tails[0] {
-        let tail_avgs: Vec<f64> = (0..tail_gaps.len()).map(|i| {
-            let start = (i as i32 - 50).max(0) as usize;
-            let end = (i + 50).min(tail_gaps.len() - 1);
-            tail_gaps[start..=end].iter().sum::<f64>() / (end - start + 1) as f64
-        }).collect();
-        
-        signature_tail = tail_gaps.iter().zip(tail_avgs.iter()).zip(tail_weights_at_note.iter()).zip(tail_ks_arr_at_note.iter())
-            .map(|(((gap, avg), weight), ks)| (gap / avg / tail_gaps.len() as f64 * weight).sqrt() * ks.powf(0.25))
-            .sum();
-        
-        ref_signature_tail = tail_gaps.iter().zip(tail_avgs.iter()).zip(tail_weights_at_note.iter())
-            .map(|((gap, avg), weight)| gap / avg * weight)
-            .sum::<f64>().sqrt();
-    }
-    
-    let switches = (signature_head * head_gaps.len() as f64 + signature_tail * tail_gaps.len() as f64) / 
-                   (ref_signature_head * head_gaps.len() as f64 + ref_signature_tail * tail_gaps.len() as f64);
+    let reference = ref_signature_head * head_gap_count as f64 + ref_signature_tail * tail_gap_count as f64;
+    if reference.abs() < 1e-10 {
+        return Err(CalculationError::DivisionByZero("switch: reference signature".to_string()).into());
+    }
+    let switches = (signature_head * head_gap_count as f64 + signature_tail * tail_gap_count as f64) / reference;
     
     Ok(switches / 2.0 + 0.5)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log_distance(d: f64) -> f64 {
+        (1.0 + d).ln()
+    }
+
+    #[test]
+    fn coarse_histograms_stay_within_one_bin_of_the_exact_entropy() {
+        // widely spread fractional gaps, with repeats
+        let unique: Vec<f64> = (0..2000).map(|i| (i * i) as f64 * 0.37 + (i % 7) as f64 / 3.0).collect();
+        let counts: Vec<usize> = (0..unique.len()).map(|i| 1 + i % 3).collect();
+        let total = counts.iter().sum::<usize>() as f64;
+        let p: Vec<f64> = counts.iter().map(|&count| count as f64 / total).collect();
+
+        let exact = pairwise_entropy(&unique, &p, log_distance);
+        let span = unique[unique.len() - 1] - unique[0];
+        for bins in [1u32 << 12, 1 << 15, 1 << 18] {
+            let bins_per_unit = bins as f64 / span;
+            let binned = histogram_entropy(&unique, &counts, bins_per_unit, log_distance);
+            assert!((binned - exact).abs() <= 1.0 / bins_per_unit, "{} bins: {} vs {}", bins, binned, exact);
+        }
+    }
+
+    #[test]
+    fn integral_histograms_are_exact() {
+        let unique: Vec<f64> = (0..500).map(|i| (100 * i + i * i % 97) as f64).collect();
+        let counts = vec![2; unique.len()];
+        let p = vec![1.0 / unique.len() as f64; unique.len()];
+        let exact = pairwise_entropy(&unique, &p, log_distance);
+        let binned = histogram_entropy(&unique, &counts, 1.0, log_distance);
+        assert!((binned - exact).abs() < 1e-9 * exact, "{} vs {}", binned, exact);
+    }
+
+    #[test]
+    fn identical_values_have_no_entropy() {
+        assert_eq!(rao_quadratic_entropy_log(&[], 1).unwrap(), 0.0);
+        assert_eq!(rao_quadratic_entropy_log(&[125.0; 40], 2).unwrap(), 0.0);
+
+        // Two equally likely values: Q = 2 * 1/4 * ln(1 + 100)
+        let q = rao_quadratic_entropy_log(&[0.0, 100.0], 1).unwrap();
+        assert!((q - 0.5 * 101f64.ln()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn regular_streams_have_no_rhythm_variety() {
+        let notes: Vec<Note> = (0..64).map(|i| Note::simple(i % 4, 125.0 * i as f64)).collect();
+        let by_column: Vec<Vec<Note>> = (0..4).map(|column| notes.iter().filter(|note| note.column == column).cloned().collect()).collect();
+        assert_eq!(variety(&notes, &by_column).unwrap(), 0.0);
+
+        let swung: Vec<Note> = (0..64).map(|i| Note::simple(i % 4, 125.0 * i as f64 + if i % 2 == 1 { 40.0 } else { 0.0 })).collect();
+        let by_column: Vec<Vec<Note>> = (0..4).map(|column| swung.iter().filter(|note| note.column == column).cloned().collect()).collect();
+        assert!(variety(&swung, &by_column).unwrap() > 0.0);
+    }
+
+    #[test]
+    fn spikiness_grows_with_the_spread_of_the_difficulty() {
+        let weights = [1.0; 4];
+        let flat = spikiness(&[3.0; 4], &weights).unwrap();
+        let mild = spikiness(&[2.0, 3.0, 3.0, 4.0], &weights).unwrap();
+        let spiky = spikiness(&[1.0, 1.0, 1.0, 6.0], &weights).unwrap();
+        assert!(flat < 1e-6, "{}", flat);
+        assert!(flat < mild && mild < spiky, "{} {} {}", flat, mild, spiky);
+    }
+
+    #[test]
+    fn degenerate_inputs_are_errors() {
+        assert!(matches!(spikiness(&[], &[]), Err(crate::types::StarRatingError::CalculationError(CalculationError::EmptyData(_)))));
+        assert!(matches!(spikiness(&[1.0], &[0.0]), Err(crate::types::StarRatingError::CalculationError(CalculationError::DivisionByZero(_)))));
+        assert!(matches!(switch(&[Note::simple(0, 0.0)], &[], &[0.0], &[1.0], &[1.0]), Err(crate::types::StarRatingError::CalculationError(CalculationError::EmptyData(_)))));
+    }
+}
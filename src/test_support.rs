@@ -0,0 +1,43 @@
+//! Helpers shared by the unit tests
+
+/// Deterministic xorshift generator for randomized tests
+pub(crate) struct TestRng(u64);
+
+impl TestRng {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self(seed.max(1))
+    }
+
+    /// Uniform value in [0, 1)
+    pub(crate) fn next_f64(&mut self) -> f64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    pub(crate) fn values(&mut self, len: usize, scale: f64) -> Vec<f64> {
+        (0..len).map(|_| self.next_f64() * scale).collect()
+    }
+
+    /// Nondecreasing time points; about one step in five repeats the previous point
+    pub(crate) fn sorted_points(&mut self, len: usize) -> Vec<f64> {
+        let mut t = 0.0;
+        (0..len)
+            .map(|_| {
+                if self.next_f64() >= 0.2 {
+                    t += (self.next_f64() * 200.0).floor() + 1.0;
+                }
+                t
+            })
+            .collect()
+    }
+}
+
+/// Asserts that two slices hold the same values, bit for bit
+pub(crate) fn assert_bits_eq(name: &str, fast: &[f64], reference: &[f64]) {
+    assert_eq!(fast.len(), reference.len(), "{}: length differs", name);
+    for (i, (a, b)) in fast.iter().zip(reference).enumerate() {
+        assert_eq!(a.to_bits(), b.to_bits(), "{}[{}]: {} != {}", name, i, a, b);
+    }
+}
@@ -0,0 +1,169 @@
+/// Outcome of a single judged note in a replay
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JudgementResult {
+    /// The note was missed
+    Miss,
+    /// The note was hit with the given error in milliseconds (negative = early)
+    Hit(f64),
+}
+
+/// Per-note judgement coming from a parsed replay
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NoteJudgement {
+    /// Column index (0-based), below the column count of the map
+    pub column: usize,
+    /// Note time in milliseconds, in the same time base as the MapData (mods applied)
+    pub time: f64,
+    /// Judgement outcome
+    pub result: JudgementResult,
+}
+
+impl NoteJudgement {
+    /// Creates a missed note judgement
+    pub fn miss(column: usize, time: f64) -> Self {
+        Self { column, time, result: JudgementResult::Miss }
+    }
+
+    /// Creates a hit note judgement
+    pub fn hit(column: usize, time: f64, hit_error: f64) -> Self {
+        Self { column, time, result: JudgementResult::Hit(hit_error) }
+    }
+}
+
+/// Skill families a difficulty spike can be attributed to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SkillKind {
+    /// Same-column repetitions (Jbar)
+    Jack,
+    /// Pressing intensity across the whole keyboard (Pbar)
+    Stream,
+    /// Long note releases (Rbar)
+    Release,
+    /// Adjacent-column coordination (Xbar)
+    Technical,
+}
+
+impl SkillKind {
+    /// All skill kinds, in report order
+    pub const ALL: [SkillKind; 4] = [SkillKind::Jack, SkillKind::Stream, SkillKind::Release, SkillKind::Technical];
+
+    /// Human-readable name of the skill
+    pub fn name(&self) -> &'static str {
+        match self {
+            SkillKind::Jack => "jack",
+            SkillKind::Stream => "stream",
+            SkillKind::Release => "LN release",
+            SkillKind::Technical => "technical",
+        }
+    }
+}
+
+/// Share of the local difficulty attributed to each skill (sums to 1 when difficulty is non-zero)
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SkillShares {
+    /// Jack share
+    pub jack: f64,
+    /// Stream share
+    pub stream: f64,
+    /// LN release share
+    pub release: f64,
+    /// Technical share
+    pub technical: f64,
+}
+
+impl SkillShares {
+    /// Returns the share of the given skill
+    pub fn get(&self, skill: SkillKind) -> f64 {
+        match skill {
+            SkillKind::Jack => self.jack,
+            SkillKind::Stream => self.stream,
+            SkillKind::Release => self.release,
+            SkillKind::Technical => self.technical,
+        }
+    }
+
+    /// Returns the skill with the largest share, or `None` when every share is zero
+    ///
+    /// Ties go to the skill listed first in `SkillKind::ALL`.
+    pub fn dominant(&self) -> Option<SkillKind> {
+        let mut best = None;
+        for skill in SkillKind::ALL {
+            if self.get(skill) > best.map_or(0.0, |best| self.get(best)) {
+                best = Some(skill);
+            }
+        }
+        best
+    }
+}
+
+/// Raw bar values at one point of the timeline
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BarSnapshot {
+    /// J bar value
+    pub jbar: f64,
+    /// X bar value
+    pub xbar: f64,
+    /// P bar value
+    pub pbar: f64,
+    /// A bar value
+    pub abar: f64,
+    /// R bar value
+    pub rbar: f64,
+}
+
+/// One miss or large hit error placed on the difficulty timeline
+#[derive(Debug, Clone)]
+pub struct WeaknessEntry {
+    /// Judgement being analysed
+    pub judgement: NoteJudgement,
+    /// Local difficulty (d_all) at the note
+    pub difficulty: f64,
+    /// Fraction of the map's total weight (corner weight times gap, as in the rating)
+    /// lying on easier points (0..=1)
+    pub difficulty_percentile: f64,
+    /// Bar values at the note
+    pub bars: BarSnapshot,
+    /// Skill shares at the note
+    pub shares: SkillShares,
+    /// Skill dominating the local difficulty, `None` where the difficulty is zero
+    pub dominant_skill: Option<SkillKind>,
+}
+
+/// Aggregated counts for one skill
+#[derive(Debug, Clone, PartialEq)]
+pub struct SkillSummary {
+    /// Skill being summarised
+    pub skill: SkillKind,
+    /// Misses where this skill dominated
+    pub misses: usize,
+    /// Large hit errors where this skill dominated
+    pub large_errors: usize,
+    /// Mean share of this skill over all map corners, for comparison
+    pub map_share: f64,
+}
+
+/// Weakness report joining replay judgements with the difficulty timeline
+#[derive(Debug, Clone)]
+pub struct WeaknessReport {
+    /// Misses and large hit errors, in time order
+    pub entries: Vec<WeaknessEntry>,
+    /// Per-skill summaries, sorted by number of flagged judgements (descending)
+    pub summaries: Vec<SkillSummary>,
+    /// Skill most over-represented in the flagged judgements, if any
+    pub weakest_skill: Option<SkillKind>,
+    /// Mean difficulty percentile of the flagged judgements
+    pub mean_percentile: f64,
+    /// Hit error threshold (ms) used to flag large errors
+    pub large_error_threshold: f64,
+}
+
+/// Pattern descriptors of a map, used for tagging
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MapDescriptors {
+    /// Rhythm variety: entropy of head, tail and per-column gaps
+    pub rhythm_variety: f64,
+    /// Difficulty spikiness: deviation of the difficulty curve relative to its weighted mean
+    pub spikiness: f64,
+    /// Hand-switch rate (starts at 0.5, higher = more switching between keys)
+    pub hand_switch: f64,
+}
@@ -0,0 +1,265 @@
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use super::error::StarRatingResult;
+
+/// Shared flag asking a running batch to stop before its next file
+///
+/// Clones share the same flag; files already being rated when the token is
+/// cancelled still finish and are reported.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    /// Creates a token that is not cancelled
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Wraps an existing flag
+    pub fn from_flag(cancelled: Arc<AtomicBool>) -> Self {
+        Self { cancelled }
+    }
+
+    /// Requests cancellation
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns true once cancellation was requested
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// Progress of a batch, reported after each file
+#[derive(Debug, Clone)]
+pub struct BatchProgress {
+    /// Files finished so far (rated or failed)
+    pub completed: usize,
+    /// Files in the batch
+    pub total: usize,
+    /// File that just finished
+    pub path: PathBuf,
+    /// Whether that file was rated successfully
+    pub succeeded: bool,
+}
+
+/// Progress callback, invoked from the worker threads
+pub type ProgressCallback = Arc<dyn Fn(&BatchProgress) + Send + Sync>;
+
+/// Options of a batch run
+#[derive(Clone)]
+pub struct BatchOptions {
+    /// Worker threads (0 = available parallelism)
+    pub workers: usize,
+    /// Mod applied to every map ("DT", "HT", anything else = none)
+    pub mod_name: String,
+    /// Stops the batch before its next file once cancelled
+    pub cancellation: CancellationToken,
+    /// Called after each file
+    pub progress: Option<ProgressCallback>,
+}
+
+impl BatchOptions {
+    /// Creates options with one worker per available core and no mod
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the number of worker threads (0 = available parallelism)
+    pub fn with_workers(mut self, workers: usize) -> Self {
+        self.workers = workers;
+        self
+    }
+
+    /// Sets the mod applied to every map
+    pub fn with_mod(mut self, mod_name: &str) -> Self {
+        self.mod_name = mod_name.to_string();
+        self
+    }
+
+    /// Sets the cancellation token
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = cancellation;
+        self
+    }
+
+    /// Sets the progress callback
+    pub fn with_progress<F: Fn(&BatchProgress) + Send + Sync + 'static>(mut self, progress: F) -> Self {
+        self.progress = Some(Arc::new(progress));
+        self
+    }
+}
+
+impl Default for BatchOptions {
+    fn default() -> Self {
+        Self {
+            workers: 0,
+            mod_name: "None".to_string(),
+            cancellation: CancellationToken::new(),
+            progress: None,
+        }
+    }
+}
+
+/// Rating of one file of a batch
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchRating {
+    /// Final star rating
+    pub rating: f64,
+    /// Number of columns
+    pub column_count: usize,
+    /// Number of notes
+    pub note_count: usize,
+    /// Number of long notes
+    pub long_note_count: usize,
+}
+
+/// Outcome of one file of a batch
+#[derive(Debug)]
+pub struct BatchResult {
+    /// Position of the file in the batch input
+    pub index: usize,
+    /// Rated file
+    pub path: PathBuf,
+    /// Rating, or the error that stopped this file
+    pub outcome: StarRatingResult<BatchRating>,
+}
+
+impl BatchResult {
+    /// Header line matching `to_csv_row`
+    pub const CSV_HEADER: &'static str = "path,status,rating,columns,notes,long_notes,error";
+
+    /// Formats the result as one CSV row (no trailing newline)
+    pub fn to_csv_row(&self) -> String {
+        let path = csv_field(&self.path.to_string_lossy());
+        match &self.outcome {
+            Ok(rating) => format!(
+                "{},ok,{},{},{},{},",
+                path, rating.rating, rating.column_count, rating.note_count, rating.long_note_count
+            ),
+            Err(e) => format!("{},error,,,,,{}", path, csv_field(&e.to_string())),
+        }
+    }
+
+    /// Formats the result as one JSON object (no trailing newline)
+    pub fn to_json(&self) -> String {
+        let path = json_string(&self.path.to_string_lossy());
+        match &self.outcome {
+            Ok(rating) => format!(
+                "{{\"path\":{},\"status\":\"ok\",\"rating\":{},\"columns\":{},\"notes\":{},\"long_notes\":{}}}",
+                path, json_number(rating.rating), rating.column_count, rating.note_count, rating.long_note_count
+            ),
+            Err(e) => format!("{{\"path\":{},\"status\":\"error\",\"error\":{}}}", path, json_string(&e.to_string())),
+        }
+    }
+}
+
+/// Writes results as CSV, header included
+///
+/// # Arguments
+/// * `results` - Results to write, in the desired order
+/// * `writer` - Destination
+pub fn write_csv<'a, W: Write, I: IntoIterator<Item = &'a BatchResult>>(results: I, mut writer: W) -> io::Result<()> {
+    writeln!(writer, "{}", BatchResult::CSV_HEADER)?;
+    for result in results {
+        writeln!(writer, "{}", result.to_csv_row())?;
+    }
+    Ok(())
+}
+
+/// Writes results as JSON Lines (one object per line)
+///
+/// # Arguments
+/// * `results` - Results to write, in the desired order
+/// * `writer` - Destination
+pub fn write_json_lines<'a, W: Write, I: IntoIterator<Item = &'a BatchResult>>(results: I, mut writer: W) -> io::Result<()> {
+    for result in results {
+        writeln!(writer, "{}", result.to_json())?;
+    }
+    Ok(())
+}
+
+/// Quotes a CSV field when it contains a separator, a quote or a line break
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Encodes a JSON string literal
+fn json_string(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Encodes a JSON number (null for NaN and infinities)
+fn json_number(value: f64) -> String {
+    if value.is_finite() { value.to_string() } else { "null".to_string() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::StarRatingError;
+
+    fn rated(path: &str) -> BatchResult {
+        let rating = BatchRating { rating: 4.5, column_count: 7, note_count: 120, long_note_count: 30 };
+        BatchResult { index: 0, path: PathBuf::from(path), outcome: Ok(rating) }
+    }
+
+    fn failed(path: &str, message: &str) -> BatchResult {
+        BatchResult { index: 0, path: PathBuf::from(path), outcome: Err(StarRatingError::MissingData(message.to_string())) }
+    }
+
+    #[test]
+    fn csv_fields_with_separators_or_quotes_are_quoted() {
+        assert_eq!(rated("maps/plain.osu").to_csv_row(), "maps/plain.osu,ok,4.5,7,120,30,");
+        assert_eq!(rated("maps/Artist - Song, \"TV Size\".osu").to_csv_row(), "\"maps/Artist - Song, \"\"TV Size\"\".osu\",ok,4.5,7,120,30,");
+        assert_eq!(
+            failed("a.osu", "no \"notes\", sorry\nat all").to_csv_row(),
+            "a.osu,error,,,,,\"Missing data: no \"\"notes\"\", sorry\nat all\""
+        );
+
+        let mut csv = Vec::new();
+        write_csv([&rated("a.osu"), &failed("b.osu", "x")], &mut csv).unwrap();
+        assert_eq!(String::from_utf8(csv).unwrap(), format!("{}\na.osu,ok,4.5,7,120,30,\nb.osu,error,,,,,Missing data: x\n", BatchResult::CSV_HEADER));
+    }
+
+    #[test]
+    fn json_strings_are_escaped() {
+        assert_eq!(
+            rated("maps\\\"quoted\"\t.osu").to_json(),
+            "{\"path\":\"maps\\\\\\\"quoted\\\"\\t.osu\",\"status\":\"ok\",\"rating\":4.5,\"columns\":7,\"notes\":120,\"long_notes\":30}"
+        );
+        assert_eq!(
+            failed("a.osu", "line\nbreak\u{1}").to_json(),
+            "{\"path\":\"a.osu\",\"status\":\"error\",\"error\":\"Missing data: line\\nbreak\\u0001\"}"
+        );
+        let mut nan = rated("a.osu");
+        nan.outcome.as_mut().unwrap().rating = f64::NAN;
+        assert!(nan.to_json().contains("\"rating\":null"));
+    }
+}
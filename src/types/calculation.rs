@@ -7,6 +7,16 @@ pub struct StarRating {
     pub components: StarRatingComponents,
 }
 
+impl StarRating {
+    /// Rating without detailed components (the pipeline does not extract them yet)
+    pub fn rating_only(rating: f64) -> Self {
+        Self {
+            rating,
+            components: StarRatingComponents::new(Vec::new(), Vec::new(), Vec::new(), Vec::new(), 0.0, 0.0, 0.0),
+        }
+    }
+}
+
 /// Detailed components of star rating calculation
 #[derive(Debug, Clone)]
 pub struct StarRatingComponents {
@@ -49,6 +59,125 @@ impl StarRatingComponents {
     }
 }
 
+/// Mods/rate context of a difficulty calculation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CalculationContext {
+    /// Playback rate applied on top of the map data (1.0 = as parsed)
+    pub clock_rate: f64,
+}
+
+impl CalculationContext {
+    /// Creates a context with the given playback rate
+    pub fn new(clock_rate: f64) -> Self {
+        Self { clock_rate }
+    }
+
+    /// Creates a context from a mod name ("DT", "HT", anything else = no rate change)
+    pub fn from_mod(mod_name: &str) -> Self {
+        Self::new(crate::algorithm::process::normalize::clock_rate(mod_name))
+    }
+}
+
+impl Default for CalculationContext {
+    fn default() -> Self {
+        Self::new(1.0)
+    }
+}
+
+/// Common result of every difficulty calculator
+#[derive(Debug, Clone)]
+pub struct DifficultyResult {
+    /// Name of the calculator that produced the result
+    pub calculator: &'static str,
+    /// Playback rate the map was rated at
+    pub clock_rate: f64,
+    /// Headline rating
+    pub rating: f64,
+    /// Named sub-ratings (e.g. MSD skillsets), empty when the algorithm has none
+    pub skillsets: Vec<(String, f64)>,
+}
+
+/// Result of a strain-based (osu!stable / osu!lazer) star rating calculation
+#[derive(Debug, Clone)]
+pub struct StrainStarRating {
+    /// Final star rating value
+    pub rating: f64,
+    /// Highest strain of each section, in time order
+    pub strain_peaks: Vec<f64>,
+}
+
+/// Etterna MSD skillsets
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Skillset {
+    /// Highest of all skillsets
+    Overall,
+    /// Single-note streams
+    Stream,
+    /// Streams mixed with jumps
+    Jumpstream,
+    /// Streams mixed with hands (3-note chords)
+    Handstream,
+    /// Sustained difficulty over long charts
+    Stamina,
+    /// Fast same-column repetitions
+    JackSpeed,
+    /// Jacked chords
+    Chordjack,
+    /// Irregular rhythms and patterns
+    Technical,
+}
+
+impl Skillset {
+    /// All skillsets, in Etterna display order
+    pub const ALL: [Skillset; 8] = [
+        Skillset::Overall,
+        Skillset::Stream,
+        Skillset::Jumpstream,
+        Skillset::Handstream,
+        Skillset::Stamina,
+        Skillset::JackSpeed,
+        Skillset::Chordjack,
+        Skillset::Technical,
+    ];
+
+    /// Human-readable name of the skillset
+    pub fn name(&self) -> &'static str {
+        match self {
+            Skillset::Overall => "Overall",
+            Skillset::Stream => "Stream",
+            Skillset::Jumpstream => "Jumpstream",
+            Skillset::Handstream => "Handstream",
+            Skillset::Stamina => "Stamina",
+            Skillset::JackSpeed => "JackSpeed",
+            Skillset::Chordjack => "Chordjack",
+            Skillset::Technical => "Technical",
+        }
+    }
+}
+
+/// Result of an MSD-style skillset calculation
+#[derive(Debug, Clone)]
+pub struct MsdRating {
+    /// Music rate the chart was rated at
+    pub rate: f64,
+    /// Accuracy target (e.g. 0.93 or 0.965)
+    pub score_goal: f64,
+    /// Skillset values, indexed like `Skillset::ALL`
+    pub values: [f64; 8],
+}
+
+impl MsdRating {
+    /// Returns the value of a skillset
+    pub fn get(&self, skillset: Skillset) -> f64 {
+        self.values[skillset as usize]
+    }
+
+    /// Returns the overall value
+    pub fn overall(&self) -> f64 {
+        self.get(Skillset::Overall)
+    }
+}
+
 /// Input parameters for star rating calculation
 #[derive(Debug, Clone)]
 pub struct CalculationInput {
@@ -57,15 +186,15 @@ pub struct CalculationInput {
     /// Number of columns
     pub column_count: usize,
     /// Total duration
-    pub total_duration: i64,
+    pub total_duration: f64,
     /// All notes
-    pub notes: Vec<(usize, i64, i64)>,
+    pub notes: Vec<(usize, f64, f64)>,
     /// Notes by column
-    pub notes_by_column: Vec<Vec<(usize, i64, i64)>>,
+    pub notes_by_column: Vec<Vec<(usize, f64, f64)>>,
     /// Long notes
-    pub long_notes: Vec<(usize, i64, i64)>,
+    pub long_notes: Vec<(usize, f64, f64)>,
     /// Tail sequence
-    pub tail_sequence: Vec<(usize, i64, i64)>,
+    pub tail_sequence: Vec<(usize, f64, f64)>,
 }
 
 impl CalculationInput {
@@ -103,11 +232,11 @@ pub struct CalculationState {
     /// A corners
     pub a_corners: Vec<f64>,
     /// Key usage
-    pub key_usage: std::collections::HashMap<usize, Vec<bool>>,
+    pub key_usage: crate::types::ColumnMatrix<bool>,
     /// Active columns
-    pub active_columns: Vec<Vec<usize>>,
+    pub active_columns: crate::types::ActiveColumns,
     /// Key usage 400ms
-    pub key_usage_400: std::collections::HashMap<usize, Vec<f64>>,
+    pub key_usage_400: crate::types::ColumnMatrix<f64>,
     /// Anchor values
     pub anchor: Vec<f64>,
 }
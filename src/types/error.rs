@@ -6,9 +6,9 @@ pub enum StarRatingError {
     /// File reading error
     FileError(std::io::Error),
     /// Parsing error
-    ParseError(String),
+    ParseError(ParseError),
     /// Calculation error
-    CalculationError(String),
+    CalculationError(CalculationError),
     /// Missing data error
     MissingData(String),
     /// Invalid format error
@@ -19,14 +19,25 @@ impl fmt::Display for StarRatingError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             StarRatingError::FileError(e) => write!(f, "File error: {}", e),
-            StarRatingError::ParseError(msg) => write!(f, "Parse error: {}", msg),
-            StarRatingError::CalculationError(msg) => write!(f, "Calculation error: {}", msg),
+            StarRatingError::ParseError(e) => write!(f, "Parse error: {}", e),
+            StarRatingError::CalculationError(e) => write!(f, "Calculation error: {}", e),
             StarRatingError::MissingData(msg) => write!(f, "Missing data: {}", msg),
             StarRatingError::InvalidFormat(msg) => write!(f, "Invalid format: {}", msg),
         }
     }
 }
 
+impl StarRatingError {
+    /// I/O error prefixed with what was being read, e.g. the file path
+    ///
+    /// The error kind is kept, so `NotFound` and the like can still be told apart.
+    pub fn io(context: impl fmt::Display, error: std::io::Error) -> Self {
+        StarRatingError::FileError(std::io::Error::new(error.kind(), format!("{}: {}", context, error)))
+    }
+}
+
+// No `source()`: the message already includes the wrapped error, and reporters
+// walking the chain would print it twice
 impl std::error::Error for StarRatingError {}
 
 /// Automatic conversion from io::Error
@@ -39,6 +50,27 @@ impl From<std::io::Error> for StarRatingError {
 /// Standard result type for the application
 pub type StarRatingResult<T> = Result<T, StarRatingError>;
 
+/// Position in the parsed content (1-based line and column)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Location {
+    /// Line number, 1-based: the first line of the content is line 1
+    pub line: usize,
+    /// Column in characters (not bytes), 1-based: the first character of the line is column 1
+    pub column: usize,
+}
+
+impl Location {
+    pub fn new(line: usize, column: usize) -> Self {
+        Self { line, column }
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
+}
+
 /// Parsing-specific errors
 #[derive(Debug)]
 pub enum ParseError {
@@ -52,6 +84,31 @@ pub enum ParseError {
     InvalidValue(String, String),
     /// Insufficient data
     InsufficientData(String),
+    /// Error raised at a given position of the content
+    Located(Location, Box<ParseError>),
+}
+
+impl ParseError {
+    /// Attaches the position where the error was raised
+    pub fn at(self, location: Location) -> Self {
+        ParseError::Located(location, Box::new(self))
+    }
+
+    /// Position of the error, when known
+    pub fn location(&self) -> Option<Location> {
+        match self {
+            ParseError::Located(location, _) => Some(*location),
+            _ => None,
+        }
+    }
+
+    /// The error itself, without its position
+    pub fn kind(&self) -> &ParseError {
+        match self {
+            ParseError::Located(_, error) => error.kind(),
+            error => error,
+        }
+    }
 }
 
 impl fmt::Display for ParseError {
@@ -62,10 +119,12 @@ impl fmt::Display for ParseError {
             ParseError::MissingSection(section) => write!(f, "Missing section: {}", section),
             ParseError::InvalidValue(field, value) => write!(f, "Invalid value for {}: {}", field, value),
             ParseError::InsufficientData(msg) => write!(f, "Insufficient data: {}", msg),
+            ParseError::Located(location, error) => write!(f, "{} ({})", error, location),
         }
     }
 }
 
+// Like `StarRatingError`, `Located` prints its inner error and reports no source
 impl std::error::Error for ParseError {}
 
 /// Calculation-specific errors
@@ -81,6 +140,8 @@ pub enum CalculationError {
     IndexOutOfBounds(String, usize, usize),
     /// Invalid number (NaN or infinity)
     InvalidNumber(String, f64),
+    /// Job stopped before producing a result (panic, closed or failed task)
+    Aborted(String),
 }
 
 impl fmt::Display for CalculationError {
@@ -91,6 +152,7 @@ impl fmt::Display for CalculationError {
             CalculationError::EmptyData(context) => write!(f, "Empty data in: {}", context),
             CalculationError::IndexOutOfBounds(context, index, max) => write!(f, "Index {} out of bounds in {} (max: {})", index, context, max),
             CalculationError::InvalidNumber(context, value) => write!(f, "Invalid number in {}: {}", context, value),
+            CalculationError::Aborted(reason) => write!(f, "Aborted: {}", reason),
         }
     }
 }
@@ -100,13 +162,28 @@ impl std::error::Error for CalculationError {}
 /// Conversion from ParseError to StarRatingError
 impl From<ParseError> for StarRatingError {
     fn from(error: ParseError) -> Self {
-        StarRatingError::ParseError(error.to_string())
+        StarRatingError::ParseError(error)
     }
 }
 
 /// Conversion from CalculationError to StarRatingError
 impl From<CalculationError> for StarRatingError {
     fn from(error: CalculationError) -> Self {
-        StarRatingError::CalculationError(error.to_string())
+        StarRatingError::CalculationError(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error;
+
+    #[test]
+    fn wrapped_errors_are_printed_once() {
+        let error: StarRatingError = ParseError::InvalidValue("CircleSize".to_string(), "0".to_string())
+            .at(Location::new(7, 1))
+            .into();
+        assert_eq!(error.to_string(), "Parse error: Invalid value for CircleSize: 0 (line 7, column 1)");
+        assert!(error.source().is_none());
     }
 }
@@ -1,4 +1,8 @@
 use crate::types::note::Note;
+use crate::types::parse::{ParseWarning, TimePrecision};
+
+/// Highest key count the rating supports, shared by the parser and the calculation
+pub const MAX_KEY_COUNT: usize = 10;
 
 /// Parsed osu! map data
 #[derive(Debug, Clone)]
@@ -8,7 +12,7 @@ pub struct MapData {
     /// Number of columns in the map
     pub column_count: usize,
     /// Total duration in milliseconds
-    pub total_duration: i64,
+    pub total_duration: f64,
     /// All notes sorted by hit time
     pub notes: Vec<Note>,
     /// Notes organized by column
@@ -21,6 +25,23 @@ pub struct MapData {
     pub long_notes_by_column: Vec<Vec<Note>>,
     /// Overall difficulty of the map
     pub overall_difficulty: f64,
+    /// Malformed values replaced while parsing (lenient mode)
+    pub warnings: Vec<ParseWarning>,
+    /// Resolution of the note times, kept through rate changes
+    pub time_precision: TimePrecision,
+    /// Playback rate applied to the note times (1.0 when unchanged)
+    pub clock_rate: f64,
+    /// Sections of the parsed .osu file, in file order; empty for maps built in code
+    pub source_sections: Vec<OsuSection>,
+}
+
+/// Raw section of a .osu file, kept so the map can be written back
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OsuSection {
+    /// Header such as "[General]"; empty for the lines before the first header
+    pub header: String,
+    /// Lines of the section, without line endings ([HitObjects] keeps none)
+    pub lines: Vec<String>,
 }
 
 impl MapData {
@@ -29,13 +50,17 @@ impl MapData {
         Self {
             hit_leniency: 0.0,
             column_count: 0,
-            total_duration: 0,
+            total_duration: 0.0,
             notes: Vec::new(),
             notes_by_column: Vec::new(),
             long_notes: Vec::new(),
             tail_sequence: Vec::new(),
             long_notes_by_column: Vec::new(),
             overall_difficulty: 0.0,
+            warnings: Vec::new(),
+            time_precision: TimePrecision::default(),
+            clock_rate: 1.0,
+            source_sections: Vec::new(),
         }
     }
 
@@ -0,0 +1,64 @@
+use super::batch::BatchRating;
+use super::error::StarRatingResult;
+
+/// Game mode number of osu!mania in the [General] Mode field
+pub const MODE_MANIA: i32 = 3;
+
+/// [General] and [Metadata] fields of a .osu file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BeatmapMetadata {
+    /// Game mode (0 osu!, 1 taiko, 2 catch, 3 mania); 0 when the field is absent
+    pub mode: i32,
+    /// Song title (romanised)
+    pub title: String,
+    /// Song artist (romanised)
+    pub artist: String,
+    /// Mapper
+    pub creator: String,
+    /// Difficulty name
+    pub version: String,
+}
+
+impl BeatmapMetadata {
+    /// Returns true for an osu!mania difficulty
+    pub fn is_mania(&self) -> bool {
+        self.mode == MODE_MANIA
+    }
+}
+
+/// One mania difficulty of a .osz archive
+#[derive(Debug)]
+pub struct MapsetDifficulty {
+    /// Name of the .osu entry in the archive
+    pub file_name: String,
+    /// Metadata read from the entry
+    pub metadata: BeatmapMetadata,
+    /// Rating, or the error that prevented it
+    pub outcome: StarRatingResult<BatchRating>,
+}
+
+/// Ratings of the mania difficulties of a .osz archive
+#[derive(Debug, Default)]
+pub struct MapsetRating {
+    /// Song title, from the first mania difficulty
+    pub title: String,
+    /// Song artist, from the first mania difficulty
+    pub artist: String,
+    /// Mapper, from the first mania difficulty
+    pub creator: String,
+    /// Mania difficulties in archive order
+    pub difficulties: Vec<MapsetDifficulty>,
+    /// .osu entries of other game modes, left out
+    pub skipped: Vec<String>,
+}
+
+impl MapsetRating {
+    /// Highest rating among the difficulties that were rated
+    pub fn max_rating(&self) -> Option<f64> {
+        self.difficulties
+            .iter()
+            .filter_map(|difficulty| difficulty.outcome.as_ref().ok())
+            .map(|rating| rating.rating)
+            .reduce(f64::max)
+    }
+}
@@ -0,0 +1,189 @@
+use super::error::{CalculationError, StarRatingResult};
+
+/// Dense per-column values over the corner timeline, stored column-major
+///
+/// Each column is a contiguous slice of `rows` values, so per-column passes
+/// (two-pointer sweeps, smoothing) walk memory linearly.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnMatrix<T> {
+    columns: usize,
+    rows: usize,
+    data: Vec<T>,
+}
+
+impl<T: Copy> ColumnMatrix<T> {
+    /// Creates a matrix filled with `value`
+    pub fn new(columns: usize, rows: usize, value: T) -> Self {
+        Self {
+            columns,
+            rows,
+            data: vec![value; columns * rows],
+        }
+    }
+
+    /// Wraps existing column-major storage of `columns * rows` values
+    pub fn from_vec(columns: usize, rows: usize, data: Vec<T>) -> Self {
+        assert_eq!(data.len(), columns * rows, "ColumnMatrix storage must hold columns * rows values");
+        Self { columns, rows, data }
+    }
+
+    /// Releases the column-major storage
+    pub fn into_vec(self) -> Vec<T> {
+        self.data
+    }
+
+    /// Number of columns
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+
+    /// Number of rows (corners) per column
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// Returns the value of a column at a row
+    #[inline]
+    pub fn get(&self, column: usize, row: usize) -> T {
+        self.data[column * self.rows + row]
+    }
+
+    /// Sets the value of a column at a row
+    #[inline]
+    pub fn set(&mut self, column: usize, row: usize, value: T) {
+        self.data[column * self.rows + row] = value;
+    }
+
+    /// Returns the values of one column
+    #[inline]
+    pub fn column(&self, column: usize) -> &[T] {
+        &self.data[column * self.rows..(column + 1) * self.rows]
+    }
+
+    /// Returns the values of one column, mutably
+    #[inline]
+    pub fn column_mut(&mut self, column: usize) -> &mut [T] {
+        &mut self.data[column * self.rows..(column + 1) * self.rows]
+    }
+
+    /// Iterates over the columns in order
+    pub fn iter_columns(&self) -> impl Iterator<Item = &[T]> {
+        (0..self.columns).map(move |col| self.column(col))
+    }
+
+    /// Iterates over the columns in order, mutably (no column is yielded when `rows` is 0)
+    pub fn columns_mut(&mut self) -> std::slice::ChunksMut<'_, T> {
+        self.data.chunks_mut(self.rows.max(1))
+    }
+
+    /// Raw column-major storage
+    pub fn as_slice(&self) -> &[T] {
+        &self.data
+    }
+}
+
+#[cfg(feature = "parallel")]
+impl<T: Copy + Send + Sync> ColumnMatrix<T> {
+    /// Parallel iterator over the columns (no column is yielded when `rows` is 0)
+    pub fn par_columns(&self) -> rayon::slice::Chunks<'_, T> {
+        use rayon::slice::ParallelSlice;
+        self.data.par_chunks(self.rows.max(1))
+    }
+
+    /// Parallel iterator over the columns, mutably (no column is yielded when `rows` is 0)
+    pub fn par_columns_mut(&mut self) -> rayon::slice::ChunksMut<'_, T> {
+        use rayon::slice::ParallelSliceMut;
+        self.data.par_chunks_mut(self.rows.max(1))
+    }
+}
+
+/// Active columns at each corner, as one bitmask per corner (bit `col` set = active)
+///
+/// Supports up to 64 columns.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ActiveColumns {
+    masks: Vec<u64>,
+}
+
+impl ActiveColumns {
+    /// Maximum number of columns a mask can hold
+    pub const MAX_COLUMNS: usize = 64;
+
+    /// Builds the masks from boolean key usage
+    ///
+    /// # Returns
+    /// The masks, or `IndexOutOfBounds` for more than `MAX_COLUMNS` columns
+    pub fn from_key_usage(key_usage: &ColumnMatrix<bool>) -> StarRatingResult<Self> {
+        if key_usage.columns() > Self::MAX_COLUMNS {
+            return Err(CalculationError::IndexOutOfBounds(
+                "ActiveColumns column count".to_string(),
+                key_usage.columns(),
+                Self::MAX_COLUMNS,
+            ).into());
+        }
+        let mut masks = vec![0u64; key_usage.rows()];
+        for (col, usage) in key_usage.iter_columns().enumerate() {
+            let bit = 1u64 << col;
+            for (mask, &used) in masks.iter_mut().zip(usage.iter()) {
+                if used {
+                    *mask |= bit;
+                }
+            }
+        }
+        Ok(Self { masks })
+    }
+
+    /// Number of corners
+    pub fn len(&self) -> usize {
+        self.masks.len()
+    }
+
+    /// Returns true if there are no corners
+    pub fn is_empty(&self) -> bool {
+        self.masks.is_empty()
+    }
+
+    /// Bitmask of the active columns at a corner
+    #[inline]
+    pub fn mask(&self, corner: usize) -> u64 {
+        self.masks[corner]
+    }
+
+    /// Returns true if `column` is active at `corner` (false outside the timeline)
+    #[inline]
+    pub fn is_active(&self, corner: usize, column: usize) -> bool {
+        column < Self::MAX_COLUMNS && self.masks.get(corner).is_some_and(|&mask| mask & (1u64 << column) != 0)
+    }
+
+    /// Number of active columns at a corner
+    #[inline]
+    pub fn count(&self, corner: usize) -> usize {
+        self.masks[corner].count_ones() as usize
+    }
+
+    /// Active columns at a corner, in ascending order
+    #[inline]
+    pub fn iter(&self, corner: usize) -> ActiveColumnIter {
+        ActiveColumnIter { mask: self.masks[corner] }
+    }
+}
+
+/// Iterator over the set bits of an active column mask
+#[derive(Debug, Clone)]
+pub struct ActiveColumnIter {
+    mask: u64,
+}
+
+impl Iterator for ActiveColumnIter {
+    type Item = usize;
+
+    #[inline]
+    fn next(&mut self) -> Option<usize> {
+        if self.mask == 0 {
+            return None;
+        }
+        let col = self.mask.trailing_zeros() as usize;
+        self.mask &= self.mask - 1;
+        Some(col)
+    }
+}
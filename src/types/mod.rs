@@ -7,9 +7,21 @@ pub mod note;
 pub mod map;
 pub mod calculation;
 pub mod error;
+pub mod analysis;
+pub mod matrix;
+pub mod batch;
+pub mod parse;
+pub mod mapset;
+pub mod stepmania;
 
 // Re-export commonly used types
 pub use note::*;
 pub use map::*;
 pub use calculation::*;
 pub use error::*;
+pub use analysis::*;
+pub use matrix::*;
+pub use batch::*;
+pub use parse::*;
+pub use mapset::*;
+pub use stepmania::*;
@@ -1,19 +1,19 @@
 use std::fmt;
 
 /// Represents a note in an osu! map
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
 pub struct Note {
     /// Column index (0-based)
     pub column: usize,
-    /// Hit time in milliseconds
-    pub hit_time: i64,
+    /// Hit time in milliseconds (may be fractional)
+    pub hit_time: f64,
     /// End time in milliseconds (-1 for single notes)
-    pub tail_time: i64,
+    pub tail_time: f64,
 }
 
 impl Note {
     /// Creates a new note
-    pub fn new(column: usize, hit_time: i64, tail_time: i64) -> Self {
+    pub fn new(column: usize, hit_time: f64, tail_time: f64) -> Self {
         Self {
             column,
             hit_time,
@@ -22,16 +22,16 @@ impl Note {
     }
 
     /// Creates a single note (no tail)
-    pub fn simple(column: usize, hit_time: i64) -> Self {
+    pub fn simple(column: usize, hit_time: f64) -> Self {
         Self {
             column,
             hit_time,
-            tail_time: -1,
+            tail_time: -1.0,
         }
     }
 
     /// Creates a long note
-    pub fn long_note(column: usize, hit_time: i64, tail_time: i64) -> Self {
+    pub fn long_note(column: usize, hit_time: f64, tail_time: f64) -> Self {
         Self {
             column,
             hit_time,
@@ -41,15 +41,15 @@ impl Note {
 
     /// Returns true if this is a long note
     pub fn is_long_note(&self) -> bool {
-        self.tail_time >= 0
+        self.tail_time >= 0.0
     }
 
     /// Returns the duration of the note in milliseconds
-    pub fn duration(&self) -> i64 {
+    pub fn duration(&self) -> f64 {
         if self.is_long_note() {
             self.tail_time - self.hit_time
         } else {
-            0
+            0.0
         }
     }
 }
@@ -70,7 +70,7 @@ impl fmt::Display for Note {
 #[derive(Debug, Clone)]
 pub struct LongNoteRepresentation {
     /// Time points where values change
-    pub points: Vec<i64>,
+    pub points: Vec<f64>,
     /// Cumulative sum of values
     pub cumulative_sum: Vec<f64>,
     /// Values at each point
@@ -79,7 +79,7 @@ pub struct LongNoteRepresentation {
 
 impl LongNoteRepresentation {
     /// Creates a new sparse representation
-    pub fn new(points: Vec<i64>, cumulative_sum: Vec<f64>, values: Vec<f64>) -> Self {
+    pub fn new(points: Vec<f64>, cumulative_sum: Vec<f64>, values: Vec<f64>) -> Self {
         Self {
             points,
             cumulative_sum,
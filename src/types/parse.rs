@@ -0,0 +1,149 @@
+use std::fmt;
+use super::error::Location;
+
+/// How the parser handles malformed values
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ParseMode {
+    /// Rejects the map on the first malformed value
+    Strict,
+    /// Replaces malformed values by a fallback and records a warning
+    #[default]
+    Lenient,
+}
+
+/// Resolution of note times
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimePrecision {
+    /// Keeps fractional milliseconds, also through rate changes
+    #[default]
+    Fractional,
+    /// Truncates parsed times to whole milliseconds and floors them after rate
+    /// changes, as earlier versions did (for rating parity)
+    Integer,
+}
+
+impl TimePrecision {
+    /// Time as stored after parsing
+    pub fn parsed(self, time: f64) -> f64 {
+        match self {
+            TimePrecision::Fractional => time,
+            TimePrecision::Integer => time.trunc(),
+        }
+    }
+
+    /// Time played at `rate` (e.g. 1.5 for DT)
+    pub fn rated(self, time: f64, rate: f64) -> f64 {
+        match self {
+            TimePrecision::Fractional => time / rate,
+            TimePrecision::Integer => (time / rate).floor(),
+        }
+    }
+}
+
+/// Options of the .osu parser
+#[derive(Debug, Clone)]
+pub struct ParseOptions {
+    /// Handling of malformed values (lenient by default)
+    pub mode: ParseMode,
+    /// Resolution of note times (fractional by default)
+    pub time_precision: TimePrecision,
+    /// Stops reading once [General] shows the map is not osu!mania (off by default)
+    pub mania_only: bool,
+    /// Keeps the sections other than [HitObjects] in `MapData::source_sections`, so
+    /// the writer can reproduce them (on by default; rating-only runs such as batches
+    /// and .osz archives turn it off)
+    pub keep_sections: bool,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            mode: ParseMode::default(),
+            time_precision: TimePrecision::default(),
+            mania_only: false,
+            keep_sections: true,
+        }
+    }
+}
+
+impl ParseOptions {
+    /// Creates lenient options
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Options rejecting malformed values
+    pub fn strict() -> Self {
+        Self::new().with_mode(ParseMode::Strict)
+    }
+
+    /// Sets the parsing mode
+    pub fn with_mode(mut self, mode: ParseMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Sets the resolution of note times
+    pub fn with_time_precision(mut self, time_precision: TimePrecision) -> Self {
+        self.time_precision = time_precision;
+        self
+    }
+
+    /// Sets whether the original sections are kept for the writer
+    pub fn with_keep_sections(mut self, keep_sections: bool) -> Self {
+        self.keep_sections = keep_sections;
+        self
+    }
+
+    /// Sets whether reading stops at a map of another game mode
+    ///
+    /// The parser then returns right after [General] with only the metadata read, and
+    /// `Parser::metadata` tells the map apart.
+    pub fn with_mania_only(mut self, mania_only: bool) -> Self {
+        self.mania_only = mania_only;
+        self
+    }
+}
+
+/// Malformed value replaced by a fallback in lenient mode
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseWarning {
+    /// Position of the value
+    pub location: Location,
+    /// Field the value belongs to (e.g. "time", "OverallDifficulty")
+    pub field: String,
+    /// Value as written in the file (empty when the value is missing)
+    pub value: String,
+    /// Value used instead
+    pub fallback: String,
+}
+
+impl fmt::Display for ParseWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.value.is_empty() {
+            return write!(f, "{}: missing {}, using {}", self.location, self.field, self.fallback);
+        }
+        write!(f, "{}: invalid {} '{}', using {}", self.location, self.field, self.value, self.fallback)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fractional_times_are_kept() {
+        assert_eq!(TimePrecision::Fractional.parsed(1000.75), 1000.75);
+        assert_eq!(TimePrecision::Fractional.rated(1000.0, 1.5), 1000.0 / 1.5);
+        assert_eq!(TimePrecision::Fractional.rated(1000.0, 0.75), 1000.0 / 0.75);
+    }
+
+    #[test]
+    fn integer_times_are_truncated_then_floored() {
+        assert_eq!(TimePrecision::Integer.parsed(1000.75), 1000.0);
+        assert_eq!(TimePrecision::Integer.parsed(-10.5), -10.0);
+        assert_eq!(TimePrecision::Integer.rated(1000.0, 1.5), 666.0);
+        assert_eq!(TimePrecision::Integer.rated(1000.0, 0.75), 1333.0);
+        assert_eq!(TimePrecision::Integer.rated(-100.0, 1.5), -67.0);
+    }
+}
@@ -0,0 +1,41 @@
+use super::map::MapData;
+use super::note::Note;
+
+/// Overall difficulty given to StepMania charts, which carry none
+///
+/// Matches the osu!mania 300 window (64 - 3 * OD ms) to the 45 ms Perfect window
+/// of judge 4.
+pub const STEPMANIA_OVERALL_DIFFICULTY: f64 = 19.0 / 3.0;
+
+/// One chart (difficulty) of a .sm/.ssc simfile
+#[derive(Debug, Clone)]
+pub struct StepmaniaChart {
+    /// Steps type: "dance-single" (4K) or "dance-double" (8K)
+    pub steps_type: String,
+    /// Difficulty slot, e.g. "Hard" or "Challenge"
+    pub difficulty: String,
+    /// Meter (block rating) written by the stepper; 0 when missing
+    pub meter: i32,
+    /// Description or chart author
+    pub description: String,
+    /// Taps, holds and rolls, ready for `calculate`
+    pub map_data: MapData,
+    /// Mines, as simple notes
+    pub mines: Vec<Note>,
+    /// Fake notes (not judged), as simple notes
+    pub fakes: Vec<Note>,
+}
+
+/// Charts of a .sm/.ssc simfile
+#[derive(Debug, Clone, Default)]
+pub struct StepmaniaSimfile {
+    /// Song title
+    pub title: String,
+    /// Song artist
+    pub artist: String,
+    /// dance-single and dance-double charts, in file order (other steps types are left out)
+    pub charts: Vec<StepmaniaChart>,
+    /// dance-single and dance-double charts left out because they hold no tap, hold or
+    /// roll (empty, mines or fakes only), as "steps type difficulty"
+    pub skipped: Vec<String>,
+}